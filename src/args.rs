@@ -1,36 +1,246 @@
 //! This module defines the command-line arguments for the application.
 //! It uses the `clap` crate to parse and validate user input.
 
-use clap::Parser;
+use crate::buster::{HeaderInjectMode, RetryPolicy};
+use crate::notify::DEFAULT_NOTIFY_CODES;
+use crate::output::{ReportSelection, SortKey};
+use crate::parser::{HeaderFileFormat, ResolveOverride, UnicodeForm, WordTransform};
+use crate::reauth::{DEFAULT_REAUTH_THRESHOLD, ReauthTrigger, parse_reauth_trigger};
+use crate::waf::DEFAULT_WAF_CODES;
+use clap::{ArgGroup, Args as _, Parser, Subcommand};
 
 /// A fast, concurrent, and feature-rich directory and file buster.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
+#[command(group(ArgGroup::new("word_source").args(["word_list", "import_burp", "from_report"]).required(true)))]
 pub struct Args {
-    /// The base URL to scan.
+    /// Path to a TOML config file whose keys mirror these options. Command-line flags take precedence.
+    /// `~/.config/dirbuster-rs/config.toml` is loaded first when it exists.
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Print the effective configuration, with secrets masked, and exit.
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// The base URL to scan. Without a scheme, HTTPS is tried first and then HTTP.
     #[arg(short, long)]
     pub url: String,
 
+    /// Path under the base URL to scan, e.g. `api/v2`. Wildcard probing still uses the base URL.
+    #[arg(long, default_value = "")]
+    pub path_prefix: String,
+
     /// The number of concurrent threads to use for scanning.
     #[arg(short, long, default_value_t = 20)]
     pub threads: usize,
 
-    /// Path to the wordlist file.
-    #[arg(short, long)]
-    pub word_list: String,
+    /// The most concurrent requests sent to any one host, within the --threads total.
+    /// Defaults to --threads.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads_per_host: Option<u32>,
+
+    /// Tune the concurrency during the scan from the error rate, 429s and p95 latency,
+    /// starting at 5 and never exceeding --threads.
+    #[arg(long)]
+    pub auto_threads: bool,
+
+    /// Paths to the wordlist files. Several lists are scanned one after the other.
+    #[arg(short, long, num_args = 1..)]
+    pub word_list: Vec<String>,
+
+    /// Skip word list lines starting with `#` and cut off inline comments after ` # `.
+    #[arg(long)]
+    pub strip_comments: bool,
+
+    /// Remove duplicate words from the word list, keeping the first occurrence.
+    #[arg(long)]
+    pub dedup: bool,
+
+    /// Pipe the word list through this shell command, one word per line, and scan the words
+    /// it prints instead, e.g. `tr a-z A-Z`. It may print more or fewer words than it reads.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        conflicts_with = "wordlist_transform_builtin"
+    )]
+    pub wordlist_transform: Option<String>,
+
+    /// Transform every word natively before scanning: base64, md5, sha1 or reverse.
+    #[arg(long, value_enum, value_name = "TRANSFORM")]
+    pub wordlist_transform_builtin: Option<WordTransform>,
+
+    /// Use the paths of a Burp Suite XML export under the base URL as the word list.
+    #[arg(long, value_name = "FILE")]
+    pub import_burp: Option<String>,
+
+    /// Use the words of a previous JSON report as the word list, e.g. to verify findings or
+    /// scan failed words again. Which entries are used is chosen with --select.
+    #[arg(long, value_name = "FILE")]
+    pub from_report: Option<String>,
+
+    /// The --from-report entries to scan: `all`, `found`, `errors`, or `status:403` and
+    /// `status:400-499` for status codes.
+    #[arg(long, value_name = "SELECTION", default_value = "all", value_parser = crate::parser::parse_report_selection, requires = "from_report")]
+    pub select: ReportSelection,
+
+    /// Skip the words a stopped scan already processed, as saved in its progress file.
+    #[arg(long, value_name = "FILE")]
+    pub resume: Option<String>,
+
+    /// Where to save the processed words when the scan is stopped before the end
+    /// [default: `<scan-id>.progress.json`].
+    #[arg(long, value_name = "FILE")]
+    pub progress_file: Option<String>,
+
+    /// Skip words an ffuf JSON output file already has results for.
+    #[arg(long, value_name = "FILE")]
+    pub import_ffuf_json: Option<String>,
+
+    /// Skip words a gobuster output file already has results for.
+    #[arg(long, value_name = "FILE")]
+    pub import_gobuster: Option<String>,
+
+    /// Skip words starting with this prefix (e.g. `#`). Can be given multiple times.
+    #[arg(long = "wordlist-skip-starts-with", value_name = "PREFIX")]
+    pub wordlist_skip_prefixes: Vec<String>,
+
+    /// Skip words that fully match this regex.
+    #[arg(long, value_name = "PATTERN")]
+    pub wordlist_skip_regex: Option<String>,
+
+    /// Skip words shorter than this many characters.
+    #[arg(long, value_name = "CHARS")]
+    pub wordlist_min_length: Option<usize>,
+
+    /// Skip words longer than this many characters.
+    #[arg(long, value_name = "CHARS")]
+    pub wordlist_max_length: Option<usize>,
 
     /// Timeout in seconds for each HTTP request.
     #[arg(long, default_value_t = 5)]
     pub timeout: u64,
 
+    /// Print redirect (3xx) results to the console, with their target location.
+    #[arg(long)]
+    pub show_redirects: bool,
+
+    /// Report redirects (3xx) as results with their target location, instead of following
+    /// them.
+    #[arg(long)]
+    pub no_follow_redirects: bool,
+
     /// Display only successful results (status codes 200-299).
     #[arg(long)]
     pub only_success: bool,
 
+    /// Exit with code 1 when the scan completes without any findings.
+    #[arg(long)]
+    pub fail_on_empty: bool,
+
+    /// Print only one plain `<status> <size> <url>` line per found result, with no banner,
+    /// progress bar or summary. Failed requests and fatal errors are still reported on stderr.
+    #[arg(short = 's', long)]
+    pub quiet: bool,
+
+    /// Disable colored output. Colors are also disabled by the NO_COLOR environment variable
+    /// and when stdout is not a terminal.
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Increase log verbosity on stderr: -v for debug, -vv for trace, -vvv to include dependencies.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Write debug logs (request attempts, retries, backoff, wildcard decisions) to this file.
+    #[arg(long)]
+    pub log_file: Option<String>,
+
+    /// Print the URL and headers of the first requests that would be sent, plus the total
+    /// number of paths, and exit without any network traffic.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Number of planned requests to print with --dry-run.
+    #[arg(long, default_value_t = 20, requires = "dry_run")]
+    pub dry_run_count: usize,
+
+    /// Replace matches of this regex in words, URLs and logs with `[REDACTED]`, in the
+    /// console, output files and notifications. Can be given multiple times.
+    #[arg(long = "redact", value_name = "PATTERN")]
+    pub redact_patterns: Vec<String>,
+
+    /// Revalidate responses cached by previous scans with If-None-Match/If-Modified-Since,
+    /// and reuse them on 304 Not Modified instead of downloading the body again.
+    #[arg(long)]
+    pub use_cache: bool,
+
+    /// File the --use-cache responses are loaded from and saved to.
+    #[arg(long, default_value = crate::cache::DEFAULT_CACHE_FILE, requires = "use_cache")]
+    pub cache_file: String,
+
+    /// Fetch /robots.txt before the scan and scan its Disallow, Allow and Sitemap paths
+    /// ahead of the word list.
+    #[arg(long)]
+    pub parse_robots: bool,
+
+    /// Fetch /sitemap.xml (or /sitemap.xml.gz) before the scan, following a sitemap index one
+    /// level deep, and scan the paths of its pages on the target host ahead of the word list.
+    #[arg(long)]
+    pub parse_sitemap: bool,
+
+    /// The most paths taken from the sitemap with --parse-sitemap.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 5000,
+        requires = "parse_sitemap"
+    )]
+    pub sitemap_max_urls: usize,
+
+    /// Follow the same-host links of found HTML pages up to N levels deep, scanning the
+    /// linked paths the word list does not have after the word list.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub spider_depth: Option<u32>,
+
+    /// The most paths queued by following links with --spider-depth.
+    #[arg(long, value_name = "N", default_value_t = crate::spider::DEFAULT_SPIDER_MAX_URLS, requires = "spider_depth")]
+    pub spider_max_urls: usize,
+
+    /// Harvest identifier-like words from the bodies of found pages into a secondary word list.
+    #[arg(long)]
+    pub extract_words: bool,
+
+    /// File the --extract-words word list is written to after the scan.
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = "extracted-words.txt",
+        requires = "extract_words"
+    )]
+    pub extracted_words_output: String,
+
     /// Hide the progress bar during the scan.
     #[arg(long)]
     pub no_progress: bool,
 
+    /// Percent-encode every non-alphanumeric character of a word before requesting it.
+    #[arg(long)]
+    pub encode_url: bool,
+
+    /// Percent-encode words twice, so `/` becomes `%252F`.
+    #[arg(long)]
+    pub encode_double: bool,
+
+    /// Normalize words to this Unicode form (nfc, nfd) before any percent-encoding.
+    #[arg(long, value_enum)]
+    pub encode_unicode: Option<UnicodeForm>,
+
+    /// Append a random `?_cb=`, `#` or `;sessionid=` suffix to request URLs, to get past
+    /// caches. Off by default, as strict routers answer such URLs with 404.
+    #[arg(long)]
+    pub cache_bust: bool,
+
     /// Minimum time in milliseconds to delay between requests.
     #[arg(long, default_value_t = 0)]
     pub delay_min: u64,
@@ -39,10 +249,93 @@ pub struct Args {
     #[arg(long, default_value_t = 0)]
     pub delay_max: u64,
 
+    /// Limit the scan to this many requests per second across all threads. Fractional rates
+    /// such as 0.5 are allowed.
+    #[arg(long, value_name = "RPS", value_parser = crate::parser::parse_rate)]
+    pub rate: Option<f64>,
+
     /// Number of times to retry a failed request.
     #[arg(long, default_value_t = 2)]
     pub retries: usize,
 
+    /// What is retried: status codes, status ranges, and `conn`, `timeout` or `dns` for
+    /// network errors, comma-separated.
+    #[arg(long, value_name = "LIST", default_value = "429,500-599,conn,timeout,dns", value_parser = crate::parser::parse_retry_policy)]
+    pub retry_on: RetryPolicy,
+
+    /// After the scan, request the words that failed with a network error once more at a
+    /// quarter of the concurrency. Their errors are only reported if they fail again.
+    #[arg(long)]
+    pub retry_failed_pass: bool,
+
+    /// The concurrency of the --retry-failed-pass pass, within --threads. Defaults to a
+    /// quarter of --threads.
+    #[arg(long, value_name = "N", requires = "retry_failed_pass", value_parser = clap::value_parser!(u32).range(1..))]
+    pub retry_pass_threads: Option<u32>,
+
+    /// The longest wait, in seconds, honored from a `Retry-After` header on a 429 or 503
+    /// response. Longer requested waits are cut to this.
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub max_retry_after: u64,
+
+    /// Wait as the rate limit headers of any response ask: for `Retry-After`, or until
+    /// `X-RateLimit-Reset` once `X-RateLimit-Remaining` reaches 0. Capped by --max-retry-after.
+    #[arg(long, alias = "rate-limit-header")]
+    pub rate_limit_headers: bool,
+
+    /// Stop the scan after this many consecutive failed requests.
+    #[arg(long)]
+    pub max_errors: Option<usize>,
+
+    /// Stop the scan after this many failed requests in total.
+    #[arg(long)]
+    pub max_total_errors: Option<usize>,
+
+    /// Stop the scan when at least this percentage (1-100) of the last 50 requests failed.
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub max_error_rate: Option<u8>,
+
+    /// Stop the scan after this many seconds, saving the results so far.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_scan_time: Option<u64>,
+
+    /// Stop the scan once this many body bytes were downloaded, saving the results so far.
+    /// Accepts K, M and G suffixes, e.g. `500M`.
+    #[arg(long, value_name = "BYTES", value_parser = crate::parser::parse_byte_size)]
+    pub max_bytes: Option<u64>,
+
+    /// Stop the scan once this many results were found, letting the requests in flight finish.
+    #[arg(long, value_name = "N", alias = "max-results", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub limit: Option<usize>,
+
+    /// Read at most this many bytes of each response body, so huge files cannot stall the
+    /// scan. Accepts K, M and G suffixes; 0 reads bodies in full.
+    #[arg(long, value_name = "BYTES", default_value = "5M", value_parser = crate::parser::parse_body_size_cap)]
+    pub max_body_size: u64,
+
+    /// Pause the scan when a WAF seems to block it: once --waf-threshold percent of the last
+    /// 50 requests got a --waf-codes status or failed, new requests wait --waf-cooldown
+    /// seconds and the scan resumes slowly.
+    #[arg(long)]
+    pub waf_detect: bool,
+
+    /// Status codes counted as blocked by --waf-detect.
+    #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_WAF_CODES, requires = "waf_detect")]
+    pub waf_codes: Vec<u16>,
+
+    /// The percentage (1-100) of blocked requests among the last 50 that starts a cool-down.
+    #[arg(long, value_name = "PERCENT", default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100), requires = "waf_detect")]
+    pub waf_threshold: u8,
+
+    /// How long, in seconds, new requests are paused when a block is detected.
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 30,
+        requires = "waf_detect"
+    )]
+    pub waf_cooldown: u64,
+
     /// Rotate User-Agent for each request from the user agents file or through pre-set defaults.
     #[arg(long)]
     pub rotate_user_agent: bool,
@@ -51,27 +344,187 @@ pub struct Args {
     #[arg(long)]
     pub rotate_ip_headers: bool,
 
+    /// Attach a body of spaces to about 3 in 10 requests. Some servers and WAFs answer GETs
+    /// with a body differently, so results may vary from one request to the next.
+    #[arg(long)]
+    pub padding_body: bool,
+
+    /// The range of --padding-body sizes in bytes, e.g. 10-50.
+    #[arg(long, value_name = "MIN-MAX", default_value = "10-50", requires = "padding_body", value_parser = crate::parser::parse_padding_size)]
+    pub padding_body_size: (usize, usize),
+
+    /// Do not add the browser-like Referer, Accept-*, DNT, Sec-Fetch-* and
+    /// Upgrade-Insecure-Requests headers, which strict servers may reject.
+    #[arg(long)]
+    pub no_random_headers: bool,
+
+    /// Send this Referer with every request instead of a random one. An empty value sends
+    /// no Referer at all.
+    #[arg(long, value_name = "URL", alias = "static-referer")]
+    pub referer: Option<String>,
+
+    /// Send this Accept header instead of the browser-like one, e.g. for APIs that only
+    /// return JSON when asked. `json`, `xml` and `all` are shortcuts for `application/json`,
+    /// `application/xml` and `*/*`.
+    #[arg(long, value_name = "MIME")]
+    pub accept: Option<String>,
+
+    /// Send `Accept-Encoding: identity` instead of the rotating value, so bodies arrive
+    /// uncompressed.
+    #[arg(long, alias = "accept-encoding-none")]
+    pub no_encoding: bool,
+
+    /// Send this Accept-Encoding instead of the rotating value. Bodies are never
+    /// decompressed, so word and line counts see them as sent.
+    #[arg(long, value_name = "ENCODING", conflicts_with = "no_encoding")]
+    pub force_encoding: Option<String>,
+
     /// Path to a file containing User-Agent strings, one per line.
     #[arg(long, default_value = "")]
     pub user_agents: String,
 
-    /// HTTP proxy to use for requests (e.g., http://127.0.0.1:8080).
+    /// HTTP proxy to use for requests (e.g., http://127.0.0.1:8080). Repeat it to rotate
+    /// among several proxies, one request each in turn.
     #[arg(long)]
-    pub proxy: Option<String>,
+    pub proxy: Vec<String>,
+
+    /// File with one proxy URL per line to rotate among, on top of any `--proxy`.
+    #[arg(long, value_name = "FILE")]
+    pub proxy_file: Option<String>,
+
+    /// Credentials (user:pass) to authenticate to the proxies with.
+    #[arg(long, value_name = "USER:PASS")]
+    pub proxy_auth: Option<String>,
+
+    /// Seconds a proxy failing several requests in a row is skipped for, when rotating.
+    #[arg(long, default_value_t = 60)]
+    pub proxy_cooldown: u64,
+
+    /// Accept invalid TLS certificates, e.g. the self-signed certificates of internal targets.
+    #[arg(long, default_value_t = false)]
+    pub insecure: bool,
+
+    /// Trust the certificates of this PEM file as root certificates, on top of the system ones.
+    #[arg(long, value_name = "PEM")]
+    pub ca_cert: Option<String>,
+
+    /// Authenticate with the client certificate of this PEM file (mTLS), with --client-key.
+    #[arg(
+        long,
+        value_name = "PEM",
+        requires = "client_key",
+        conflicts_with = "client_pkcs12"
+    )]
+    pub client_cert: Option<String>,
+
+    /// The PKCS#8 PEM private key of --client-cert.
+    #[arg(long, value_name = "PEM", requires = "client_cert")]
+    pub client_key: Option<String>,
+
+    /// Authenticate with the client certificate and key of this PKCS#12 file (mTLS).
+    #[arg(long, value_name = "FILE")]
+    pub client_pkcs12: Option<String>,
+
+    /// The password of the --client-pkcs12 file.
+    #[arg(long, value_name = "PASSWORD", requires = "client_pkcs12")]
+    pub pkcs12_password: Option<String>,
 
     /// Enable the reqwest cookie store to persist cookies between requests.
     #[arg(long, default_value_t = false)]
     pub cookie_jar: bool,
 
-    /// Format for the output file (text, json, xml, csv).
-    #[arg(long, default_value = "text")]
-    pub output_format: String,
+    /// Cookies sent with every request, e.g. `session=abc; theme=dark`. With --cookie-jar
+    /// they seed the jar instead. May be given as `env:NAME` or `file:/path`.
+    #[arg(long, value_name = "COOKIES")]
+    pub cookie: Option<String>,
+
+    /// Show the cookies successful responses set, and save their Set-Cookie headers in reports.
+    #[arg(long)]
+    pub show_cookies: bool,
+
+    /// Only connect to the target over IPv4. The scan fails if the host has no IPv4 address.
+    #[arg(long, default_value_t = false, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Only connect to the target over IPv6. The scan fails if the host has no IPv6 address.
+    #[arg(long, default_value_t = false)]
+    pub ipv6: bool,
+
+    /// Connect to HOST at these addresses instead of its DNS records, like curl's --resolve
+    /// (e.g., example.com:443:10.0.0.5). Several addresses are separated by commas. An entry
+    /// only applies to the target host on that port, and --ipv4/--ipv6 keep only the addresses
+    /// of their version. Can be repeated.
+    #[arg(long, value_name = "HOST:PORT:ADDR", value_parser = crate::parser::parse_resolve)]
+    pub resolve: Vec<ResolveOverride>,
+
+    /// Bind connections to the address of this network interface (e.g., tun0), preferring
+    /// IPv4 unless --ipv6 is given.
+    #[arg(long, value_name = "NAME", alias = "network-interface")]
+    pub interface: Option<String>,
+
+    /// Bind connections to this local IP address, which must be assigned to this host.
+    #[arg(long, value_name = "IP", alias = "bind", conflicts_with = "interface")]
+    pub bind_address: Option<std::net::IpAddr>,
+
+    /// Format for the output file (text, json, xml, csv, markdown) [default: text].
+    /// Without --output-file, results are saved to `<scan-id>.<format>`.
+    #[arg(long)]
+    pub output_format: Option<String>,
 
     /// Path to save the final scan results.
     #[arg(long)]
     pub output_file: Option<String>,
 
+    /// Append failed requests (timestamp, word and error) to this file as JSON lines while
+    /// the scan runs, with or without an output file.
+    #[arg(long, value_name = "FILE", alias = "connection-errors-to-file")]
+    pub error_log: Option<String>,
+
+    /// Leave failed requests out of the output file, when they go to --error-log.
+    #[arg(long, requires = "error_log")]
+    pub no_errors_in_main_output: bool,
+
+    /// Append every request sent (time, method, URL, headers, response status and length)
+    /// to this file as JSON lines, as an audit trail.
+    #[arg(long, value_name = "FILE")]
+    pub request_log: Option<String>,
+
+    /// Write the values of sensitive headers like Authorization and Cookie to --request-log
+    /// instead of masking them.
+    #[arg(long, requires = "request_log")]
+    pub log_sensitive_headers: bool,
+
+    /// Sort the entries of the output file. Responses without a content length sort as 0 bytes.
+    #[arg(long, value_enum, alias = "output-sort")]
+    pub sort_by: Option<SortKey>,
+
+    /// Sort the output file entries in descending order.
+    #[arg(long, requires = "sort_by", alias = "output-sort-desc")]
+    pub sort_desc: bool,
+
+    /// Identifier embedded in all output files. A random UUID is generated when not set.
+    #[arg(long)]
+    pub scan_id: Option<String>,
+
+    /// Append to the output file instead of creating a new one (text and csv formats only).
+    #[arg(long, conflicts_with_all = ["output_overwrite", "sort_by"])]
+    pub output_append: bool,
+
+    /// Overwrite the output file if it exists, instead of writing to a suffixed file name.
+    #[arg(long)]
+    pub output_overwrite: bool,
+
+    /// Webhook URL (Slack or Discord compatible) notified about interesting results.
+    /// Messages are batched and sent at most once every 10 seconds.
+    #[arg(long)]
+    pub notify_webhook: Option<String>,
+
+    /// Status codes that trigger a webhook notification.
+    #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_NOTIFY_CODES, requires = "notify_webhook")]
+    pub notify_codes: Vec<u16>,
+
     /// Custom Authorization header to send with each request.
+    /// Like all auth and header values, it may be given as `env:NAME` or `file:/path`.
     #[arg(long)]
     pub auth_header: Option<String>,
 
@@ -79,14 +532,52 @@ pub struct Args {
     #[arg(long)]
     pub basic_auth: Option<String>,
 
+    /// Read the basic authentication credentials (username:password) from this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "basic_auth")]
+    pub basic_auth_file: Option<String>,
+
     /// Bearer token for authentication.
     #[arg(long)]
     pub bearer_token: Option<String>,
 
+    /// Read the bearer token from this environment variable.
+    #[arg(long, value_name = "VAR", conflicts_with = "bearer_token")]
+    pub bearer_token_env: Option<String>,
+
+    /// Sign every request with an HMAC header, e.g.
+    /// "header=X-Signature;secret=env:SIGKEY;payload={{method}}{{path}}{{timestamp}};algo=sha256".
+    /// The secret is read from env:NAME or file:/path only.
+    #[arg(long, value_name = "SPEC")]
+    pub sign_hmac: Option<String>,
+
+    /// Shell command run when the session expires, printing a new `Cookie: ...` header or
+    /// bearer token that replaces the one sent so far.
+    #[arg(long, requires = "reauth_trigger")]
+    pub reauth_command: Option<String>,
+
+    /// Responses showing the session expired, as status codes with an optional Location
+    /// substring for redirects (e.g. 401,302:/login).
+    #[arg(long, value_delimiter = ',', value_parser = parse_reauth_trigger, requires = "reauth_command")]
+    pub reauth_trigger: Vec<ReauthTrigger>,
+
+    /// Trigger responses in a row that run --reauth-command.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_REAUTH_THRESHOLD, value_parser = clap::value_parser!(u32).range(1..))]
+    pub reauth_threshold: u32,
+
     /// Custom headers to send with each request, in key:value format.
+    /// The value may be given as `env:NAME` or `file:/path`.
     #[arg(long)]
     pub headers: Vec<String>,
 
+    /// Read custom headers from a file, one `Key: Value` per line. Headers given with
+    /// --headers win over the file.
+    #[arg(long, value_name = "PATH")]
+    pub header_file: Option<String>,
+
+    /// The layout of --header-file: plain `Key: Value` lines, or a raw request from Burp.
+    #[arg(long, value_enum, default_value_t = HeaderFileFormat::Plain, requires = "header_file")]
+    pub header_file_format: HeaderFileFormat,
+
     /// Filter out responses with these status codes.
     #[arg(long)]
     pub filter_codes: Vec<u16>,
@@ -95,27 +586,179 @@ pub struct Args {
     #[arg(long)]
     pub filter_size: Option<String>,
 
+    /// Widen the --filter-size range, and the --filter-size-exact size, by this percentage
+    /// (e.g., 5) for pages whose size varies with dynamic content.
+    #[arg(long, value_name = "PERCENT", value_parser = crate::parser::parse_tolerance)]
+    pub filter_size_tolerance: Option<f32>,
+
+    /// Filter out responses of exactly this many bytes, give or take --filter-size-tolerance.
+    #[arg(long, value_name = "BYTES")]
+    pub filter_size_exact: Option<u64>,
+
+    /// Drop responses smaller than this many bytes.
+    #[arg(long, value_name = "BYTES")]
+    pub min_response_size: Option<u64>,
+
+    /// Drop responses larger than this many bytes.
+    #[arg(long, value_name = "BYTES")]
+    pub max_response_size: Option<u64>,
+
     /// Filter responses that take longer than this time in milliseconds.
     #[arg(long)]
     pub filter_time: Option<u64>,
 
+    /// Drop responses with an empty body, without having to set a size filter.
+    #[arg(long, alias = "exclude-zero")]
+    pub exclude_length_zero: bool,
+
+    /// Drop responses whose Content-Type does not start with this MIME type, e.g.
+    /// `application/json`. Responses without a Content-Type are dropped too.
+    #[arg(long, value_name = "MIME")]
+    pub require_content_type: Option<String>,
+
     /// Filter responses by word count range (e.g., "50-200").
     #[arg(long)]
     pub filter_words: Option<String>,
 
+    /// Filter out responses whose body contains this HTML tag, e.g. `script` for injected
+    /// block pages. Can be given multiple times.
+    #[arg(long = "filter-html-tag", value_name = "TAG", alias = "tag-filter")]
+    pub filter_html_tags: Vec<String>,
+
     /// Show the content length of the response in the output.
     #[arg(long)]
     pub show_content_length: bool,
 
+    /// Show the number of words in the response body in the output.
+    #[arg(long)]
+    pub show_word_count: bool,
+
+    /// Show the number of lines in the response body in the output.
+    #[arg(long, alias = "response-line-count-in-output")]
+    pub show_line_count: bool,
+
+    /// Show the HTTP version each response was received over in the output.
+    #[arg(long)]
+    pub show_http_version: bool,
+
     /// Show the response time in milliseconds in the output.
     #[arg(long)]
     pub show_response_time: bool,
 
+    /// Show the last error, with its word, next to the error count in the progress bar.
+    #[arg(long)]
+    pub show_errors_inline: bool,
+
+    /// Show response times at microsecond resolution, to spot the small timing differences
+    /// time-based enumeration relies on. Implies --show-response-time.
+    #[arg(long, alias = "timing-attack")]
+    pub high_res_timing: bool,
+
+    /// Split the response time of every request into time to first byte and body read time,
+    /// shown in the console and written to JSON and CSV reports.
+    #[arg(long)]
+    pub timing_detail: bool,
+
+    /// After the scan, print the number of responses received with each status code.
+    #[arg(long = "status-summary", alias = "status-codes-summary")]
+    pub status_code_summary: bool,
+
+    /// After the scan, print the displayed results again sorted by response time, fastest first.
+    #[arg(long)]
+    pub sort_by_time: bool,
+
     /// Enable automatic detection and filtering of wildcard responses.
     #[arg(long)]
     pub detect_wildcards: bool,
 
+    /// Learn the typical response (modal status, content length and word count) from the
+    /// first 20 responses of the scan, and filter responses like it, those included.
+    #[arg(long)]
+    pub smart_filter: bool,
+
+    /// Filter responses whose body is identical to one seen earlier in the scan, keeping only
+    /// the first.
+    #[arg(long)]
+    pub dedup_responses: bool,
+
+    /// Show the wildcard confidence score of filtered results, and print them to the console.
+    #[arg(long, requires = "detect_wildcards")]
+    pub show_wildcard_confidence: bool,
+
+    /// Log the criteria each wildcard check matched and what they added to its confidence.
+    #[arg(long, requires = "detect_wildcards")]
+    pub debug_wildcards: bool,
+
+    /// Path under the base URL whose body is fetched once as a baseline. Responses whose
+    /// words differ from it by less than --diff-threshold are filtered.
+    #[arg(long, value_name = "PATH")]
+    pub diff_baseline: Option<String>,
+
+    /// Minimum word-level difference (0-1) from the baseline for a response to be kept.
+    #[arg(long, default_value_t = 0.1, requires = "diff_baseline")]
+    pub diff_threshold: f32,
+
+    /// Show the difference score from the baseline in the output.
+    #[arg(long, requires = "diff_baseline")]
+    pub show_diff_score: bool,
+
     /// Similarity threshold (0-100) for wildcard detection. Higher is stricter.
     #[arg(long)]
     pub wildcard_threshold: Option<u32>,
+
+    /// Request every path over both HTTP/1.1 and HTTP/2 and report both when the statuses differ.
+    #[arg(long)]
+    pub http_version_probe: bool,
+
+    /// Only speak HTTP/1.1, including for the wildcard probes.
+    #[arg(long, conflicts_with_all = ["http2_prior_knowledge", "http_version_probe"])]
+    pub http1: bool,
+
+    /// Speak HTTP/2 without negotiating it first, including for the wildcard probes.
+    #[arg(long, conflicts_with = "http_version_probe")]
+    pub http2_prior_knowledge: bool,
+
+    /// Speak HTTP/3 over QUIC, falling back to HTTP/1.1 and HTTP/2 if the target does not answer over it (needs the http3 cargo feature).
+    #[arg(long, conflicts_with_all = ["http1", "http2_prior_knowledge", "http_version_probe"])]
+    pub http3: bool,
+
+    /// With --http3, stop instead of falling back when the target does not answer over HTTP/3.
+    #[arg(long, requires = "http3")]
+    pub http3_only: bool,
+
+    /// Inject access-control bypass headers into every request (method-override, url-override, client-ip, all).
+    #[arg(long, value_enum, default_value_t = HeaderInjectMode::None)]
+    pub header_inject: HeaderInjectMode,
+}
+
+/// Subcommands that work on saved results instead of running a scan.
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Compare two JSON scan results and show new, removed and changed URLs.
+    Compare(CompareArgs),
+    /// Validate the scan options, config file included, without sending any requests.
+    Check(Box<Args>),
+}
+
+/// The arguments of the `compare` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct CompareArgs {
+    /// The earlier JSON result file.
+    pub file1: String,
+
+    /// The later JSON result file.
+    pub file2: String,
+
+    /// Save the comparison report as JSON to this file.
+    #[arg(long, value_name = "FILE")]
+    pub compare_output: Option<String>,
+}
+
+/// Builds the full command line: the scan arguments plus the subcommands.
+/// The scan arguments are neither required nor allowed when a subcommand is given.
+pub fn command() -> clap::Command {
+    let command = Commands::augment_subcommands(clap::Command::new(env!("CARGO_PKG_NAME")));
+    Args::augment_args(command)
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
 }
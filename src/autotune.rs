@@ -0,0 +1,266 @@
+//! This module implements `--auto-threads`, which tunes the concurrency during the scan.
+//!
+//! Requests record their outcome in [`RequestMetrics`]. A controller task periodically takes
+//! a snapshot of these counters and resizes an [`AdaptiveLimit`]: the limit grows while the
+//! target looks healthy and is halved on 429s, a high error rate or a p95 latency well above
+//! the best one seen so far.
+//!
+//! The same limit enforces `--threads` on every scan, so the `+` and `-` keys can also resize
+//! it by hand while scanning.
+
+use crate::buster::ScanState;
+use chrono::Utc;
+use indicatif::ProgressBar;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::{MissedTickBehavior, interval};
+use tracing::debug;
+
+/// The concurrency an auto-tuned scan starts with, unless `--threads` is lower.
+pub const INITIAL_CONCURRENCY: usize = 5;
+
+/// The highest concurrency the `+` key can raise the limit to.
+pub const MAX_CONCURRENCY: usize = 500;
+
+/// How many permits a `+` or `-` key press adds or removes.
+pub const CONCURRENCY_STEP: usize = 5;
+
+/// How often the controller re-evaluates the concurrency.
+const TUNE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Counters updated by every request attempt, read and reset by the controller.
+#[derive(Debug, Default)]
+pub struct RequestMetrics {
+    /// Attempts that received a response or failed.
+    requests: AtomicUsize,
+    /// Attempts that failed with a network error or a server error status.
+    failures: AtomicUsize,
+    /// Attempts answered with 429 Too Many Requests.
+    rate_limited: AtomicUsize,
+    /// Latencies of the responses received since the last snapshot.
+    latencies: Mutex<Vec<Duration>>,
+}
+
+/// The request outcomes between two controller ticks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub requests: usize,
+    pub failures: usize,
+    pub rate_limited: usize,
+    pub p95_latency: Option<Duration>,
+}
+
+impl RequestMetrics {
+    /// Records an attempt that received a response.
+    pub fn record_response(&self, status: u16, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        match status {
+            429 => {
+                self.rate_limited.fetch_add(1, Ordering::Relaxed);
+            }
+            500..=599 => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        self.latencies.lock().unwrap().push(latency);
+    }
+
+    /// Records an attempt that failed without a response.
+    pub fn record_failure(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the outcomes since the last snapshot and resets the counters.
+    pub fn take_snapshot(&self) -> MetricsSnapshot {
+        let mut latencies = std::mem::take(&mut *self.latencies.lock().unwrap());
+        latencies.sort_unstable();
+        let p95_latency = latencies
+            .get((latencies.len() * 95).div_ceil(100).saturating_sub(1))
+            .copied();
+        MetricsSnapshot {
+            requests: self.requests.swap(0, Ordering::Relaxed),
+            failures: self.failures.swap(0, Ordering::Relaxed),
+            rate_limited: self.rate_limited.swap(0, Ordering::Relaxed),
+            p95_latency,
+        }
+    }
+}
+
+/// Computes the next concurrency limit from the last interval's outcomes.
+///
+/// `best_p95` is the lowest p95 latency seen so far; twice that counts as trouble.
+/// Without any requests in the interval the limit stays unchanged.
+pub fn next_limit(
+    current: usize,
+    max: usize,
+    snapshot: &MetricsSnapshot,
+    best_p95: Option<Duration>,
+) -> usize {
+    if snapshot.requests == 0 {
+        return current;
+    }
+
+    let slow = match (snapshot.p95_latency, best_p95) {
+        (Some(p95), Some(best)) => p95 > best * 2,
+        _ => false,
+    };
+    let failing = snapshot.failures * 10 > snapshot.requests;
+
+    if snapshot.rate_limited > 0 || failing || slow {
+        (current / 2).max(1)
+    } else {
+        (current + (current / 4).max(1)).min(max)
+    }
+}
+
+/// A change of the concurrency limit made from the keyboard, recorded for the report.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ConcurrencyChange {
+    /// When the limit was changed, in RFC 3339 format.
+    pub changed_at: String,
+    pub from: usize,
+    pub to: usize,
+}
+
+/// A concurrency limit that can be resized while tasks hold permits.
+#[derive(Debug)]
+pub struct AdaptiveLimit {
+    semaphore: Arc<Semaphore>,
+    limit: AtomicUsize,
+    /// Permits still to be removed once running tasks release them.
+    debt: AtomicUsize,
+    /// The ceiling of the controller, raised when the limit is adjusted above it by hand.
+    max: AtomicUsize,
+    changes: Mutex<Vec<ConcurrencyChange>>,
+}
+
+impl AdaptiveLimit {
+    /// Creates a limit starting at `initial` permits that never grows beyond `max`.
+    pub fn new(initial: usize, max: usize) -> Self {
+        let initial = initial.clamp(1, max.max(1));
+        AdaptiveLimit {
+            semaphore: Arc::new(Semaphore::new(initial)),
+            limit: AtomicUsize::new(initial),
+            debt: AtomicUsize::new(0),
+            max: AtomicUsize::new(max.max(1)),
+            changes: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The semaphore tasks acquire a permit from before sending.
+    pub fn semaphore(&self) -> Arc<Semaphore> {
+        self.semaphore.clone()
+    }
+
+    /// The current concurrency limit.
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// The highest concurrency the limit may grow to.
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Changes the limit. Shrinking removes idle permits now and busy ones as they are released.
+    pub fn resize(&self, target: usize) {
+        let target = target.clamp(1, self.max());
+        let current = self.limit.swap(target, Ordering::Relaxed);
+        if target > current {
+            let mut grow = target - current;
+            // Cancel pending removals before adding new permits
+            let debt = self.debt.load(Ordering::Relaxed);
+            let cancelled = debt.min(grow);
+            self.debt.fetch_sub(cancelled, Ordering::Relaxed);
+            grow -= cancelled;
+            self.semaphore.add_permits(grow);
+        } else {
+            self.debt.fetch_add(current - target, Ordering::Relaxed);
+        }
+        self.settle();
+    }
+
+    /// Adds or removes permits by hand and records the change.
+    ///
+    /// Raising the limit above the controller's ceiling raises the ceiling as well, up to
+    /// [`MAX_CONCURRENCY`]. Returns `None` if the limit is already at its bound.
+    pub fn adjust(&self, delta: isize) -> Option<ConcurrencyChange> {
+        let current = self.limit();
+        let target = current
+            .saturating_add_signed(delta)
+            .clamp(1, MAX_CONCURRENCY.max(self.max()));
+        if target == current {
+            return None;
+        }
+
+        self.max.fetch_max(target, Ordering::Relaxed);
+        self.resize(target);
+        let change = ConcurrencyChange {
+            changed_at: Utc::now().to_rfc3339(),
+            from: current,
+            to: target,
+        };
+        self.changes.lock().unwrap().push(change.clone());
+        Some(change)
+    }
+
+    /// The changes made with [`AdaptiveLimit::adjust`] so far.
+    pub fn changes(&self) -> Vec<ConcurrencyChange> {
+        self.changes.lock().unwrap().clone()
+    }
+
+    /// Removes as many owed permits as are currently idle.
+    pub fn settle(&self) {
+        let debt = self.debt.load(Ordering::Relaxed);
+        if debt > 0 {
+            let forgotten = self.semaphore.forget_permits(debt);
+            self.debt.fetch_sub(forgotten, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Periodically resizes the limit from the request metrics until the scan stops.
+///
+/// The progress bar message is updated with the current concurrency after every tick.
+pub async fn run_controller(
+    limit: Arc<AdaptiveLimit>,
+    state: Arc<ScanState>,
+    pb: Option<ProgressBar>,
+) {
+    let mut ticker = interval(TUNE_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+    let mut best_p95: Option<Duration> = None;
+
+    while !state.should_stop.load(Ordering::Relaxed) {
+        ticker.tick().await;
+        limit.settle();
+
+        let snapshot = state.metrics.take_snapshot();
+        let current = limit.limit();
+        let next = next_limit(current, limit.max(), &snapshot, best_p95);
+        if let Some(p95) = snapshot.p95_latency {
+            best_p95 = Some(best_p95.map_or(p95, |best| best.min(p95)));
+        }
+
+        if next != current {
+            debug!(
+                from = current,
+                to = next,
+                requests = snapshot.requests,
+                failures = snapshot.failures,
+                rate_limited = snapshot.rate_limited,
+                p95_ms = snapshot.p95_latency.map(|p95| p95.as_millis() as u64),
+                "Adjusted concurrency"
+            );
+            limit.resize(next);
+        }
+        if let Some(pb) = &pb {
+            pb.set_message(state.progress_message(Some(next)));
+        }
+    }
+}
@@ -2,67 +2,510 @@
 //! It defines the data structures for scan configuration and results,
 //! and the main function for sending HTTP requests with retries and evasion techniques.
 
-use crate::parser;
+use crate::autotune::RequestMetrics;
+use crate::cache::{CachedResponse, ResponseCache};
+use crate::client;
+use crate::console::{print_diagnostic, progress_message};
+use crate::output::{SortKey, format_bytes, format_request_headers, format_response_headers};
+use crate::parser::{self, Template, TemplateValues};
+use crate::proxy::{PROXY_FAILURE_LIMIT, ProxyRotation};
+use crate::rate::RateLimiter;
+use crate::reauth::Reauth;
+use crate::request_log::{RequestLog, RequestLogEntry};
+use crate::signing::SigningSpec;
+use crate::spider::{self, Spider};
+use crate::waf::{RESUME_DELAY_MS, WAF_WINDOW, WafDetector};
 use crate::wildcard::*;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chrono::Utc;
 use rand::Rng;
 use rand::prelude::IndexedRandom;
-use reqwest::Client;
-use reqwest::header::USER_AGENT;
-use std::collections::HashMap;
+use regex::Regex;
+use reqwest::header::{
+    AUTHORIZATION, CONTENT_TYPE, COOKIE, ETAG, HeaderMap, HeaderName, HeaderValue, LAST_MODIFIED,
+    LOCATION, RETRY_AFTER, SET_COOKIE, USER_AGENT,
+};
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url, Version};
+use std::collections::{BTreeMap, VecDeque};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
-use std::time::Duration;
-use tokio::time::{Instant, sleep};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, info, trace, warn};
 
 /// Represents the outcome of a single directory/file bust attempt.
 #[derive(Debug, Clone)]
 pub enum BustResult {
     /// A successful request (typically 2xx status code) that was not filtered.
     Success(DetailedResponse),
-    /// A request that resulted in a non-successful status code (e.g., 404) and was not filtered.
+    /// A redirect (3xx status code) that was not filtered, with the value of its `Location` header.
+    Redirect(DetailedResponse, String),
+    /// A client error (4xx status code, e.g. 404 or 403) that was not filtered.
+    ClientError(DetailedResponse),
+    /// A response with any other non-successful status code (e.g. 5xx) that was not filtered.
     NotFound(DetailedResponse),
-    /// A request that failed due to a network error or other issue.
-    Error(String, String),
+    /// A request that failed due to a network error or other issue, with its message and kind.
+    Error(String, String, ErrorKind),
     /// A response that was filtered out based on user-defined criteria.
     Filtered(DetailedResponse),
 }
 
+/// The families of access-control bypass headers that can be injected into requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HeaderInjectMode {
+    /// Do not inject any bypass headers.
+    #[default]
+    None,
+    /// Method override headers (`X-HTTP-Method-Override` and friends).
+    MethodOverride,
+    /// URL rewrite headers (`X-Original-URL`, `X-Rewrite-URL`) pointing at the word.
+    UrlOverride,
+    /// Client IP spoofing headers claiming the request comes from localhost.
+    ClientIp,
+    /// All of the above.
+    All,
+}
+
+impl fmt::Display for HeaderInjectMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HeaderInjectMode::None => "none",
+            HeaderInjectMode::MethodOverride => "method-override",
+            HeaderInjectMode::UrlOverride => "url-override",
+            HeaderInjectMode::ClientIp => "client-ip",
+            HeaderInjectMode::All => "all",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which responses and network errors are retried, from `--retry-on`.
+///
+/// The default retries rate limits (429), server errors (5xx) and connection, timeout and
+/// DNS failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Inclusive ranges of retried status codes.
+    pub statuses: Vec<(u16, u16)>,
+    /// Retry network errors about the connection, e.g. a refused or reset connection, or a
+    /// failed proxy.
+    pub conn: bool,
+    /// Retry requests that timed out.
+    pub timeout: bool,
+    /// Retry failed DNS lookups.
+    pub dns: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            statuses: vec![(429, 429), (500, 599)],
+            conn: true,
+            timeout: true,
+            dns: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns true if a response with this status is retried.
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.statuses
+            .iter()
+            .any(|&(min, max)| (min..=max).contains(&status))
+    }
+
+    /// Returns true if a request failing with this kind of error is retried.
+    pub fn retries_error(&self, kind: ErrorKind) -> bool {
+        match kind {
+            ErrorKind::Connect | ErrorKind::Read | ErrorKind::Proxy => self.conn,
+            ErrorKind::Timeout => self.timeout,
+            ErrorKind::Dns => self.dns,
+            ErrorKind::Tls | ErrorKind::Other => false,
+        }
+    }
+}
+
+/// What a failed request failed on, as told by `client::error_kind`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorKind {
+    /// The host name could not be resolved.
+    Dns,
+    /// The connection was refused or could not be established.
+    Connect,
+    /// The TLS handshake failed, e.g. on an invalid certificate.
+    Tls,
+    /// The request or connection timed out.
+    Timeout,
+    /// The connection was reset or closed while the request was sent or the response read.
+    Read,
+    /// The proxy the request was sent through failed.
+    Proxy,
+    /// Any other failure, e.g. an invalid URL or a request given up on after rate limiting.
+    #[default]
+    Other,
+}
+
+impl ErrorKind {
+    /// Every kind, in the order they are listed in summaries.
+    pub const ALL: [ErrorKind; 7] = [
+        ErrorKind::Dns,
+        ErrorKind::Connect,
+        ErrorKind::Tls,
+        ErrorKind::Timeout,
+        ErrorKind::Read,
+        ErrorKind::Proxy,
+        ErrorKind::Other,
+    ];
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Dns => "dns",
+            ErrorKind::Connect => "connect",
+            ErrorKind::Tls => "tls",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Read => "read",
+            ErrorKind::Proxy => "proxy",
+            ErrorKind::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Counters for failed requests, one per kind of error.
+#[derive(Debug, Default)]
+pub struct ErrorKindCounts([AtomicUsize; ErrorKind::ALL.len()]);
+
+impl ErrorKindCounts {
+    /// Counts one failed request of this kind.
+    pub fn record(&self, kind: ErrorKind) {
+        self.0[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of failed requests of each kind that occurred, in `ErrorKind::ALL` order.
+    pub fn breakdown(&self) -> BTreeMap<ErrorKind, usize> {
+        ErrorKind::ALL
+            .into_iter()
+            .map(|kind| (kind, self.0[kind as usize].load(Ordering::Relaxed)))
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+}
+
+impl BustResult {
+    /// Returns the response carried by the result, if one was received.
+    pub fn response(&self) -> Option<&DetailedResponse> {
+        match self {
+            BustResult::Success(resp)
+            | BustResult::Redirect(resp, _)
+            | BustResult::ClientError(resp)
+            | BustResult::NotFound(resp)
+            | BustResult::Filtered(resp) => Some(resp),
+            BustResult::Error(..) => None,
+        }
+    }
+
+    /// Returns the response of a result that is reported, i.e. received and not filtered.
+    pub fn reported_response(&self) -> Option<&DetailedResponse> {
+        match self {
+            BustResult::Filtered(_) => None,
+            other => other.response(),
+        }
+    }
+
+    /// Returns the word the result was produced for.
+    pub fn word(&self) -> &str {
+        match self {
+            BustResult::Error(word, ..) => word,
+            other => other.response().map_or("", |resp| resp.word.as_str()),
+        }
+    }
+}
+
 /// Contains detailed information about a single HTTP response.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct DetailedResponse {
     /// The word from the wordlist that was used for this request.
     pub word: String,
     /// The HTTP status code of the response.
     pub status: u16,
-    /// The content length of the response body, if available.
+    /// The content length of the response body: its `Content-Length` header, or else the
+    /// length of the body as read.
     pub content_length: Option<u64>,
-    /// The time it took to receive the response.
+    /// The `Content-Length` header of the response, if it had one.
+    pub header_length: Option<u64>,
+    /// The length of the body as read and decoded, unless a cached response was reused.
+    pub body_length: Option<u64>,
+    /// Whether reading the body stopped at `--max-body-size`, so the word and line counts and
+    /// the wildcard sample only cover its first bytes.
+    pub body_truncated: bool,
+    /// The time it took to receive the response headers, i.e. the time to first byte.
     pub response_time: Duration,
+    /// The time it took to read the body after the headers, with `--timing-detail`.
+    pub body_read_time: Option<Duration>,
     /// The number of words in the response body.
     pub word_count: Option<usize>,
+    /// The number of lines in the response body.
+    pub line_count: Option<usize>,
+    /// The bypass header mode that was active, set only when the response status
+    /// differed from a baseline request sent without the injected headers.
+    pub bypass_mode: Option<HeaderInjectMode>,
+    /// The HTTP version label (`H1` or `H2`) the response was fetched with, set only when
+    /// version probing found the two protocols answering differently.
+    pub probed_version: Option<&'static str>,
+    /// The HTTP version the response was received over, e.g. `HTTP/2`.
+    pub http_version: Option<&'static str>,
+    /// The wildcard confidence score, set whenever the response was checked against the wildcard profile.
+    pub wildcard_confidence: Option<f32>,
+    /// The word-level difference from the `--diff-baseline` body, from 0 (identical) to 1.
+    pub diff_score: Option<f32>,
+    /// Where the word came from when it was not in the word list: `robots`, `sitemap` or
+    /// `spider`.
+    pub source: Option<&'static str>,
+    /// The `Content-Type` header of the response.
+    pub content_type: Option<String>,
+    /// The `Set-Cookie` headers of a successful response, kept with `--show-cookies`.
+    pub set_cookies: Vec<String>,
+    /// The full URL the request was sent to, with its `--cache-bust` suffix or filled-in
+    /// URL template.
+    pub requested_url: String,
 }
 
 /// Holds all the configuration settings for the scan.
 /// This struct is shared across all concurrent tasks.
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ScanConfig {
+    pub scan_id: String,
     pub base_url: String,
+    pub path_prefix: String,
+    /// The shortest and longest words kept from the word list, in characters.
+    pub wordlist_min_len: Option<usize>,
+    pub wordlist_max_len: Option<usize>,
+    /// The path of a `--url` with placeholders, after the base URL, filled in per request.
+    pub url_template: Option<Template>,
+    /// The most concurrent requests to one host, on top of the global concurrency limit.
+    pub threads_per_host: usize,
     pub retries: usize,
+    pub retry_policy: RetryPolicy,
+    /// The cap on waits requested by `Retry-After` headers.
+    pub max_retry_after: Duration,
+    /// Slow down as the `X-RateLimit-*` and `Retry-After` headers of any response ask.
+    pub respect_rate_limit_headers: bool,
     pub delay_min: u64,
     pub delay_max: u64,
     pub rotate_user_agent: bool,
     pub rotate_ip_headers: bool,
+    /// The size range of the `--padding-body` sent with some requests, if enabled.
+    pub padding_body: Option<(usize, usize)>,
+    /// Append a random query, fragment or `;sessionid=` suffix to every request URL.
+    pub cache_bust: bool,
+    pub no_browser_headers: bool,
+    /// A fixed Referer replacing the random one; empty to send none.
+    pub referer: Option<String>,
+    /// A fixed Accept header replacing the browser-like default.
+    pub accept_header: Option<String>,
+    /// Whether `Accept-Encoding: identity` replaces the rotating value.
+    pub force_no_encoding: bool,
+    /// A fixed Accept-Encoding replacing the rotating value.
+    pub force_encoding: Option<String>,
     pub user_agents: Vec<String>,
     pub auth_header: Option<String>,
     pub basic_auth: Option<String>,
     pub bearer_token: Option<String>,
+    /// The `--cookie` header value, unless it seeds the cookie jar instead.
+    pub cookie: Option<String>,
     pub custom_headers: HashMap<String, String>,
+    /// The custom headers whose values have placeholders, filled in per request.
+    pub header_templates: Vec<(String, Template)>,
+    /// Signs every request with an HMAC header, with `--sign-hmac`.
+    pub signing: Option<SigningSpec>,
     pub filter_codes: Vec<u16>,
     pub filter_size: Option<(u64, u64)>, // min, max
+    /// The percentage `filter_size` and `filter_size_exact` are widened by.
+    pub filter_size_tolerance: Option<f32>,
+    /// A size whose responses are filtered out, give or take the tolerance.
+    pub filter_size_exact: Option<u64>,
+    pub min_response_size: Option<u64>,
+    pub max_response_size: Option<u64>,
     pub filter_time: Option<u64>,
+    /// Whether responses with an empty body are filtered.
+    pub exclude_length_zero: bool,
+    /// The MIME type the `Content-Type` of a kept response must start with.
+    pub require_content_type: Option<String>,
     pub filter_words: Option<(usize, usize)>,
+    pub filter_html_tags: Vec<String>,
     pub show_content_length: bool,
+    pub show_line_count: bool,
+    pub show_word_count: bool,
+    pub show_http_version: bool,
+    pub show_cookies: bool,
+    /// Whether requests go over HTTP/3, with `--http3` against a target answering over it.
+    pub http3: bool,
+    /// Whether `-v` was given: the headers of every request and response are printed, and
+    /// results show their full URL.
+    pub verbose: bool,
     pub show_response_time: bool,
+    pub show_errors_inline: bool,
+    pub high_res_timing: bool,
+    /// Whether the body read time is measured next to the time to first byte.
+    pub timing_detail: bool,
     pub detect_wildcards: bool,
+    /// Whether the typical response is learned from the first words and filtered.
+    pub smart_filter: bool,
+    /// Whether responses with a body identical to an earlier one are filtered.
+    pub dedup_body_hash: bool,
+    pub show_wildcard_confidence: bool,
+    /// Whether the score of every wildcard check is logged, criterion by criterion.
+    pub debug_wildcards: bool,
+    pub diff_baseline: Option<String>,
+    pub diff_threshold: f32,
+    pub show_diff_score: bool,
+    pub extract_words: bool,
+    /// How many levels of links of found pages are followed, 0 to not follow any.
+    pub spider_depth: usize,
+    /// The words seeded from robots.txt or the sitemap, with their source (`robots` or
+    /// `sitemap`) to tag them with in reports.
+    pub word_sources: HashMap<String, &'static str>,
+    pub redact_patterns: Vec<Regex>,
+    pub encode_mode: parser::EncodeMode,
+    pub header_inject_mode: HeaderInjectMode,
+    pub force_ipv4: bool,
+    pub force_ipv6: bool,
+    /// The `--resolve` overrides connections use instead of DNS, for reports.
+    pub resolve_overrides: Vec<parser::ResolveOverride>,
+    pub bind_interface: Option<String>,
+    /// The local address connections are made from, also when taken from `bind_interface`.
+    pub bind_address: Option<IpAddr>,
+    /// Whether TLS certificates were left unverified with `--insecure`, for reports.
+    pub insecure: bool,
+    /// The `--ca-cert` file trusted as a root certificate, for reports.
+    pub ca_cert: Option<String>,
+    /// The file failed requests are appended to as JSON lines, with `--error-log`.
+    pub error_log: Option<String>,
+    /// Whether requests go through a proxy, so failed connections are proxy errors.
+    pub proxied: bool,
+    /// The file every request sent is appended to.
+    pub request_log: Option<String>,
+    /// Whether sensitive header values are written to the request log unmasked.
+    pub log_sensitive_headers: bool,
+    /// Whether failed requests are left out of the output file, as they go to the error log.
+    pub omit_errors_from_output: bool,
+    pub sort_by: Option<SortKey>,
+    pub sort_desc: bool,
+    pub max_errors: Option<usize>,
+    pub max_total_errors: Option<usize>,
+    pub max_error_rate: Option<u8>,
+    /// The wall-clock seconds after which the scan is stopped.
+    pub max_scan_time: Option<u64>,
+    /// The body bytes downloaded after which the scan stops.
+    pub max_bytes: Option<u64>,
+    /// The number of found results after which the scan stops.
+    pub max_results: Option<usize>,
+    /// The most bytes read of each response body.
+    pub max_body_size: Option<u64>,
+}
+
+/// Checks a scan configuration for settings that cannot work together, without sending
+/// any requests. Returns one message per problem found.
+pub fn validate_config(config: &ScanConfig) -> Vec<String> {
+    let mut errors = Vec::new();
+    match Url::parse(&config.base_url) {
+        Ok(url) if !matches!(url.scheme(), "http" | "https") => errors.push(format!(
+            "--url: unsupported scheme {:?}, expected http or https",
+            url.scheme()
+        )),
+        Ok(url) if url.host_str().is_none() => {
+            errors.push(format!("--url: {} has no host", config.base_url))
+        }
+        Ok(_) => {}
+        Err(e) => errors.push(format!("--url: invalid URL {}: {e}", config.base_url)),
+    }
+    // A maximum of 0 leaves it unset, and the minimum is used as a fixed delay
+    if config.delay_max != 0 && config.delay_min > config.delay_max {
+        errors.push(format!(
+            "--delay-min ({}) is greater than --delay-max ({})",
+            config.delay_min, config.delay_max
+        ));
+    }
+    if let Some((min, max)) = config.filter_size
+        && min > max
+    {
+        errors.push(format!("--filter-size: {min}-{max} is an empty range"));
+    }
+    if let Some((min, max)) = config.filter_words
+        && min > max
+    {
+        errors.push(format!("--filter-words: {min}-{max} is an empty range"));
+    }
+    if let (Some(min), Some(max)) = (config.wordlist_min_len, config.wordlist_max_len)
+        && min > max
+    {
+        errors.push(format!(
+            "--wordlist-min-length ({min}) is greater than --wordlist-max-length ({max})"
+        ));
+    }
+    if let (Some(min), Some(max)) = (config.min_response_size, config.max_response_size)
+        && min > max
+    {
+        errors.push(format!(
+            "--min-response-size ({min}) is greater than --max-response-size ({max})"
+        ));
+    }
+    errors
+}
+
+/// A point-in-time view of a running scan, dumped on SIGUSR1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanStatus {
+    /// The number of results recorded so far.
+    pub processed: usize,
+    pub total: usize,
+    pub found: usize,
+    pub errors: usize,
+    pub filtered: usize,
+    pub global_delay_ms: u64,
+    pub elapsed: Duration,
+    /// The estimated time left at the rate so far, once anything was processed.
+    pub eta: Option<Duration>,
+}
+
+impl fmt::Display for ScanStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "processed {}/{}, found {}, errors {}, filtered {}, delay {}ms, elapsed {}s, ETA {}",
+            self.processed,
+            self.total,
+            self.found,
+            self.errors,
+            self.filtered,
+            self.global_delay_ms,
+            self.elapsed.as_secs(),
+            match self.eta {
+                Some(eta) => format!("{}s", eta.as_secs()),
+                None => "unknown".to_string(),
+            }
+        )
+    }
 }
 
 /// Holds the mutable state of the scan, shared across all concurrent tasks.
@@ -74,23 +517,558 @@ pub struct ScanState {
     pub found_count: AtomicUsize,
     /// Counter for requests that resulted in an error.
     pub error_count: AtomicUsize,
+    /// Counters for the requests that resulted in an error, by kind of error.
+    pub error_kinds: ErrorKindCounts,
     /// Counter for responses that were filtered out.
     pub filtered_count: AtomicUsize,
+    /// Counter for redirect responses that were not filtered.
+    pub redirect_count: AtomicUsize,
+    /// Counter for requests that received an HTTP response, whatever the status.
+    pub response_count: AtomicUsize,
+    /// Counter for failed requests in a row, reset by any HTTP response.
+    pub consecutive_errors: AtomicUsize,
+    /// Counter for all failed requests, checked against `max_total_errors`.
+    pub failed_requests: AtomicUsize,
+    /// Whether each of the last `ERROR_RATE_WINDOW` requests failed, checked against `max_error_rate`.
+    pub recent_outcomes: Mutex<VecDeque<bool>>,
     /// A flag to signal all tasks to stop gracefully (e.g., on Ctrl+C).
     pub should_stop: AtomicBool,
+    /// Whether the scan was paused from the keyboard. No new requests are sent while set.
+    pub paused: AtomicBool,
+    /// Why the scan was stopped. Only the first reason is kept.
+    pub stop_reason: OnceLock<StopReason>,
     /// The profile generated for detecting wildcard responses.
     pub wildcard_profile: WildcardProfile,
+    /// The body of the `--diff-baseline` path, fetched once at startup.
+    pub baseline_body: Option<String>,
+    /// The shared `--rate` limiter every request waits on before it is sent.
+    pub rate_limiter: Option<RateLimiter>,
+    /// Outcomes of recent request attempts, read by the `--auto-threads` controller.
+    pub metrics: RequestMetrics,
+    /// Responses of previous scans to revalidate instead of re-fetching, with `--use-cache`.
+    pub response_cache: Option<ResponseCache>,
+    /// Detects WAF blocks and pauses new requests during cool-downs, with `--waf-detect`.
+    pub waf_detector: Option<WafDetector>,
+    /// Words harvested from the bodies of found pages, with `--extract-words`.
+    pub harvested_words: Mutex<HashSet<String>>,
+    /// The typical response filtered with `--smart-filter`, set once it is learned.
+    pub smart_filter: OnceLock<SmartFilterProfile>,
+    /// The SHA256 hashes of the response bodies seen so far, with `--dedup-responses`.
+    pub seen_body_hashes: Mutex<HashSet<String>>,
+    /// Counter for responses filtered because their body was seen before.
+    pub deduplicated_count: AtomicUsize,
+    /// The clients of the proxies requests rotate among, when several are given.
+    pub proxies: Option<ProxyRotation>,
+    /// Renews the session with `--reauth-command` when it expires.
+    pub reauth: Option<Reauth>,
+    /// Queues the links of found pages for the next pass, with `--spider-depth`.
+    pub spider: Option<Spider>,
+    /// The words scanned to the end, added in batches of `PROCESSED_BATCH_SIZE`.
+    pub processed_words: Mutex<Vec<String>>,
+    /// The word and message of the last error, shown with `--show-errors-inline`.
+    pub last_error: Mutex<Option<String>>,
+    /// Records every request sent, with `--request-log`.
+    pub request_log: Option<RequestLog>,
+    /// The decoded body bytes downloaded, checked against `max_bytes`.
+    pub body_bytes: AtomicU64,
+    /// The sum of the `Content-Length` headers of the responses that had one.
+    pub content_length_bytes: AtomicU64,
 }
 
+impl ScanState {
+    /// Returns where a word came from, if it was not in the word list.
+    pub fn word_source(&self, word: &str, config: &ScanConfig) -> Option<&'static str> {
+        config.word_sources.get(word).copied().or_else(|| {
+            self.spider
+                .as_ref()
+                .is_some_and(|spider| spider.is_spidered(word))
+                .then_some("spider")
+        })
+    }
+
+    /// Signals all tasks to stop gracefully, recording the reason if none was recorded yet.
+    pub fn request_stop(&self, reason: StopReason) {
+        let _ = self.stop_reason.set(reason);
+        self.should_stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Counts the bytes of a downloaded body, stopping the scan once `max_bytes` is reached.
+    pub fn record_download(
+        &self,
+        body_length: u64,
+        header_length: Option<u64>,
+        config: &ScanConfig,
+    ) {
+        let total = self.body_bytes.fetch_add(body_length, Ordering::Relaxed) + body_length;
+        if let Some(length) = header_length {
+            self.content_length_bytes
+                .fetch_add(length, Ordering::Relaxed);
+        }
+        if let Some(max) = config.max_bytes
+            && total >= max
+            && !self.should_stop.load(Ordering::Relaxed)
+        {
+            info!("Download limit reached.");
+            self.request_stop(StopReason::ByteLimit(max));
+        }
+    }
+
+    /// Counts a found result, stopping the scan once `max_results` are found.
+    ///
+    /// Returns false, counting nothing, for a result found after the limit was reached.
+    pub fn record_found(&self, config: &ScanConfig) -> bool {
+        let Some(max) = config.max_results else {
+            self.found_count.fetch_add(1, Ordering::Relaxed);
+            return true;
+        };
+        let counted =
+            self.found_count
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                    (total < max).then_some(total + 1)
+                });
+        match counted {
+            Ok(total) => {
+                if total + 1 >= max {
+                    info!("Found limit reached.");
+                    self.request_stop(StopReason::FoundLimit(max));
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Waits while the scan is paused, returning early when it is stopped.
+    pub async fn wait_while_paused(&self) {
+        while self.paused.load(Ordering::Relaxed) && !self.should_stop.load(Ordering::Relaxed) {
+            sleep(PAUSE_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Takes a snapshot of the counters of a scan over `total` words running for `elapsed`.
+    pub fn status(&self, total: usize, elapsed: Duration) -> ScanStatus {
+        let processed =
+            self.response_count.load(Ordering::Relaxed) + self.error_count.load(Ordering::Relaxed);
+        let eta = (processed > 0)
+            .then(|| elapsed.mul_f64(total.saturating_sub(processed) as f64 / processed as f64));
+        ScanStatus {
+            processed,
+            total,
+            found: self.found_count.load(Ordering::Relaxed),
+            errors: self.error_count.load(Ordering::Relaxed),
+            filtered: self.filtered_count.load(Ordering::Relaxed),
+            global_delay_ms: self.global_delay.load(Ordering::Relaxed),
+            elapsed,
+            eta,
+        }
+    }
+
+    /// The progress bar message for the current errors and the given concurrency, if any.
+    pub fn progress_message(&self, concurrency: Option<usize>) -> String {
+        progress_message(
+            self.error_count.load(Ordering::Relaxed),
+            self.body_bytes.load(Ordering::Relaxed),
+            concurrency,
+            self.last_error.lock().unwrap().as_deref(),
+        )
+    }
+
+    /// Moves a batch of processed words into `processed_words`, leaving the batch empty.
+    pub fn add_processed_words(&self, batch: &mut Vec<String>) {
+        if !batch.is_empty() {
+            self.processed_words.lock().unwrap().append(batch);
+        }
+    }
+
+    /// Captures the progress of the scan so far, e.g. to save it for resuming.
+    ///
+    /// The counters and word list are read under the word list lock, so no batch is added
+    /// in between. Counters of words in a batch not yet added may already include them.
+    pub fn snapshot(&self) -> ScanProgress {
+        let processed_words = self.processed_words.lock().unwrap();
+        ScanProgress {
+            processed_words: processed_words.clone(),
+            found_count: self.found_count.load(Ordering::Relaxed),
+            error_count: self.error_count.load(Ordering::Relaxed),
+            filtered_count: self.filtered_count.load(Ordering::Relaxed),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+        }
+    }
+
+    /// Returns the reason the scan was stopped, if it was.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason.get().copied()
+    }
+
+    /// Counts a request outcome against the error limits and stops the scan once one is reached.
+    pub fn track_errors(&self, result: &BustResult, config: &ScanConfig) {
+        let failed = matches!(result, BustResult::Error(..));
+
+        if let Some(max_rate) = config.max_error_rate {
+            let mut window = self.recent_outcomes.lock().unwrap();
+            if window.len() == ERROR_RATE_WINDOW {
+                window.pop_front();
+            }
+            window.push_back(failed);
+            // Only judge the rate once the window is full, so a few early errors cannot stop the scan
+            let errors = window.iter().filter(|&&failed| failed).count();
+            if window.len() == ERROR_RATE_WINDOW
+                && errors * 100 >= ERROR_RATE_WINDOW * max_rate as usize
+            {
+                self.stop_for_errors(StopReason::MaxErrorRate(max_rate));
+            }
+        }
+
+        if !failed {
+            self.consecutive_errors.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        let consecutive = self.consecutive_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        let total = self.failed_requests.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if let Some(max) = config.max_errors
+            && consecutive >= max
+        {
+            self.stop_for_errors(StopReason::MaxErrors(max));
+        }
+        if let Some(max) = config.max_total_errors
+            && total >= max
+        {
+            self.stop_for_errors(StopReason::MaxTotalErrors(max));
+        }
+    }
+
+    /// Counts a request outcome towards WAF block detection, starting a cool-down when the
+    /// target seems to block the scan. The scan resumes with a raised global delay.
+    pub fn track_blocks(&self, result: &BustResult) {
+        let Some(episode) = self
+            .waf_detector
+            .as_ref()
+            .and_then(|detector| detector.record(result))
+        else {
+            return;
+        };
+        warn!(
+            "WAF block detected: {}% of the last {WAF_WINDOW} requests were blocked, pausing for {}s",
+            episode.blocked_percent, episode.cooldown_secs
+        );
+        self.global_delay
+            .fetch_max(RESUME_DELAY_MS, Ordering::Relaxed);
+    }
+
+    /// Halves the global delay after a response that was not rate limited, so a wait the
+    /// server asked for, or the slow resume after a WAF cool-down, wears off step by step
+    /// instead of being kept for the rest of the scan.
+    pub fn relax_global_delay(&self) {
+        let _ = self
+            .global_delay
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |delay| {
+                (delay > 0).then_some(delay / 2)
+            });
+    }
+
+    /// Stops the scan for an error limit, logging why the first time.
+    fn stop_for_errors(&self, reason: StopReason) {
+        if self.stop_reason().is_none() {
+            warn!("Stopping scan: {reason}");
+        }
+        self.request_stop(reason);
+    }
+}
+
+/// How often a paused request checks whether the scan was resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The number of most recent requests the `--max-error-rate` percentage is computed over.
+pub const ERROR_RATE_WINDOW: usize = 50;
+
+/// The reason a scan was stopped before reaching the end of the word list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The user pressed Ctrl+C.
+    Interrupted,
+    /// The given number of consecutive requests failed.
+    MaxErrors(usize),
+    /// The given number of requests failed in total.
+    MaxTotalErrors(usize),
+    /// At least the given percentage of the recent requests failed.
+    MaxErrorRate(u8),
+    /// The scan ran for the given number of seconds of `--max-scan-time`.
+    TimeLimit(u64),
+    /// The scan downloaded the given number of body bytes of `--max-bytes`.
+    ByteLimit(u64),
+    /// The scan found the given number of results of `--limit`.
+    FoundLimit(usize),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Interrupted => write!(f, "interrupted by user"),
+            StopReason::MaxErrors(max) => write!(f, "{max} consecutive errors"),
+            StopReason::MaxTotalErrors(max) => write!(f, "{max} errors in total"),
+            StopReason::MaxErrorRate(rate) => write!(
+                f,
+                "{rate}% or more of the last {ERROR_RATE_WINDOW} requests failed"
+            ),
+            StopReason::TimeLimit(secs) => write!(f, "scan time limit of {secs}s reached"),
+            StopReason::ByteLimit(bytes) => {
+                write!(f, "download limit of {} reached", format_bytes(*bytes))
+            }
+            StopReason::FoundLimit(max) => write!(f, "found limit of {max} reached"),
+        }
+    }
+}
+
+/// The process exit status, describing how a scan ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The scan completed, with findings or without `--fail-on-empty`.
+    Completed,
+    /// The scan completed without findings and `--fail-on-empty` was set.
+    NoFindings,
+    /// The scan was stopped by Ctrl+C.
+    Interrupted,
+    /// The scan was aborted by errors, or the target never responded.
+    Aborted,
+    /// The command-line arguments or input files were invalid.
+    InvalidArguments,
+    /// The `check` subcommand found problems in the scan options.
+    InvalidConfig,
+}
+
+impl ExitStatus {
+    /// Picks the exit status for a finished scan from its stop reason and counters.
+    pub fn for_scan(state: &ScanState, fail_on_empty: bool) -> Self {
+        match state.stop_reason() {
+            Some(StopReason::Interrupted) => ExitStatus::Interrupted,
+            Some(
+                StopReason::MaxErrors(_)
+                | StopReason::MaxTotalErrors(_)
+                | StopReason::MaxErrorRate(_),
+            ) => ExitStatus::Aborted,
+            // A scan stopped by its time, byte or found limit ends like a completed one
+            Some(
+                StopReason::TimeLimit(_) | StopReason::ByteLimit(_) | StopReason::FoundLimit(_),
+            )
+            | None => {
+                if state.response_count.load(Ordering::Relaxed) == 0 {
+                    ExitStatus::Aborted
+                } else if state.found_count.load(Ordering::Relaxed) == 0 && fail_on_empty {
+                    ExitStatus::NoFindings
+                } else {
+                    ExitStatus::Completed
+                }
+            }
+        }
+    }
+
+    /// Returns the numeric process exit code.
+    pub fn code(self) -> u8 {
+        match self {
+            ExitStatus::Completed => 0,
+            ExitStatus::NoFindings => 1,
+            ExitStatus::Interrupted => 2,
+            ExitStatus::Aborted => 3,
+            ExitStatus::InvalidArguments => 4,
+            ExitStatus::InvalidConfig => 5,
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ExitStatus::Completed => "completed",
+            ExitStatus::NoFindings => "no findings",
+            ExitStatus::Interrupted => "interrupted",
+            ExitStatus::Aborted => "aborted",
+            ExitStatus::InvalidArguments => "invalid arguments",
+            ExitStatus::InvalidConfig => "invalid configuration",
+        };
+        write!(f, "{} ({name})", self.code())
+    }
+}
+
+/// The words of the `--retry-failed-pass` pass: those that failed with an error in the
+/// earlier passes, whose errors are held back until they fail again.
+#[derive(Debug, Default)]
+pub struct RetryPass {
+    /// The errors held back, one per failed word.
+    failed: Mutex<Vec<BustResult>>,
+    /// Counter for the words of the retry pass that did not fail again.
+    recovered: AtomicUsize,
+}
+
+impl RetryPass {
+    /// The concurrency of the retry pass: the configured one, or a quarter of `threads`,
+    /// so a struggling target is given some room.
+    pub fn concurrency(threads: usize, configured: Option<u32>) -> usize {
+        configured.map_or((threads / 4).max(1), |configured| configured as usize)
+    }
+
+    /// Holds back the error of a failed request, whose word is retried in the retry pass.
+    /// Returns true if it was held back.
+    pub fn defer(&self, result: &BustResult) -> bool {
+        let failed = result.response().is_none();
+        if failed {
+            self.failed.lock().unwrap().push(result.clone());
+        }
+        failed
+    }
+
+    /// Counts a word of the retry pass as recovered, unless it failed again.
+    pub fn record_retry(&self, result: &BustResult) {
+        if result.response().is_some() {
+            self.recovered.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Takes the words held back so far, to be scanned in the retry pass.
+    pub fn take_words(&self) -> Vec<String> {
+        self.take_errors()
+            .iter()
+            .map(|result| result.word().to_string())
+            .collect()
+    }
+
+    /// Takes the errors held back so far, to be reported when the scan stops before the
+    /// retry pass.
+    pub fn take_errors(&self) -> Vec<BustResult> {
+        std::mem::take(&mut *self.failed.lock().unwrap())
+    }
+
+    /// The number of words the retry pass recovered.
+    pub fn recovered(&self) -> usize {
+        self.recovered.load(Ordering::Relaxed)
+    }
+}
+
+/// The error of a request cut short because the scan was stopped. Its word counts as not scanned.
+pub const SCAN_STOPPED: &str = "Scan stopped";
+
+/// How many processed words are collected before they are added to the scan state at once.
+pub const PROCESSED_BATCH_SIZE: usize = 100;
+
 /// Represents the data saved to a file for resuming a scan.
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ScanProgress {
     /// The list of words that have already been processed.
     pub processed_words: Vec<String>,
+    #[serde(default)]
+    pub found_count: usize,
+    #[serde(default)]
+    pub error_count: usize,
+    #[serde(default)]
+    pub filtered_count: usize,
     /// The timestamp when the progress was saved.
     pub timestamp: u64,
 }
 
+impl ScanProgress {
+    /// Writes the progress to a file, through a temporary file renamed into place.
+    pub fn save_to_disk(&self, path: &str) -> std::io::Result<()> {
+        let tmp_path = format!("{path}.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    /// Reads the progress saved by an earlier scan.
+    pub fn load_from_disk(path: &str) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// A pair of clients pinned to HTTP/1.1 and HTTP/2, used for version probing.
+pub struct VersionProbeClients {
+    pub http1: Client,
+    pub http2: Client,
+}
+
+/// Returns the URL requested for a word, with the word encoded according to the config.
+///
+/// The word is placed under the path prefix, if any. Slashes around the prefix are
+/// normalized, so `api/v2`, `/api/v2/` and `api/v2/` all produce `{base_url}/api/v2/{word}`.
+pub fn request_url(word: &str, config: &ScanConfig) -> String {
+    build_url(word, config, Template::display)
+}
+
+/// Returns the URL requested for a word, with the placeholders of a `--url` template filled
+/// in at `timestamp`. Unlike `request_url`, it differs from one request to the next.
+pub fn expanded_request_url(word: &str, config: &ScanConfig, timestamp: i64) -> String {
+    build_url(word, config, |template, word| {
+        template.expand(&TemplateValues {
+            timestamp,
+            ..TemplateValues::new(word)
+        })
+    })
+}
+
+/// Builds the URL of a word, filling a URL template with `fill`.
+fn build_url(word: &str, config: &ScanConfig, fill: impl Fn(&Template, &str) -> String) -> String {
+    let base = config.base_url.trim_end_matches('/');
+    let word = parser::encode_word(word, config.encode_mode);
+    let word = match &config.url_template {
+        Some(template) => fill(template, &word),
+        None => word,
+    };
+    let prefix = config.path_prefix.trim_matches('/');
+    if prefix.is_empty() {
+        format!("{base}/{word}")
+    } else {
+        format!("{base}/{prefix}/{word}")
+    }
+}
+
+/// Fills in the custom headers with placeholders for one request at `timestamp`.
+///
+/// Fails if a value is not valid in a header once filled in, e.g. because the word has a
+/// control character, so the request is not sent with the unexpanded template instead.
+/// Names that are not valid HTTP are skipped, as by `request_headers`.
+pub fn expanded_headers(
+    word: &str,
+    config: &ScanConfig,
+    timestamp: i64,
+) -> Result<HeaderMap, String> {
+    let word = parser::encode_word(word, config.encode_mode);
+    let values = TemplateValues {
+        timestamp,
+        ..TemplateValues::new(&word)
+    };
+    let mut headers = HeaderMap::new();
+    for (name, template) in &config.header_templates {
+        let Ok(header) = HeaderName::from_bytes(name.as_bytes()) else {
+            continue;
+        };
+        let value = template.expand(&values);
+        let value = HeaderValue::from_str(&value)
+            .map_err(|_| format!("Header {name}: `{value}` is not a valid header value"))?;
+        headers.insert(header, value);
+    }
+    Ok(headers)
+}
+
+/// Computes the `--sign-hmac` headers of a request to `url` at `timestamp`, signing the
+/// path and query the server sees.
+pub fn signing_headers(spec: &SigningSpec, word: &str, url: &str, timestamp: i64) -> HeaderMap {
+    let path = match Url::parse(url) {
+        Ok(url) => match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        },
+        Err(_) => String::new(),
+    };
+    spec.headers(&TemplateValues {
+        word,
+        timestamp,
+        method: "GET",
+        path: &path,
+    })
+}
+
 /// Generates a random IP address string.
 fn random_ip() -> String {
     let mut rng = rand::rng();
@@ -145,6 +1123,192 @@ fn random_encoding() -> &'static str {
         .expect("Can't choose language")
 }
 
+/// Builds a bare GET request for a URL, sent over HTTP/3 once `--http3` is in effect.
+///
+/// A client built for HTTP/3 still speaks HTTP/1.1 and HTTP/2 unless a request asks for
+/// HTTP/3, so every request of the scan and of the wildcard probes is built here.
+pub fn get_request(client: &Client, url: &str, config: &ScanConfig) -> RequestBuilder {
+    match config.http3 {
+        true => client.get(url).version(Version::HTTP_3),
+        false => client.get(url),
+    }
+}
+
+/// Builds the GET request for a URL with all configured headers applied.
+///
+/// This covers header rotation, authentication, custom headers and the browser-like
+/// headers, but not bypass header injection, which is applied separately so that a
+/// plain baseline request can be built for comparison.
+pub fn build_request(client: &Client, full_path: &str, config: &ScanConfig) -> RequestBuilder {
+    let mut rng = rand::rng();
+    let mut request = get_request(client, full_path, config).headers(request_headers(config));
+
+    // With --padding-body, occasionally add a small request body
+    if let Some((min, max)) = config.padding_body
+        && rng.random_range(0..10) < 3
+    {
+        request = request.body(" ".repeat(rng.random_range(min..=max)));
+    }
+
+    request
+}
+
+/// Returns the headers `build_request` sends, without needing a client.
+///
+/// Rotated and randomized headers get a fresh value on every call. Header names or values
+/// that are not valid HTTP are skipped.
+pub fn request_headers(config: &ScanConfig) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let mut insert = |name: &str, value: &str| {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.insert(name, value);
+        }
+    };
+
+    // Apply header rotation and other evasion techniques
+    if config.rotate_user_agent {
+        insert(USER_AGENT.as_str(), random_user_agent(&config.user_agents));
+    }
+
+    if config.rotate_ip_headers {
+        let spoofed_ip = random_ip();
+        insert("X-Forwarded-For", &spoofed_ip);
+        insert("X-Real-IP", &spoofed_ip);
+        insert("True-Client-IP", &spoofed_ip);
+    }
+
+    // Apply authentication headers
+    if let Some(auth) = &config.auth_header {
+        insert(AUTHORIZATION.as_str(), auth);
+    }
+
+    if let Some(basic) = &config.basic_auth
+        && let Some((user, pass)) = basic.split_once(':')
+    {
+        let credentials = BASE64_STANDARD.encode(format!("{user}:{pass}"));
+        insert(AUTHORIZATION.as_str(), &format!("Basic {credentials}"));
+    }
+
+    if let Some(token) = &config.bearer_token {
+        insert(AUTHORIZATION.as_str(), &format!("Bearer {token}"));
+    }
+
+    if let Some(cookie) = &config.cookie {
+        insert(COOKIE.as_str(), cookie);
+    }
+
+    // An explicit Referer is sent even without the browser-like headers, an empty one never
+    match config.referer.as_deref() {
+        Some("") => {}
+        Some(referer) => insert("Referer", referer),
+        None if !config.no_browser_headers => insert("Referer", random_referer()),
+        None => {}
+    }
+
+    // Like the Referer, an explicit Accept is sent even without the browser-like headers
+    match &config.accept_header {
+        Some(accept) => insert("Accept", accept),
+        None if !config.no_browser_headers => insert(
+            "Accept",
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+        ),
+        None => {}
+    }
+
+    // A pinned Accept-Encoding is sent even without the browser-like headers
+    match (config.force_no_encoding, &config.force_encoding) {
+        (true, _) => insert("Accept-Encoding", "identity"),
+        (false, Some(encoding)) => insert("Accept-Encoding", encoding),
+        (false, None) if !config.no_browser_headers => insert("Accept-Encoding", random_encoding()),
+        (false, None) => {}
+    }
+
+    // Apply common browser-like headers
+    if !config.no_browser_headers {
+        insert("Accept-Language", random_language());
+        insert("DNT", "1");
+        insert("Connection", "keep-alive");
+        insert("Sec-Fetch-Site", "none");
+        insert("Sec-Fetch-Mode", "navigate");
+        insert("Sec-Fetch-User", "?1");
+        insert("Sec-Fetch-Dest", "document");
+        insert("Upgrade-Insecure-Requests", "1");
+    }
+
+    // Custom headers go last, so they replace any default of the same name
+    for (key, value) in &config.custom_headers {
+        insert(key, value);
+    }
+
+    for name in [AUTHORIZATION, COOKIE] {
+        if let Some(value) = headers.get_mut(name) {
+            value.set_sensitive(true);
+        }
+    }
+    headers
+}
+
+/// Header values claiming the request originates from the local machine.
+const CLIENT_IP_HEADERS: [&str; 7] = [
+    "X-Forwarded-For",
+    "X-Real-IP",
+    "X-Originating-IP",
+    "X-Remote-IP",
+    "X-Remote-Addr",
+    "X-Client-IP",
+    "X-Custom-IP-Authorization",
+];
+
+/// Adds access-control bypass headers for the given mode to a request.
+///
+/// These are added on top of the normal headers built by `build_request`.
+/// Note that method override headers ask the server to treat the request as a `DELETE`,
+/// which frameworks honoring them may act upon.
+pub fn inject_bypass_headers(
+    request: RequestBuilder,
+    word: &str,
+    mode: HeaderInjectMode,
+) -> RequestBuilder {
+    request.headers(bypass_headers(word, mode))
+}
+
+/// Returns the access-control bypass headers for the given mode.
+pub fn bypass_headers(word: &str, mode: HeaderInjectMode) -> HeaderMap {
+    let all = mode == HeaderInjectMode::All;
+    let mut headers = HeaderMap::new();
+    let delete = HeaderValue::from_static("DELETE");
+    let localhost = HeaderValue::from_static("127.0.0.1");
+
+    if all || mode == HeaderInjectMode::MethodOverride {
+        for header in [
+            "X-HTTP-Method-Override",
+            "X-HTTP-Method",
+            "X-Method-Override",
+        ] {
+            headers.insert(header, delete.clone());
+        }
+    }
+
+    if all || mode == HeaderInjectMode::UrlOverride {
+        let path = format!("/{}", word.trim_start_matches('/'));
+        if let Ok(path) = HeaderValue::from_str(&path) {
+            headers.insert("X-Original-URL", path.clone());
+            headers.insert("X-Rewrite-URL", path);
+        }
+    }
+
+    if all || mode == HeaderInjectMode::ClientIp {
+        for header in CLIENT_IP_HEADERS {
+            headers.insert(header, localhost.clone());
+        }
+    }
+
+    headers
+}
+
 /// Performs a single HTTP GET request for a given word, with retry logic.
 ///
 /// This is the core function of the scanner. It constructs the full URL,
@@ -156,26 +1320,166 @@ pub async fn bust_url_with_retry(
     config: &ScanConfig,
     state: &ScanState,
 ) -> BustResult {
-    let mut rng = rand::rng();
+    let result = send_with_retry(client, word, config, state).await;
+    state.track_errors(&result, config);
+    state.track_blocks(&result);
+    result
+}
+
+/// Returns the name of an HTTP version as shown in output and reports.
+fn version_name(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
+    }
+}
+
+/// Reads a response body as text, stopping after `max_size` bytes. Returns the body, the
+/// number of bytes read and whether it was cut short.
+///
+/// A body that fails partway is kept as far as it was read, and counts as cut short.
+/// Invalid UTF-8 is replaced, as the body is only counted, hashed and searched, so the
+/// length is taken before that.
+async fn read_body(mut response: Response, max_size: Option<u64>) -> (String, u64, bool) {
+    let limit = max_size.map_or(usize::MAX, |size| size as usize);
+    let mut body = Vec::new();
+    let mut truncated = false;
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                debug!(
+                    url = %response.url(),
+                    read = body.len(),
+                    error = %e,
+                    "Body read failed partway, keeping what was read"
+                );
+                truncated = true;
+                break;
+            }
+        };
+        let room = limit - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let length = body.len() as u64;
+    (
+        String::from_utf8_lossy(&body).into_owned(),
+        length,
+        truncated,
+    )
+}
+
+/// Sends a request, recording it and its outcome in the `--request-log` if there is one.
+async fn execute_logged(
+    client: &Client,
+    request: reqwest::Request,
+    state: &ScanState,
+    config: &ScanConfig,
+) -> reqwest::Result<Response> {
+    let Some(log) = &state.request_log else {
+        return client.execute(request).await;
+    };
+    let entry = RequestLogEntry::new(&request, config);
+    let sent = client.execute(request).await;
+    log.record(entry.with_outcome(&sent, config));
+    sent
+}
+
+/// Returns true if the response headers declare an HTML body.
+fn is_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.to_ascii_lowercase().contains("html"))
+}
 
-    // Add a random suffix for cache-busting
-    let suffix = match rng.random_range(0..4) {
+/// Returns how long a response's `Retry-After` header asks to wait, capped at `max`.
+pub fn retry_after(headers: &HeaderMap, max: Duration) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    parser::parse_retry_after(value, Utc::now()).map(|wait| wait.min(max))
+}
+
+/// Returns how long the rate limit headers of a response ask to wait, capped at `max`: the
+/// `Retry-After` wait, or the time until `X-RateLimit-Reset` once `X-RateLimit-Remaining`
+/// reached 0.
+pub fn rate_limit_wait(headers: &HeaderMap, max: Duration) -> Option<Duration> {
+    if let Some(wait) = retry_after(headers, max) {
+        return Some(wait);
+    }
+    if header_number(headers, "x-ratelimit-remaining")? > 0 {
+        return None;
+    }
+    let reset = headers.get("x-ratelimit-reset")?.to_str().ok()?;
+    parser::parse_rate_limit_reset(reset, Utc::now()).map(|wait| wait.min(max))
+}
+
+/// Reads a header holding a non-negative number.
+fn header_number(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Raises the global delay to a wait the server asked for, so all workers slow down.
+/// Returns the resulting global delay in milliseconds.
+fn honor_retry_after(state: &ScanState, wait: Duration) -> u64 {
+    let wait_ms = wait.as_millis() as u64;
+    state
+        .global_delay
+        .fetch_max(wait_ms, Ordering::Relaxed)
+        .max(wait_ms)
+}
+
+/// Returns a random suffix appended to the URL with `--cache-bust`, so caches in front of
+/// the server do not answer in its place. One in four requests gets none.
+pub fn cache_bust_suffix(rng: &mut impl Rng) -> String {
+    match rng.random_range(0..4) {
         0 => format!("?_cb={}", rng.random_range(10000..99999)),
         1 => format!("#{}", rng.random_range(1000..9999)),
         2 => format!(";sessionid={}", rng.random_range(100000..999999)),
         _ => String::new(),
+    }
+}
+
+/// Sends the request for a word, retrying on rate limits, server errors and network errors.
+async fn send_with_retry(
+    client: &Client,
+    word: String,
+    config: &ScanConfig,
+    state: &ScanState,
+) -> BustResult {
+    let mut rng = rand::rng();
+
+    // A URL template places the word itself, possibly before a query string
+    let suffix = if config.cache_bust && config.url_template.is_none() {
+        cache_bust_suffix(&mut rng)
+    } else {
+        String::new()
     };
 
-    let full_path = format!(
-        "{}/{}{}",
-        config.base_url.trim_end_matches('/'),
-        word,
-        suffix
-    );
+    let url = request_url(&word, config);
+    let cached = state
+        .response_cache
+        .as_ref()
+        .and_then(|cache| cache.get(&url));
+
+    // The status of the request without bypass headers, sent once per word
+    let mut baseline_status = None;
+    // Set after sleeping for a Retry-After wait, which already covers the global delay
+    let mut waited_retry_after = false;
 
     for attempt in 0..=config.retries {
+        state.wait_while_paused().await;
         if state.should_stop.load(Ordering::Relaxed) {
-            return BustResult::Error(word, "Scan stopped by user".to_string());
+            return BustResult::Error(word, SCAN_STOPPED.to_string(), ErrorKind::Other);
         }
 
         // Apply delay between requests
@@ -185,130 +1489,483 @@ pub async fn bust_url_with_retry(
             config.delay_min
         };
 
-        let extra_backoff = state.global_delay.load(Ordering::Relaxed);
+        let extra_backoff = if std::mem::take(&mut waited_retry_after) {
+            0
+        } else {
+            state.global_delay.load(Ordering::Relaxed)
+        };
         sleep_base += extra_backoff;
 
         if sleep_base > 0 {
             let jitter = rng.random_range(0..100);
+            trace!(
+                word,
+                delay_ms = sleep_base + jitter,
+                extra_backoff,
+                "Delaying request"
+            );
             sleep(Duration::from_millis(sleep_base + jitter)).await;
         }
 
-        let start_time = Instant::now();
-        let mut request = client.get(&full_path);
-
-        // Apply header rotation and other evasion techniques
-        if config.rotate_user_agent {
-            request = request.header(USER_AGENT, random_user_agent(&config.user_agents));
+        if let Some(detector) = &state.waf_detector {
+            detector.wait().await;
         }
 
-        if config.rotate_ip_headers {
-            let spoofed_ip = random_ip();
-            request = request
-                .header("X-Forwarded-For", &spoofed_ip)
-                .header("X-Real-IP", &spoofed_ip)
-                .header("True-Client-IP", &spoofed_ip);
+        if let Some(limiter) = &state.rate_limiter {
+            limiter
+                .acquire(Duration::from_millis(
+                    state.global_delay.load(Ordering::Relaxed),
+                ))
+                .await;
         }
 
-        // Apply authentication headers
-        if let Some(auth) = &config.auth_header {
-            request = request.header("Authorization", auth);
-        }
+        // Requests wait while the session is renewed, then send the renewed credential
+        let reauth_episode = match &state.reauth {
+            Some(reauth) => {
+                reauth.wait().await;
+                reauth.episodes()
+            }
+            None => 0,
+        };
+
+        // When rotating proxies, every attempt goes through the next one
+        let proxy = state
+            .proxies
+            .as_ref()
+            .map(|rotation| (rotation, rotation.next()));
+        let client = proxy.map_or(client, |(_, proxy)| &proxy.client);
 
-        if let Some(basic) = &config.basic_auth {
-            if let Some((user, pass)) = basic.split_once(':') {
-                request = request.basic_auth(user, Some(pass));
+        // One timestamp per request, shared by the templates and the signature
+        let timestamp = Utc::now().timestamp();
+        let full_path = match config.url_template {
+            Some(_) => expanded_request_url(&word, config, timestamp),
+            None => format!("{url}{suffix}"),
+        };
+        debug!(word, attempt, url = full_path, "Sending request");
+        let start_time = Instant::now();
+        let mut request = build_request(client, &full_path, config);
+        if !config.header_templates.is_empty() {
+            match expanded_headers(&word, config, timestamp) {
+                Ok(headers) => request = request.headers(headers),
+                Err(e) => return BustResult::Error(word, e, ErrorKind::Other),
             }
         }
-
-        if let Some(token) = &config.bearer_token {
-            request = request.bearer_auth(token);
+        if let Some(reauth) = &state.reauth {
+            request = request.headers(reauth.headers());
         }
-
-        for (key, value) in &config.custom_headers {
-            request = request.header(key, value);
+        let mut request = inject_bypass_headers(request, &word, config.header_inject_mode);
+        if let Some(entry) = &cached {
+            request = request.headers(entry.conditional_headers());
         }
 
-        // Apply common browser-like headers
-        request = request
-            .header("Referer", random_referer())
-            .header("Accept-Language", random_language())
-            .header("Accept-Encoding", random_encoding())
-            .header(
-                "Accept",
-                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
-            )
-            .header("DNT", "1")
-            .header("Connection", "keep-alive")
-            .header("Sec-Fetch-Site", "none")
-            .header("Sec-Fetch-Mode", "navigate")
-            .header("Sec-Fetch-User", "?1")
-            .header("Sec-Fetch-Dest", "document")
-            .header("Upgrade-Insecure-Requests", "1");
+        if let Some(spec) = &config.signing {
+            request = request.headers(signing_headers(spec, &word, &full_path, timestamp));
+        }
 
-        // Occasionally add a small request body
-        if rng.random_range(0..10) < 3 {
-            request = request.body(" ".repeat(rng.random_range(10..50)));
+        let sent = match request.build() {
+            Ok(request) => {
+                if config.verbose {
+                    print_diagnostic(&format_request_headers(
+                        request.method().as_str(),
+                        request.url().as_str(),
+                        request.headers(),
+                        config,
+                    ));
+                }
+                execute_logged(client, request, state, config).await
+            }
+            Err(e) => Err(e),
+        };
+        if config.verbose
+            && let Ok(response) = &sent
+        {
+            print_diagnostic(&format_response_headers(
+                version_name(response.version()),
+                response.status(),
+                response.headers(),
+                config,
+            ));
+        }
+        if let Some((rotation, proxy)) = proxy {
+            let ok = sent.as_ref().is_ok_and(|response| {
+                response.status() != StatusCode::PROXY_AUTHENTICATION_REQUIRED
+            });
+            if rotation.record(proxy, ok) {
+                warn!(
+                    "Proxy {} failed {PROXY_FAILURE_LIMIT} requests in a row, skipping it for {}s",
+                    proxy.label,
+                    rotation.cooldown().as_secs()
+                );
+            }
         }
 
-        match request.send().await {
+        match sent {
             Ok(response) => {
-                let status = response.status().as_u16();
+                let mut status = response.status().as_u16();
+                let version = response.version();
                 let headers = response.headers().clone();
-                let content_length = response.content_length();
+                let mut content_length = response.content_length();
                 let response_time = start_time.elapsed();
+                state.metrics.record_response(status, response_time);
+                trace!(
+                    word,
+                    status,
+                    elapsed_ms = response_time.as_millis() as u64,
+                    "Received response"
+                );
 
-                let response_text: String = response.text().await.unwrap_or_default();
+                // A response showing the session expired is sent again once it is renewed
+                if let Some(reauth) = &state.reauth {
+                    let location = headers.get(LOCATION).and_then(|value| value.to_str().ok());
+                    if !reauth.is_trigger(status, location) {
+                        reauth.clear_streak();
+                    } else if reauth.on_trigger(reauth_episode).await && attempt < config.retries {
+                        debug!(word, attempt, status, "Session renewed, resending");
+                        continue;
+                    }
+                }
 
-                let word_count = if config.show_content_length || config.filter_words.is_some() {
-                    Some(response_text.split_whitespace().count())
-                } else {
-                    None
+                // The server announced its rate limit is used up, so all workers wait for it
+                let rate_limit = config
+                    .respect_rate_limit_headers
+                    .then(|| rate_limit_wait(&headers, config.max_retry_after))
+                    .flatten();
+                if let Some(wait) = rate_limit {
+                    let global_delay = honor_retry_after(state, wait);
+                    debug!(
+                        word,
+                        status,
+                        limit = header_number(&headers, "x-ratelimit-limit"),
+                        remaining = header_number(&headers, "x-ratelimit-remaining"),
+                        wait_ms = wait.as_millis() as u64,
+                        global_delay,
+                        "Server rate limit reached, waiting for it to reset"
+                    );
+                }
+
+                // A 304 confirms the cached response is unchanged, so its body is not needed
+                let reused = match &cached {
+                    Some(entry) if status == 304 => {
+                        debug!(
+                            word,
+                            status = entry.status,
+                            "Not modified, reusing cached response"
+                        );
+                        status = entry.status;
+                        content_length = entry.content_length;
+                        Some(entry)
+                    }
+                    _ => None,
+                };
+
+                let body_start = Instant::now();
+                let (response_text, read_length, body_truncated) = match reused {
+                    Some(_) => (String::new(), 0, false),
+                    None => read_body(response, config.max_body_size).await,
+                };
+                let body_read_time =
+                    (config.timing_detail && reused.is_none()).then(|| body_start.elapsed());
+
+                // Chunked responses have no Content-Length, but the body read anyway gives the
+                // size, unless it was cut short
+                let header_length = content_length.filter(|_| reused.is_none());
+                let body_length = reused.is_none().then_some(read_length);
+                if let Some(length) = body_length {
+                    state.record_download(length, header_length, config);
+                }
+                if !body_truncated {
+                    content_length = content_length.or(body_length);
+                }
+
+                let word_count = match reused {
+                    Some(entry) => entry.word_count,
+                    None if config.show_word_count
+                        || config.filter_words.is_some()
+                        || config.smart_filter
+                        || state.response_cache.is_some() =>
+                    {
+                        Some(response_text.split_whitespace().count())
+                    }
+                    None => None,
+                };
+                let line_count = match reused {
+                    Some(entry) => entry.line_count,
+                    None if config.show_line_count || state.response_cache.is_some() => {
+                        Some(response_text.lines().count())
+                    }
+                    None => None,
+                };
+
+                let location = match reused {
+                    Some(entry) => entry.location.clone(),
+                    None => headers
+                        .get(LOCATION)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string),
+                };
+
+                let wildcard_sample = match reused {
+                    Some(entry) => entry.wildcard_sample.clone(),
+                    None if config.detect_wildcards && (200..=299).contains(&status) => {
+                        let headers_map: HashMap<String, String> = headers
+                            .iter()
+                            .map(|(k, v)| {
+                                (k.as_str().to_string(), v.to_str().unwrap_or("").to_string())
+                            })
+                            .collect();
+                        let mut sample =
+                            WildcardSample::from_response(&response_text, status, &headers_map);
+                        // The size of a truncated body is compared by its full length
+                        if body_truncated && let Some(length) = header_length {
+                            sample.size = length as usize;
+                        }
+                        Some(sample)
+                    }
+                    None => None,
+                };
+
+                let content_type = match reused {
+                    Some(entry) => entry.content_type.clone(),
+                    None => headers
+                        .get(CONTENT_TYPE)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string),
                 };
 
-                let detailed_response = DetailedResponse {
+                let diff_score = match reused {
+                    Some(entry) => entry.diff_score,
+                    None => state
+                        .baseline_body
+                        .as_deref()
+                        .map(|baseline| diff_score(&response_text, baseline)),
+                };
+
+                // Remember responses with validators for the next scan
+                if let Some(cache) = &state.response_cache
+                    && reused.is_none()
+                    && status != 429
+                    && status < 500
+                {
+                    let header = |name| {
+                        headers
+                            .get(name)
+                            .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                            .map(str::to_string)
+                    };
+                    cache.insert(
+                        &url,
+                        CachedResponse {
+                            etag: header(ETAG),
+                            last_modified: header(LAST_MODIFIED),
+                            status,
+                            content_length,
+                            word_count,
+                            line_count,
+                            location: location.clone(),
+                            content_type: content_type.clone(),
+                            diff_score,
+                            wildcard_sample: wildcard_sample.clone(),
+                        },
+                    );
+                }
+
+                // Compare against a request without the injected headers, so only
+                // responses the bypass headers actually changed are attributed to them
+                if config.header_inject_mode != HeaderInjectMode::None && baseline_status.is_none()
+                {
+                    if let Some(limiter) = &state.rate_limiter {
+                        limiter.acquire(Duration::ZERO).await;
+                    }
+                    let baseline = match build_request(client, &full_path, config).build() {
+                        Ok(request) => execute_logged(client, request, state, config).await,
+                        Err(e) => Err(e),
+                    };
+                    baseline_status = baseline.ok().map(|baseline| baseline.status().as_u16());
+                }
+                let bypass_mode = baseline_status
+                    .filter(|&baseline| baseline != status)
+                    .map(|_| config.header_inject_mode);
+
+                let mut detailed_response = DetailedResponse {
                     word: word.clone(),
                     status,
                     content_length,
+                    header_length,
+                    body_length,
+                    body_truncated,
                     response_time,
+                    body_read_time,
                     word_count,
+                    line_count,
+                    bypass_mode,
+                    probed_version: None,
+                    http_version: Some(version_name(version)),
+                    wildcard_confidence: None,
+                    diff_score,
+                    source: state.word_source(&word, config),
+                    requested_url: full_path.clone(),
+                    content_type,
+                    set_cookies: if config.show_cookies && (200..=299).contains(&status) {
+                        headers
+                            .get_all(SET_COOKIE)
+                            .iter()
+                            .filter_map(|value| value.to_str().ok())
+                            .map(str::to_string)
+                            .collect()
+                    } else {
+                        Vec::new()
+                    },
                 };
 
+                // Statuses retried beyond the rate limit and server error handling below
+                if attempt < config.retries
+                    && !matches!(status, 429 | 500..=599)
+                    && config.retry_policy.retries_status(status)
+                {
+                    debug!(word, attempt, status, "Retrying status");
+                    sleep(Duration::from_millis(500 * (attempt + 1) as u64)).await;
+                    continue;
+                }
+
+                // Pages carrying injected markup, e.g. a WAF's block page, are dropped whatever the
+                // status, except a rate limit or a server error that is still retried
+                let retried_server_error = matches!(status, 500..=599)
+                    && attempt < config.retries
+                    && config.retry_policy.retries_status(status);
+                if status != 429
+                    && !retried_server_error
+                    && let Some(tag) = config
+                        .filter_html_tags
+                        .iter()
+                        .find(|tag| parser::body_contains_tag(&response_text, tag))
+                {
+                    debug!(word, status, tag, "Filtered by HTML tag");
+                    return BustResult::Filtered(detailed_response);
+                }
+
+                // So are responses like the typical one learned with --smart-filter
+                if status != 429
+                    && let Some(profile) = state.smart_filter.get()
+                    && profile.matches(&detailed_response)
+                {
+                    debug!(word, status, "Filtered by smart filter");
+                    return BustResult::Filtered(detailed_response);
+                }
+
+                // And with --dedup-responses, every body after the first identical one
+                if config.dedup_body_hash
+                    && reused.is_none()
+                    && status != 429
+                    && !state
+                        .seen_body_hashes
+                        .lock()
+                        .unwrap()
+                        .insert(sha256_hex(&response_text))
+                {
+                    debug!(
+                        word,
+                        status, "Filtered as a duplicate of an earlier response body"
+                    );
+                    state.deduplicated_count.fetch_add(1, Ordering::Relaxed);
+                    return BustResult::Filtered(detailed_response);
+                }
+
+                if status != 429 && rate_limit.is_none() {
+                    state.relax_global_delay();
+                }
+
                 match status {
                     200..=299 => {
-                        state.global_delay.store(0, Ordering::Relaxed);
-
                         if parser::should_filter_response(&detailed_response, config) {
                             return BustResult::Filtered(detailed_response);
                         }
 
-                        let headers_map: HashMap<String, String> = headers
-                            .iter()
-                            .map(|(k, v)| {
-                                (k.as_str().to_string(), v.to_str().unwrap_or("").to_string())
-                            })
-                            .collect();
-
-                        if config.detect_wildcards {
-                            let sample =
-                                WildcardSample::from_response(&response_text, status, &headers_map);
-                            if state.wildcard_profile.is_likely_wildcard(&sample) {
+                        if let Some(sample) = &wildcard_sample {
+                            let report = state.wildcard_profile.score(sample);
+                            let (is_wildcard, confidence) = (report.is_wildcard, report.confidence);
+                            if config.debug_wildcards {
+                                // Shown without -v, since the flag asks for it
+                                info!("Wildcard check for {url}: {report}");
+                            } else {
+                                debug!(word, confidence, is_wildcard, "Wildcard check");
+                            }
+                            detailed_response.wildcard_confidence = Some(confidence);
+                            if is_wildcard {
                                 return BustResult::Filtered(detailed_response);
                             }
                         }
+                        if config.extract_words {
+                            let words = parser::extract_words_from_body(&response_text);
+                            state.harvested_words.lock().unwrap().extend(words);
+                        }
+                        if let Some(spider) = &state.spider
+                            && is_html(&headers)
+                        {
+                            let links =
+                                spider::extract_links(&response_text, &url, &config.base_url);
+                            let queued = spider.queue_links(links);
+                            debug!(word, queued, "Queued links");
+                        }
                         return BustResult::Success(detailed_response);
                     }
                     429 => {
-                        // Rate limited, increase global delay and retry
-                        state.global_delay.fetch_add(500, Ordering::Relaxed);
-                        if attempt < config.retries {
-                            sleep(Duration::from_millis(1000 * (attempt + 1) as u64)).await;
+                        // Rate limited, increase global delay and retry, waiting as long as
+                        // the server asked for when it said
+                        let wait =
+                            rate_limit.or_else(|| retry_after(&headers, config.max_retry_after));
+                        let global_delay = match wait {
+                            Some(wait) => honor_retry_after(state, wait),
+                            None => state.global_delay.fetch_add(500, Ordering::Relaxed) + 500,
+                        };
+                        debug!(
+                            word,
+                            attempt,
+                            global_delay,
+                            retry_after_ms = wait.map(|wait| wait.as_millis() as u64),
+                            "Rate limited, increased global delay"
+                        );
+                        if attempt < config.retries && config.retry_policy.retries_status(status) {
+                            match wait {
+                                Some(wait) => {
+                                    sleep(wait).await;
+                                    waited_retry_after = true;
+                                }
+                                None => {
+                                    sleep(Duration::from_millis(1000 * (attempt + 1) as u64)).await
+                                }
+                            }
                             continue;
                         }
-                        return BustResult::Error(word, "Rate limited".to_string());
+                        return BustResult::Error(
+                            word,
+                            "Rate limited".to_string(),
+                            ErrorKind::Other,
+                        );
                     }
                     500..=599 => {
+                        // An unavailable server may say when to come back
+                        let retry =
+                            attempt < config.retries && config.retry_policy.retries_status(status);
+                        if status == 503
+                            && retry
+                            && let Some(wait) = retry_after(&headers, config.max_retry_after)
+                        {
+                            let global_delay = honor_retry_after(state, wait);
+                            debug!(
+                                word,
+                                attempt,
+                                global_delay,
+                                retry_after_ms = wait.as_millis() as u64,
+                                "Service unavailable, waiting for Retry-After"
+                            );
+                            sleep(wait).await;
+                            waited_retry_after = true;
+                            continue;
+                        }
+
                         // Server error, retry after a short delay
-                        if attempt < config.retries {
+                        if retry {
+                            debug!(word, attempt, status, "Server error, retrying");
                             sleep(Duration::from_millis(500 * (attempt + 1) as u64)).await;
                             continue;
                         }
@@ -319,8 +1976,23 @@ pub async fn bust_url_with_retry(
 
                         return BustResult::NotFound(detailed_response);
                     }
+                    300..=399 => {
+                        if parser::should_filter_response(&detailed_response, config) {
+                            return BustResult::Filtered(detailed_response);
+                        }
+                        return BustResult::Redirect(
+                            detailed_response,
+                            location.unwrap_or_default(),
+                        );
+                    }
+                    400..=499 => {
+                        // Handle client errors (e.g., 404, 403)
+                        if parser::should_filter_response(&detailed_response, config) {
+                            return BustResult::Filtered(detailed_response);
+                        }
+                        return BustResult::ClientError(detailed_response);
+                    }
                     _ => {
-                        // Handle other status codes (e.g., 404, 403)
                         if parser::should_filter_response(&detailed_response, config) {
                             return BustResult::Filtered(detailed_response);
                         }
@@ -329,20 +2001,75 @@ pub async fn bust_url_with_retry(
                 }
             }
             Err(e) => {
+                state.metrics.record_failure();
                 let error_msg = e.to_string();
-                // Retry on common network errors
-                if (error_msg.contains("timeout")
-                    || error_msg.contains("connection")
-                    || error_msg.contains("dns"))
-                    && attempt < config.retries
+                let kind = client::error_kind(&e, proxy.is_some() || config.proxied);
+                // Retry on common network errors, including failed QUIC handshakes, and on any
+                // failure through a proxy, which the next attempt sends through another one
+                if attempt < config.retries
+                    && (proxy.is_some()
+                        || config.retry_policy.retries_error(kind)
+                        || (config.retry_policy.conn && client::is_quic_failure(&e)))
                 {
+                    debug!(word, attempt, error = error_msg, %kind, "Network error, retrying");
                     sleep(Duration::from_millis(1000 * (attempt + 1) as u64)).await;
                     continue;
                 }
-                return BustResult::Error(word, error_msg);
+                return BustResult::Error(word, error_msg, kind);
             }
         }
     }
 
-    BustResult::Error(word, "Max retries exceeded".to_string())
+    BustResult::Error(word, "Max retries exceeded".to_string(), ErrorKind::Other)
+}
+
+/// Requests a word over both HTTP/1.1 and HTTP/2 and compares the outcomes.
+///
+/// If both protocols return the same status only the HTTP/1.1 result is returned.
+/// Otherwise both results are returned, each labelled with the version it was fetched with.
+/// The word counts once towards the error limits and WAF detection, by its
+/// [`word_outcome`].
+pub async fn bust_url_with_version_probe(
+    clients: &VersionProbeClients,
+    word: String,
+    config: &ScanConfig,
+    state: &ScanState,
+) -> Vec<BustResult> {
+    let http1 = send_with_retry(&clients.http1, word.clone(), config, state).await;
+    let http2 = send_with_retry(&clients.http2, word, config, state).await;
+
+    let status = |result: &BustResult| result.response().map(|resp| resp.status);
+    let results = if status(&http1) == status(&http2) {
+        vec![http1]
+    } else {
+        vec![label_version(http1, "H1"), label_version(http2, "H2")]
+    };
+    let outcome = word_outcome(&results);
+    state.track_errors(outcome, config);
+    state.track_blocks(outcome);
+    results
+}
+
+/// Returns the result the outcome of a word is judged by when it was requested more than
+/// once: the first one that got a response, or the first one if none did.
+pub fn word_outcome(results: &[BustResult]) -> &BustResult {
+    results
+        .iter()
+        .find(|result| result.response().is_some())
+        .unwrap_or(&results[0])
+}
+
+/// Labels a result with the probed HTTP version it was fetched with.
+fn label_version(mut result: BustResult, version: &'static str) -> BustResult {
+    match &mut result {
+        BustResult::Success(resp)
+        | BustResult::Redirect(resp, _)
+        | BustResult::ClientError(resp)
+        | BustResult::NotFound(resp)
+        | BustResult::Filtered(resp) => {
+            resp.probed_version = Some(version);
+        }
+        BustResult::Error(_, message, _) => *message = format!("[{version}] {message}"),
+    }
+    result
 }
@@ -0,0 +1,106 @@
+//! This module implements the `--use-cache` response cache for incremental rescans.
+//!
+//! Responses carrying an `ETag` or `Last-Modified` header are remembered per URL, together
+//! with what the scan needs to classify them again. The next scan sends these validators as
+//! `If-None-Match`/`If-Modified-Since`, and when the server answers `304 Not Modified` the
+//! cached response is reused instead of downloading the body.
+
+use crate::wildcard::WildcardSample;
+use reqwest::header::{HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+
+/// The file the cache is kept in when `--cache-file` is not given.
+pub const DEFAULT_CACHE_FILE: &str = "dirbuster-cache.json";
+
+/// What is remembered about a response to reuse it after a `304 Not Modified`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub status: u16,
+    pub content_length: Option<u64>,
+    pub word_count: Option<usize>,
+    pub line_count: Option<usize>,
+    /// The `Location` header of a cached redirect.
+    pub location: Option<String>,
+    /// The `Content-Type` header, which a `304 Not Modified` usually leaves out.
+    pub content_type: Option<String>,
+    /// The difference score from the diff baseline at the time the response was fetched.
+    pub diff_score: Option<f32>,
+    /// The wildcard characteristics of the body, kept for successful responses.
+    pub wildcard_sample: Option<WildcardSample>,
+}
+
+impl CachedResponse {
+    /// Returns the conditional request headers for revalidating this response.
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = self
+            .etag
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(date) = self
+            .last_modified
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_MODIFIED_SINCE, date);
+        }
+        headers
+    }
+}
+
+/// The cached responses of a scan, keyed by request URL.
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+impl ResponseCache {
+    /// Loads the cache from a file. A missing file gives an empty cache.
+    pub fn load(path: &str) -> io::Result<Self> {
+        let entries = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(ResponseCache {
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Writes the cache to a file.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let content = serde_json::to_string(&*self.entries.lock().unwrap())?;
+        std::fs::write(path, content)
+    }
+
+    /// Returns the cached response for a URL.
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().get(url).cloned()
+    }
+
+    /// Remembers a response, if it has a validator to revalidate it with.
+    pub fn insert(&self, url: &str, entry: CachedResponse) {
+        if entry.etag.is_some() || entry.last_modified.is_some() {
+            self.entries.lock().unwrap().insert(url.to_string(), entry);
+        }
+    }
+
+    /// The number of cached responses.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns true if no response is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,608 @@
+//! This module builds the `reqwest` HTTP clients used for scanning.
+//! All clients share the settings derived from the command-line arguments,
+//! and can optionally be pinned to a specific HTTP version.
+
+use crate::args::Args;
+use crate::buster::ErrorKind;
+use crate::parser;
+use reqwest::cookie::Jar;
+use reqwest::header::LOCATION;
+use reqwest::tls::TlsInfo;
+use reqwest::{Certificate, Client, Identity, Proxy, Response, Url, Version};
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// The User-Agent sent when no rotation is configured.
+pub const DEFAULT_USER_AGENT: &str =
+    "dirbuster-rs/1.0 (+https://github.com/ConeDjordjic/dirbuster-rs)";
+
+/// The HTTP version a client is restricted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// Let the client negotiate the version with the server.
+    Auto,
+    /// Only speak HTTP/1.1.
+    Http1,
+    /// Speak HTTP/2 without prior negotiation.
+    Http2,
+    /// Speak HTTP/3 over QUIC without prior negotiation, in builds with the `http3` feature.
+    Http3,
+}
+
+/// The IP version connections are restricted to with `--ipv4` or `--ipv6`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl std::fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IpFamily::V4 => write!(f, "IPv4"),
+            IpFamily::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// The addresses of the target host that all connections are pinned to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedHost {
+    pub host: String,
+    pub addrs: Vec<SocketAddr>,
+}
+
+/// Resolves the host of the target URL and keeps only the addresses of one IP version.
+///
+/// Fails rather than falling back to the other version when the host has no such address.
+pub async fn resolve_host(url: &str, family: IpFamily) -> Result<PinnedHost, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {url}: {e}"))?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| format!("URL {url} has no host"))?;
+    // IPv6 literals are given in brackets, which the resolver does not accept
+    let lookup = host.trim_start_matches('[').trim_end_matches(']');
+
+    // Port 0 keeps the port of the URL or its scheme, which may change after a scheme upgrade
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((lookup, 0))
+        .await
+        .map_err(|e| format!("Failed to resolve {host}: {e}"))?
+        .filter(|addr| match family {
+            IpFamily::V4 => addr.is_ipv4(),
+            IpFamily::V6 => addr.is_ipv6(),
+        })
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{host} has no {family} address"));
+    }
+
+    Ok(PinnedHost {
+        host: host.to_string(),
+        addrs,
+    })
+}
+
+/// Returns the `--resolve` entry as it applies to the target, or `None` if it names another
+/// host or port than those of the target URLs.
+///
+/// The client overrides a host on every port, so an entry is only used for the port it
+/// names. With `--ipv4`/`--ipv6` only its addresses of that version are kept, and it is an
+/// error if none are left.
+pub fn target_resolve_override(
+    entry: &parser::ResolveOverride,
+    target_urls: &[String],
+    family: Option<IpFamily>,
+) -> Result<Option<parser::ResolveOverride>, String> {
+    let targeted = target_urls
+        .iter()
+        .filter_map(|url| Url::parse(url).ok())
+        .any(|url| {
+            url.host_str() == Some(entry.host.as_str())
+                && url.port_or_known_default() == Some(entry.port)
+        });
+    if !targeted {
+        return Ok(None);
+    }
+    let Some(family) = family else {
+        return Ok(Some(entry.clone()));
+    };
+    let addrs: Vec<IpAddr> = entry
+        .addrs
+        .iter()
+        .copied()
+        .filter(|addr| addr.is_ipv4() == (family == IpFamily::V4))
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("--resolve {entry}: no {family} address"));
+    }
+    Ok(Some(parser::ResolveOverride {
+        addrs,
+        ..entry.clone()
+    }))
+}
+
+/// Returns the base URLs to try for the target, in order.
+///
+/// A URL without a scheme is tried over HTTPS first, then over HTTP.
+pub fn candidate_base_urls(url: &str) -> Vec<String> {
+    if url.contains("://") {
+        vec![url.to_string()]
+    } else {
+        vec![format!("https://{url}"), format!("http://{url}")]
+    }
+}
+
+/// Returns the base URL on the other scheme if `target`, where a request for `base` was
+/// redirected to, is the same host on the other of HTTP and HTTPS.
+///
+/// The path of the base URL is kept, and a trailing slash is only added if it had one.
+pub fn upgraded_base_url(base: &str, target: &str) -> Option<String> {
+    let base_url = Url::parse(base).ok()?;
+    let target = base_url.join(target).ok()?;
+    let schemes = [base_url.scheme(), target.scheme()];
+    if !matches!(schemes, ["http", "https"] | ["https", "http"])
+        || base_url.host_str() != target.host_str()
+    {
+        return None;
+    }
+
+    let mut upgraded = base_url.clone();
+    upgraded.set_scheme(target.scheme()).ok()?;
+    upgraded.set_port(target.port()).ok()?;
+    let upgraded = upgraded.to_string();
+    Some(if base.ends_with('/') {
+        upgraded
+    } else {
+        upgraded.trim_end_matches('/').to_string()
+    })
+}
+
+/// Finds the base URL to scan with a request for each candidate.
+///
+/// The first candidate that responds is used, switched to the other scheme if it redirects
+/// there. Only if a guessed scheme is involved and no candidate responds is this an error;
+/// a URL given with a scheme is scanned as given. The response is returned too, so later
+/// checks of the connection need not request the target again.
+pub async fn detect_base_url(
+    client: &Client,
+    candidates: &[String],
+) -> Result<(String, Option<Response>), String> {
+    let mut failures = Vec::new();
+    for candidate in candidates {
+        match client.get(candidate).send().await {
+            Ok(resp) => {
+                // Unless --no-follow-redirects is set, the client already followed any redirect
+                let target = match resp.headers().get(LOCATION) {
+                    Some(location) if resp.status().is_redirection() => {
+                        location.to_str().ok().map(str::to_string)
+                    }
+                    _ => Some(resp.url().to_string()),
+                };
+                let upgraded = target.and_then(|target| upgraded_base_url(candidate, &target));
+                return Ok((upgraded.unwrap_or_else(|| candidate.clone()), Some(resp)));
+            }
+            Err(e) => failures.push(format!("{candidate}: {e}")),
+        }
+    }
+
+    match candidates {
+        [url] => Ok((url.clone(), None)),
+        _ => Err(format!(
+            "Cannot reach the target over HTTPS or HTTP ({})",
+            failures.join("; ")
+        )),
+    }
+}
+
+/// Checks that the target answers over HTTP/3 for `--http3`, trying each HTTPS candidate
+/// in turn with a client speaking only HTTP/3.
+pub async fn check_http3(client: &Client, candidates: &[String]) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for candidate in candidates.iter().filter(|url| url.starts_with("https://")) {
+        match client.get(candidate).version(Version::HTTP_3).send().await {
+            Ok(_) => return Ok(()),
+            Err(e) => failures.push(format!("{candidate}: {}", error_chain(&e))),
+        }
+    }
+    match failures.is_empty() {
+        true => Err("HTTP/3 needs an https:// target".to_string()),
+        false => Err(failures.join("; ")),
+    }
+}
+
+/// The error reqwest gives when no QUIC connection to any address of the target was set up.
+#[cfg(feature = "http3")]
+const H3_CONNECT_FAILED: &str = "failed to establish connection for HTTP/3 request";
+
+/// Returns true if a request failed while setting up or using a QUIC connection, which is
+/// retried like other connection errors.
+///
+/// The errors of the QUIC and HTTP/3 layers are told apart by their type, as their
+/// messages say nothing about QUIC.
+#[cfg(feature = "http3")]
+pub fn is_quic_failure(error: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if cause.is::<quinn::ConnectError>()
+            || cause.is::<quinn::ConnectionError>()
+            || cause.is::<h3::error::ConnectionError>()
+            || cause.is::<h3::error::StreamError>()
+            || cause.to_string() == H3_CONNECT_FAILED
+        {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// Without the http3 feature no request goes over QUIC.
+#[cfg(not(feature = "http3"))]
+pub fn is_quic_failure(_error: &reqwest::Error) -> bool {
+    false
+}
+
+/// Tells what a failed request failed on from the causes in its source chain, as the top-level
+/// message of a reqwest error only names the URL. With `proxied`, a failed connection is
+/// blamed on the proxy rather than the target.
+pub fn error_kind(error: &reqwest::Error, proxied: bool) -> ErrorKind {
+    let chain = error_chain(error).to_ascii_lowercase();
+    let mentions = |needles: &[&str]| needles.iter().any(|needle| chain.contains(needle));
+    if error.is_timeout() || mentions(&["timed out", "timeout"]) {
+        ErrorKind::Timeout
+    } else if mentions(&["dns error", "failed to lookup address"]) {
+        ErrorKind::Dns
+    } else if mentions(&["ssl", "tls", "certificate", "handshake"]) {
+        ErrorKind::Tls
+    } else if error.is_connect() {
+        if proxied || mentions(&["tunnel"]) {
+            ErrorKind::Proxy
+        } else {
+            ErrorKind::Connect
+        }
+    } else if error.is_body() || error.is_decode() || mentions(&["connection", "broken pipe"]) {
+        ErrorKind::Read
+    } else {
+        ErrorKind::Other
+    }
+}
+
+/// Fetches the robots.txt of the target for `--parse-robots`.
+///
+/// A missing file is not an error; it gives an empty body.
+pub async fn fetch_robots(client: &Client, base_url: &str) -> Result<String, reqwest::Error> {
+    let url = format!("{}/robots.txt", base_url.trim_end_matches('/'));
+    let resp = client.get(&url).send().await?;
+    if !resp.status().is_success() {
+        return Ok(String::new());
+    }
+    resp.text().await
+}
+
+/// Fetches the sitemap of the target for `--parse-sitemap` and returns its paths as words.
+///
+/// `/sitemap.xml` is tried first, then `/sitemap.xml.gz`. The sitemaps listed by a sitemap
+/// index on the target host are fetched too, one level deep. Failures are logged as warnings
+/// and give fewer words, and at most `max_urls` words are returned.
+pub async fn fetch_sitemap_words(client: &Client, base_url: &str, max_urls: usize) -> Vec<String> {
+    let base = base_url.trim_end_matches('/');
+    let mut sitemap = None;
+    for url in [
+        format!("{base}/sitemap.xml"),
+        format!("{base}/sitemap.xml.gz"),
+    ] {
+        if let Some(parsed) = fetch_sitemap(client, &url).await {
+            sitemap = Some(parsed);
+            break;
+        }
+    }
+
+    let urls = match sitemap {
+        Some(parser::Sitemap::Urls(urls)) => urls,
+        Some(parser::Sitemap::Index(sitemaps)) => {
+            let host_of = |url: &str| Url::parse(url).ok()?.host_str().map(str::to_string);
+            let host = host_of(base_url);
+            let mut urls = Vec::new();
+            for sitemap_url in sitemaps {
+                if host_of(&sitemap_url) != host {
+                    continue;
+                }
+                match fetch_sitemap(client, &sitemap_url).await {
+                    Some(parser::Sitemap::Urls(found)) => urls.extend(found),
+                    Some(parser::Sitemap::Index(_)) => {
+                        warn!(url = sitemap_url, "Skipping nested sitemap index")
+                    }
+                    None => {}
+                }
+                if urls.len() >= max_urls {
+                    break;
+                }
+            }
+            urls
+        }
+        None => Vec::new(),
+    };
+
+    let mut words = parser::url_words(&urls, base_url);
+    if words.len() > max_urls {
+        warn!(
+            found = words.len(),
+            "The sitemap lists more than {max_urls} paths, only the first {max_urls} are scanned"
+        );
+        words.truncate(max_urls);
+    }
+    words
+}
+
+/// Fetches and parses one sitemap, logging why when it cannot be used.
+async fn fetch_sitemap(client: &Client, url: &str) -> Option<parser::Sitemap> {
+    let resp = match client.get(url).send().await {
+        Ok(resp) if resp.status().is_success() => resp,
+        Ok(resp) => {
+            debug!(url, status = resp.status().as_u16(), "No sitemap");
+            return None;
+        }
+        Err(e) => {
+            warn!(url, error = %e, "Could not fetch the sitemap");
+            return None;
+        }
+    };
+    let body = match resp.bytes().await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(url, error = %e, "Could not fetch the sitemap");
+            return None;
+        }
+    };
+    parser::parse_sitemap(&body)
+        .inspect_err(|e| warn!(url, error = %e, "Could not parse the sitemap"))
+        .ok()
+}
+
+/// Returns the address of a network interface to bind connections to.
+///
+/// An IPv4 address is preferred unless `family` asks for IPv6. If there is no interface with
+/// that name, the error lists the available ones.
+pub fn interface_address(name: &str, family: Option<IpFamily>) -> Result<IpAddr, String> {
+    let interfaces =
+        if_addrs::get_if_addrs().map_err(|e| format!("Failed to list network interfaces: {e}"))?;
+    let addrs: Vec<IpAddr> = interfaces
+        .iter()
+        .filter(|iface| iface.name == name)
+        .map(|iface| iface.ip())
+        .collect();
+
+    if addrs.is_empty() {
+        let mut available: Vec<(&str, Vec<String>)> = Vec::new();
+        for iface in &interfaces {
+            let ip = iface.ip().to_string();
+            match available.iter_mut().find(|(name, _)| *name == iface.name) {
+                Some((_, ips)) => ips.push(ip),
+                None => available.push((&iface.name, vec![ip])),
+            }
+        }
+        let available: Vec<String> = available
+            .iter()
+            .map(|(name, ips)| format!("{name} ({})", ips.join(", ")))
+            .collect();
+        return Err(format!(
+            "Network interface {name} not found. Available interfaces: {}",
+            available.join(", ")
+        ));
+    }
+
+    let wanted = family.unwrap_or(IpFamily::V4);
+    let preferred = addrs
+        .iter()
+        .find(|ip| ip.is_ipv6() == (wanted == IpFamily::V6));
+    match (preferred, family) {
+        (Some(ip), _) => Ok(*ip),
+        (None, None) => Ok(addrs[0]),
+        (None, Some(_)) => Err(format!("Network interface {name} has no {wanted} address")),
+    }
+}
+
+/// Checks that connections can be made from a local address by binding a socket to it.
+///
+/// Fails with the OS error, e.g. when the address is not assigned to this host.
+pub fn check_local_address(address: IpAddr) -> Result<(), String> {
+    std::net::TcpListener::bind((address, 0))
+        .map(drop)
+        .map_err(|e| format!("Cannot bind to {address}: {e} (is it assigned to this host?)"))
+}
+
+/// How connections are made, on top of the settings taken from the arguments.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// The target host and the only addresses it is connected to, with `--ipv4`/`--ipv6`.
+    pub pinned_host: Option<PinnedHost>,
+    /// The local address connections are made from, with `--interface`/`--bind-address`.
+    pub local_address: Option<IpAddr>,
+    /// An extra root certificate to trust, loaded from `--ca-cert`.
+    pub root_certificate: Option<Certificate>,
+    /// The client certificate and key presented to the target, for mTLS.
+    pub identity: Option<Identity>,
+    /// The proxy requests are sent through, from `--proxy`.
+    pub proxy: Option<Proxy>,
+    /// The `--resolve` entries for the target host and port. They win over `pinned_host`.
+    pub resolve_overrides: Vec<parser::ResolveOverride>,
+    /// The `--cookie` cookies the cookie jar starts with, for the target host.
+    pub seed_cookies: Option<String>,
+}
+
+/// Loads the client identity of `--client-cert`/`--client-key` or `--client-pkcs12`, if given.
+pub fn load_identity(args: &Args) -> Result<Option<Identity>, String> {
+    let read = |flag: &str, path: &str| {
+        std::fs::read(path).map_err(|e| format!("{flag}: cannot read {path}: {e}"))
+    };
+    let (identity, source) = match (&args.client_cert, &args.client_key, &args.client_pkcs12) {
+        (Some(cert_path), Some(key_path), _) => {
+            let cert = read("--client-cert", cert_path)?;
+            let key = read("--client-key", key_path)?;
+            let source = format!("--client-cert: cannot use {cert_path} with the key {key_path}");
+            let identity = Identity::from_pkcs8_pem(&cert, &key)
+                .map_err(|e| format!("{source} (is it a PKCS#8 key?): {}", error_chain(&e)))?;
+            (identity, source)
+        }
+        (_, _, Some(path)) => {
+            let der = read("--client-pkcs12", path)?;
+            let password = args.pkcs12_password.as_deref().unwrap_or_default();
+            let identity = Identity::from_pkcs12_der(&der, password).map_err(|e| {
+                format!(
+                    "--client-pkcs12: cannot load {path} (wrong --pkcs12-password?): {}",
+                    error_chain(&e)
+                )
+            })?;
+            (identity, format!("--client-pkcs12: cannot use {path}"))
+        }
+        _ => return Ok(None),
+    };
+
+    // A key that does not belong to the certificate only shows when the TLS connector is built
+    Client::builder()
+        .identity(identity.clone())
+        .build()
+        .map_err(|e| {
+            format!(
+                "{source} (does the key match the certificate?): {}",
+                error_chain(&e)
+            )
+        })?;
+    Ok(Some(identity))
+}
+
+/// Describes the TLS connection made with the client certificate.
+///
+/// The response of `detect_base_url` is used when it came over HTTPS; only otherwise, e.g.
+/// after an upgrade from HTTP, is the base URL requested. Fails if the handshake does not
+/// succeed, e.g. because the server rejected the certificate.
+pub async fn check_identity(
+    client: &Client,
+    base_url: &str,
+    probe: Option<Response>,
+) -> Result<String, String> {
+    let resp = match probe.filter(|resp| resp.url().scheme() == "https") {
+        Some(resp) => resp,
+        None => client.get(base_url).send().await.map_err(|e| {
+            format!(
+                "TLS handshake with the client certificate failed: {}",
+                error_chain(&e)
+            )
+        })?,
+    };
+    let mut details = format!("{:?}, status {}", resp.version(), resp.status().as_u16());
+    if let Some(certificate) = resp
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(TlsInfo::peer_certificate)
+    {
+        details.push_str(&format!(
+            ", server certificate SHA-256 {:x}",
+            Sha256::digest(certificate)
+        ));
+    }
+    Ok(details)
+}
+
+/// Joins an error with its sources, which carry the actual TLS, QUIC or parse problem.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+/// Loads the root certificate of a `--ca-cert` PEM file.
+pub fn load_ca_cert(path: &str) -> Result<Certificate, String> {
+    let pem = std::fs::read(path).map_err(|e| format!("--ca-cert: cannot read {path}: {e}"))?;
+    Certificate::from_pem(&pem).map_err(|e| format!("--ca-cert: cannot parse {path}: {e}"))
+}
+
+/// Builds an HTTP client configured from the command-line arguments.
+pub fn build_client(
+    args: &Args,
+    version: HttpVersion,
+    connect: &ConnectOptions,
+) -> Result<Client, reqwest::Error> {
+    let mut client_builder = Client::builder()
+        .timeout(Duration::from_secs(args.timeout))
+        .connect_timeout(Duration::from_secs(10))
+        .tcp_keepalive(Duration::from_secs(60))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(args.threads.min(25))
+        .user_agent(DEFAULT_USER_AGENT);
+
+    // Redirects are followed unless they are to be reported as results
+    if args.no_follow_redirects {
+        client_builder = client_builder.redirect(reqwest::redirect::Policy::none());
+    }
+
+    // With a pinned encoding the body is kept exactly as the server sent it
+    if args.no_encoding || args.force_encoding.is_some() {
+        client_builder = client_builder.no_gzip().no_brotli().no_deflate().no_zstd();
+    }
+
+    if args.cookie_jar {
+        let jar = Jar::default();
+        if let Some(cookies) = &connect.seed_cookies {
+            for url in candidate_base_urls(&args.url)
+                .iter()
+                .filter_map(|url| Url::parse(url).ok())
+            {
+                for cookie in cookies.split(';').map(str::trim).filter(|c| !c.is_empty()) {
+                    jar.add_cookie_str(&format!("{cookie}; Path=/"), &url);
+                }
+            }
+        }
+        client_builder = client_builder.cookie_provider(Arc::new(jar));
+    }
+
+    if args.insecure {
+        client_builder = client_builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(certificate) = &connect.root_certificate {
+        client_builder = client_builder.add_root_certificate(certificate.clone());
+    }
+    if let Some(identity) = &connect.identity {
+        client_builder = client_builder.identity(identity.clone()).tls_info(true);
+    }
+
+    if let Some(proxy) = &connect.proxy {
+        client_builder = client_builder.proxy(proxy.clone());
+    }
+
+    if let Some(pinned) = &connect.pinned_host {
+        client_builder = client_builder.resolve_to_addrs(&pinned.host, &pinned.addrs);
+    }
+    for entry in &connect.resolve_overrides {
+        // Port 0 keeps the port of the URL, as with a pinned host
+        let addrs: Vec<SocketAddr> = entry
+            .addrs
+            .iter()
+            .map(|addr| SocketAddr::new(*addr, 0))
+            .collect();
+        client_builder = client_builder.resolve_to_addrs(&entry.host, &addrs);
+    }
+    if let Some(address) = connect.local_address {
+        client_builder = client_builder.local_address(address);
+    }
+
+    client_builder = match version {
+        HttpVersion::Auto => client_builder,
+        HttpVersion::Http1 => client_builder.http1_only(),
+        HttpVersion::Http2 => client_builder.http2_prior_knowledge(),
+        #[cfg(feature = "http3")]
+        HttpVersion::Http3 => client_builder.http3_prior_knowledge(),
+        // --http3 is rejected before any client is built
+        #[cfg(not(feature = "http3"))]
+        HttpVersion::Http3 => unreachable!("HTTP/3 needs the http3 feature"),
+    };
+
+    client_builder.build()
+}
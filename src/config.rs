@@ -0,0 +1,224 @@
+//! This module loads configuration files and merges them with the command line.
+//!
+//! A config file is a TOML table whose keys mirror the fields of [`Args`], e.g.
+//! `threads = 50` or `headers = ["X-Api: 1"]`. Values from the files become defaults of the
+//! corresponding arguments, so anything given explicitly on the command line overrides them.
+//! The user config (`~/.config/dirbuster-rs/config.toml`) is loaded first, then the file
+//! given with `--config`. Unknown keys only produce a warning.
+
+use crate::args::{self, Args, Commands};
+use clap::error::ErrorKind;
+use clap::{ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+/// Arguments whose values are masked when printing the effective configuration.
+const SECRET_ARGS: [&str; 8] = [
+    "auth_header",
+    "cookie",
+    "basic_auth",
+    "bearer_token",
+    "reauth_command",
+    "proxy",
+    "proxy_auth",
+    "notify_webhook",
+];
+
+/// Arguments that only control config handling and are left out of the printed configuration.
+const META_ARGS: [&str; 4] = ["help", "version", "config", "print_config"];
+
+/// Returns the path of the user config file, if a home directory is known.
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("dirbuster-rs").join("config.toml"))
+}
+
+/// What the command line asks the program to do.
+#[derive(Debug)]
+pub enum Invocation {
+    /// Run a scan with the parsed arguments and their matches.
+    Scan(Box<Args>, ArgMatches),
+    /// Run a subcommand instead of a scan.
+    Command(Commands),
+}
+
+/// Parses the process arguments, using the user config and `--config` file as defaults.
+pub fn parse_args() -> Result<Invocation, clap::Error> {
+    parse_args_from(std::env::args_os(), user_config_path())
+}
+
+/// Parses the given arguments, with `user_config` loaded first if it exists.
+pub fn parse_args_from<I, T>(
+    argv: I,
+    user_config: Option<PathBuf>,
+) -> Result<Invocation, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+    let command = args::command();
+
+    // Find --config without failing on arguments the config file may still provide
+    let probe = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)
+        .ok();
+    // `check` takes the scan arguments, so the config files provide its arguments instead
+    let checking = probe
+        .as_ref()
+        .is_some_and(|matches| matches.subcommand_name() == Some("check"));
+    let explicit_config = probe.and_then(|matches| {
+        let matches = matches.subcommand_matches("check").unwrap_or(&matches);
+        matches.get_one::<String>("config").cloned()
+    });
+
+    let mut target = if checking {
+        command
+            .find_subcommand("check")
+            .cloned()
+            .expect("check is a subcommand")
+    } else {
+        command.clone()
+    };
+    if let Some(path) = user_config.filter(|path| path.exists()) {
+        target = apply_config_file(target, &path.to_string_lossy())?;
+    }
+    if let Some(path) = explicit_config {
+        target = apply_config_file(target, &path)?;
+    }
+    let command = if checking {
+        command.mut_subcommand("check", |_| target)
+    } else {
+        target
+    };
+
+    let matches = command.try_get_matches_from(&argv)?;
+    if matches.subcommand().is_some() {
+        return Ok(Invocation::Command(Commands::from_arg_matches(&matches)?));
+    }
+    let args = Args::from_arg_matches(&matches)?;
+    Ok(Invocation::Scan(Box::new(args), matches))
+}
+
+/// Reads a config file and applies its values as argument defaults.
+fn apply_config_file(command: Command, path: &str) -> Result<Command, clap::Error> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        Args::command().error(
+            ErrorKind::Io,
+            format!("cannot read config file {path}: {e}"),
+        )
+    })?;
+    let table: Table = content.parse().map_err(|e| {
+        Args::command().error(
+            ErrorKind::InvalidValue,
+            format!("invalid config file {path}: {e}"),
+        )
+    })?;
+    apply_config(command, &table, path)
+}
+
+/// Applies the values of a config table as defaults of the matching arguments.
+pub fn apply_config(
+    mut command: Command,
+    table: &Table,
+    source: &str,
+) -> Result<Command, clap::Error> {
+    for (key, value) in table {
+        let id = key.replace('-', "_");
+        if META_ARGS.contains(&id.as_str())
+            || command
+                .get_arguments()
+                .all(|arg| arg.get_id() != id.as_str())
+        {
+            eprintln!("Warning: unknown key `{key}` in config file {source}");
+            continue;
+        }
+
+        let values = toml_values(value).ok_or_else(|| {
+            Args::command().error(
+                ErrorKind::InvalidValue,
+                format!("unsupported value for `{key}` in config file {source}"),
+            )
+        })?;
+        // A default also satisfies any required group the argument belongs to
+        let groups: Vec<String> = command
+            .get_groups()
+            .filter(|group| group.get_args().any(|arg| arg == id.as_str()))
+            .map(|group| group.get_id().to_string())
+            .collect();
+        for group in groups {
+            command = command.mut_group(group, |group| group.required(false));
+        }
+        command = command.mut_arg(id, |arg| arg.default_values(values).required(false));
+    }
+    Ok(command)
+}
+
+/// Converts a TOML value into argument values. Tables and nested arrays are not supported.
+fn toml_values(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Array(items) => items.iter().map(toml_scalar).collect(),
+        scalar => toml_scalar(scalar).map(|value| vec![value]),
+    }
+}
+
+/// Converts a scalar TOML value into its argument string.
+fn toml_scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Renders the effective configuration as TOML, with secrets masked.
+///
+/// The output can be used as a config file. Arguments without a value are omitted.
+pub fn render_config(matches: &ArgMatches) -> String {
+    let command = Args::command();
+    let mut table = Table::new();
+
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if META_ARGS.contains(&id) {
+            continue;
+        }
+        let Some(raw) = matches.get_raw(id) else {
+            continue;
+        };
+        let raw: Vec<String> = raw.map(|v| v.to_string_lossy().into_owned()).collect();
+
+        let value = if SECRET_ARGS.contains(&id) {
+            Value::String("********".to_string())
+        } else if matches!(arg.get_action(), ArgAction::Append) {
+            Value::Array(raw.iter().map(|v| typed_value(v)).collect())
+        } else {
+            match raw.first() {
+                Some(v) => typed_value(v),
+                None => continue,
+            }
+        };
+        table.insert(id.to_string(), value);
+    }
+
+    toml::to_string(&table).unwrap_or_default()
+}
+
+/// Interprets an argument string as a boolean or number where possible.
+fn typed_value(raw: &str) -> Value {
+    if let Ok(b) = raw.parse::<bool>() {
+        Value::Boolean(b)
+    } else if let Ok(i) = raw.parse::<i64>() {
+        Value::Integer(i)
+    } else {
+        Value::String(raw.to_string())
+    }
+}
@@ -0,0 +1,294 @@
+//! This module controls console output and diagnostics logging.
+//!
+//! Scan results are printed by the caller. Informational output meant for the user, such as
+//! banners and the final summary, goes through the [`notice!`](crate::notice) macro so that
+//! `--quiet` can silence it in one place. Diagnostics go through `tracing`: they are written
+//! to stderr at the level chosen with `-v`, and optionally to a log file at debug level or
+//! above, so they never mix with results on stdout.
+
+use crate::output::{format_bytes, redact};
+use indicatif::ProgressBar;
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+
+/// Whether informational output is suppressed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// The progress bar currently drawn on the terminal, if any.
+static PROGRESS_BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// The log target for this crate's own events.
+const CRATE_TARGET: &str = "dirbuster_rs";
+
+/// Enables or disables quiet mode for the whole process.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Returns true if informational output is suppressed.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints an informational line to stdout, unless quiet mode is enabled.
+#[macro_export]
+macro_rules! notice {
+    ($($arg:tt)*) => {
+        if !$crate::console::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Registers the progress bar that log lines must be drawn around, or clears it with `None`.
+pub fn set_progress_bar(pb: Option<ProgressBar>) {
+    *PROGRESS_BAR.lock().unwrap() = pb;
+}
+
+/// Writes a block of lines to stderr, drawn above the progress bar, unless quiet mode is
+/// enabled.
+pub fn print_diagnostic(text: &str) {
+    if is_quiet() {
+        return;
+    }
+    let write = || {
+        let mut stderr = io::stderr().lock();
+        let _ = writeln!(stderr, "{text}");
+    };
+    match PROGRESS_BAR.lock().unwrap().as_ref() {
+        Some(pb) => pb.suspend(write),
+        None => write(),
+    }
+}
+
+/// Single-key input on the terminal, active until dropped.
+///
+/// Unlike raw mode, only line buffering and echo are turned off, so output keeps its line
+/// endings and Ctrl+C still interrupts the scan.
+pub struct KeyInput {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+/// Reads single key presses from stdin on a background thread and passes them to `on_key`.
+///
+/// Returns `None`, leaving the terminal untouched, when stdin is not a terminal, the scan
+/// runs in the background or the platform is not supported.
+#[cfg(unix)]
+pub fn spawn_key_reader<F>(mut on_key: F) -> Option<KeyInput>
+where
+    F: FnMut(char) + Send + 'static,
+{
+    use std::io::{IsTerminal, Read};
+
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    // Changing the terminal from a background job would stop the scan with SIGTTOU
+    // SAFETY: both calls only read the process groups
+    if unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) != libc::getpgrp() } {
+        return None;
+    }
+    // SAFETY: termios is plain data, filled in by tcgetattr for the stdin descriptor
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+        return None;
+    }
+    let mut cbreak = original;
+    cbreak.c_lflag &= !(libc::ICANON | libc::ECHO);
+    cbreak.c_cc[libc::VMIN] = 1;
+    cbreak.c_cc[libc::VTIME] = 0;
+    // SAFETY: the settings are a modified copy of the valid ones read above
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &cbreak) } != 0 {
+        return None;
+    }
+
+    std::thread::spawn(move || {
+        let mut key = [0u8; 1];
+        while let Ok(1) = io::stdin().read(&mut key) {
+            on_key(key[0] as char);
+        }
+    });
+    Some(KeyInput { original })
+}
+
+/// Reads single key presses from stdin, which is not supported on this platform.
+#[cfg(not(unix))]
+pub fn spawn_key_reader<F>(_on_key: F) -> Option<KeyInput>
+where
+    F: FnMut(char) + Send + 'static,
+{
+    None
+}
+
+impl Drop for KeyInput {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read when the key reader was started
+        #[cfg(unix)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Builds the progress bar message from the concurrency, the error count, the body bytes
+/// downloaded and, with `--show-errors-inline`, the last error.
+pub fn progress_message(
+    errors: usize,
+    downloaded: u64,
+    concurrency: Option<usize>,
+    last_error: Option<&str>,
+) -> String {
+    let mut message = "Scanning...".to_string();
+    if let Some(concurrency) = concurrency {
+        message.push_str(&format!(" Threads: {concurrency}"));
+    }
+    if errors > 0 {
+        message.push_str(&format!(" Errors: {errors}"));
+    }
+    if downloaded > 0 {
+        message.push_str(&format!(" Downloaded: {}", format_bytes(downloaded)));
+    }
+    if let Some(last_error) = last_error {
+        message.push_str(&format!(" Last error: {last_error}"));
+    }
+    message
+}
+
+/// Maps the number of `-v` flags to the console log level.
+///
+/// Without flags only info and above is shown, `-v` adds debug and `-vv` adds trace.
+/// Quiet mode turns console logging off entirely.
+pub fn console_level(verbosity: u8, quiet: bool) -> LevelFilter {
+    if quiet {
+        return LevelFilter::OFF;
+    }
+    match verbosity {
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// Installs the global `tracing` subscriber.
+///
+/// Events from this crate are logged at the console level. Other crates (reqwest, hyper)
+/// only log warnings, unless `-vvv` is given. The log file, when set, records at least
+/// debug level and never contains color codes. Both redact matches of `redact_patterns`.
+pub fn init_logging(
+    verbosity: u8,
+    quiet: bool,
+    log_file: Option<&str>,
+    redact_patterns: &[Regex],
+) -> io::Result<()> {
+    let patterns: Arc<[Regex]> = redact_patterns.into();
+    let level = console_level(verbosity, quiet);
+    let dependency_level = if verbosity >= 3 {
+        LevelFilter::TRACE
+    } else {
+        LevelFilter::WARN
+    };
+
+    let console_layer = tracing_subscriber::fmt::layer()
+        .with_writer(Redacting::new(ProgressAwareStderr, patterns.clone()))
+        .with_ansi(colored::control::SHOULD_COLORIZE.should_colorize())
+        .with_target(false)
+        .without_time()
+        .with_filter(
+            Targets::new()
+                .with_target(CRATE_TARGET, level)
+                .with_default(dependency_level.min(level)),
+        );
+
+    let file_layer = match log_file {
+        Some(path) => {
+            let file_level = level.max(LevelFilter::DEBUG);
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(Redacting::new(Mutex::new(File::create(path)?), patterns))
+                    .with_ansi(false)
+                    .with_filter(
+                        Targets::new()
+                            .with_target(CRATE_TARGET, file_level)
+                            .with_default(dependency_level),
+                    ),
+            )
+        }
+        None => None,
+    };
+
+    // Ignore a subscriber that is already installed, e.g. when embedding the library
+    let _ = tracing_subscriber::registry()
+        .with(console_layer)
+        .with(file_layer)
+        .try_init();
+    Ok(())
+}
+
+/// A stderr writer that hides the progress bar while a log line is written.
+struct ProgressAwareStderr;
+
+impl<'a> MakeWriter<'a> for ProgressAwareStderr {
+    type Writer = ProgressAwareStderr;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ProgressAwareStderr
+    }
+}
+
+impl Write for ProgressAwareStderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The formatter writes each event in one call, so a single suspend covers the whole line
+        match PROGRESS_BAR.lock().unwrap().as_ref() {
+            Some(pb) => pb.suspend(|| io::stderr().write_all(buf))?,
+            None => io::stderr().write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// A writer wrapper that redacts every log line before passing it on.
+struct Redacting<M> {
+    inner: M,
+    patterns: Arc<[Regex]>,
+}
+
+impl<M> Redacting<M> {
+    fn new(inner: M, patterns: Arc<[Regex]>) -> Self {
+        Redacting { inner, patterns }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = Redacting<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Redacting::new(self.inner.make_writer(), self.patterns.clone())
+    }
+}
+
+impl<W: Write> Write for Redacting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.patterns.is_empty() {
+            return self.inner.write(buf);
+        }
+        // Each event arrives in one call, so matches never straddle two writes
+        let line = redact(&String::from_utf8_lossy(buf), &self.patterns);
+        self.inner.write_all(line.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -1,5 +1,22 @@
-pub mod wildcard;
+pub mod args;
+pub mod autotune;
 pub mod buster;
-pub mod parser;
+pub mod cache;
+pub mod client;
+pub mod config;
+pub mod console;
+pub mod notify;
 pub mod output;
-pub mod args; 
\ No newline at end of file
+pub mod parser;
+pub mod proxy;
+pub mod rate;
+pub mod reauth;
+pub mod request_log;
+pub mod signing;
+pub mod spider;
+pub mod stats;
+pub mod waf;
+pub mod wildcard;
+
+#[cfg(test)]
+mod tests;
@@ -8,223 +8,1184 @@
 //! - Handling graceful shutdown on Ctrl+C.
 //! - Displaying results and a final summary.
 //! - Saving results and scan state to files.
+//! - Exiting with a status code that reflects the scan outcome.
 
-use buster::{BustResult, ScanConfig, ScanState};
-use clap::Parser;
 use colored::*;
-use futures::{StreamExt, stream};
+use dirbuster_rs::args::{Commands, CompareArgs};
+use dirbuster_rs::buster::{
+    self, BustResult, ErrorKindCounts, ExitStatus, RetryPass, SCAN_STOPPED, ScanConfig,
+    ScanProgress, ScanState, StopReason,
+};
+use dirbuster_rs::client::{self, HttpVersion, IpFamily};
+use dirbuster_rs::config::Invocation;
+use dirbuster_rs::parser::HeaderFileFormat;
+use dirbuster_rs::rate::RateLimiter;
+use dirbuster_rs::reauth::Reauth;
+use dirbuster_rs::request_log::RequestLog;
+use dirbuster_rs::signing;
+use dirbuster_rs::spider::Spider;
+use dirbuster_rs::stats::ScanStats;
+use dirbuster_rs::waf::WafDetector;
+use dirbuster_rs::{
+    args, autotune, cache, config, console, notice, notify, output, parser, proxy, wildcard,
+};
+use futures::{StreamExt, future, stream};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::sync::Arc;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::io::IsTerminal;
+use std::process::ExitCode;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 use tokio::signal;
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::Semaphore;
 use tokio::time::Instant;
 
-mod args;
-mod buster;
-mod output;
-mod parser;
-mod wildcard;
-
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = args::Args::parse();
+async fn main() -> ExitCode {
+    let invocation = match config::parse_args() {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            // Help and version output also arrive as errors, but are not failures
+            let code = if e.use_stderr() {
+                ExitStatus::InvalidArguments.code()
+            } else {
+                0
+            };
+            let _ = e.print();
+            return ExitCode::from(code);
+        }
+    };
 
-    let word_list = parser::parse_word_list(&args.word_list)?;
-    let user_agents = parser::parse_user_agents(&args.user_agents)?;
+    let result = match invocation {
+        Invocation::Scan(args, matches) => {
+            if args.print_config {
+                print!("{}", config::render_config(&matches));
+                return ExitCode::SUCCESS;
+            }
+            run(*args).await
+        }
+        Invocation::Command(Commands::Compare(args)) => compare(&args),
+        Invocation::Command(Commands::Check(args)) => Ok(check(*args)),
+    };
+    match result {
+        Ok(status) => ExitCode::from(status.code()),
+        Err(e) => {
+            eprintln!("{} {e}", "Error:".red().bold());
+            ExitCode::from(ExitStatus::InvalidArguments.code())
+        }
+    }
+}
 
-    let wl_len = word_list.len();
-    if wl_len == 0 {
-        println!("No words to process!");
-        return Ok(());
+/// Disables colors unless they were requested and stdout is a terminal.
+fn init_colors(no_color: bool) {
+    // Colors only make sense on a terminal, and NO_COLOR (https://no-color.org) opts out of them
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if no_color || no_color_env || !std::io::stdout().is_terminal() {
+        colored::control::set_override(false);
     }
+}
+
+/// Runs the `compare` subcommand, printing the differences between two JSON results.
+fn compare(args: &CompareArgs) -> Result<ExitStatus, Box<dyn Error>> {
+    init_colors(false);
+    let report = output::compare_results(&args.file1, &args.file2)?;
+    println!("{}", output::format_comparison(&report));
+    if let Some(path) = &args.compare_output {
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)
+            .map_err(|e| format!("--compare-output: cannot write {path}: {e}"))?;
+        println!("Comparison saved to: {path}");
+    }
+    Ok(ExitStatus::Completed)
+}
+
+/// Runs the `check` subcommand, listing every problem of the scan options it finds without
+/// sending any requests.
+fn check(mut args: args::Args) -> ExitStatus {
+    init_colors(args.no_color);
+    let mut errors = Vec::new();
+
+    let mut redact_patterns = Vec::new();
+    for pattern in &args.redact_patterns {
+        match regex::Regex::new(pattern) {
+            Ok(regex) => redact_patterns.push(regex),
+            Err(e) => errors.push(format!("--redact: {e}")),
+        }
+    }
+    if let Some(pattern) = &args.wordlist_skip_regex
+        && let Err(e) = parser::parse_skip_regex(pattern)
+    {
+        errors.push(format!("--wordlist-skip-regex: {e}"));
+    }
+    let inputs = args
+        .word_list
+        .iter()
+        .map(|path| ("--word-list", path))
+        .chain(args.import_burp.iter().map(|path| ("--import-burp", path)))
+        .chain(args.from_report.iter().map(|path| ("--from-report", path)));
+    for (flag, path) in inputs {
+        if let Err(e) = std::fs::File::open(path) {
+            errors.push(format!("{flag}: cannot read {path}: {e}"));
+        }
+    }
+    if let Some(format) = &args.output_format
+        && output::OutputFormat::parse(format).is_none()
+    {
+        errors.push(format!(
+            "--output-format: unknown format {format:?}, expected text, json, xml, csv or markdown"
+        ));
+    }
+    // A scan ignores filters it cannot parse, which is worth pointing out here
+    if let Some(filter) = &args.filter_size
+        && parser::parse_size_filter(filter).is_none()
+    {
+        errors.push(format!("--filter-size: invalid range {filter:?}"));
+    }
+    if let Some(filter) = &args.filter_words
+        && parser::parse_word_filter(filter).is_none()
+    {
+        errors.push(format!("--filter-words: invalid range {filter:?}"));
+    }
+
+    match parser::split_url_template(&args.url) {
+        Ok((target_url, url_template)) => {
+            args.url = target_url;
+            match scan_config(&args, String::new(), url_template, redact_patterns) {
+                Ok(config) => errors.extend(buster::validate_config(&config)),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Err(e) => errors.push(e.to_string()),
+    }
+
+    if errors.is_empty() {
+        println!("{}", "Configuration OK".green());
+        return ExitStatus::Completed;
+    }
+    println!("{}", "Configuration errors:".red().bold());
+    for error in &errors {
+        println!("  - {error}");
+    }
+    ExitStatus::InvalidConfig
+}
+
+/// Builds the scan configuration from the arguments, resolving secrets and parsing the
+/// header, template and signing options. Nothing is requested yet.
+fn scan_config(
+    args: &args::Args,
+    scan_id: String,
+    url_template: Option<parser::Template>,
+    redact_patterns: Vec<regex::Regex>,
+) -> Result<ScanConfig, Box<dyn Error>> {
+    let user_agents = parser::parse_user_agents(&args.user_agents)?;
 
-    // Set up shared configuration
-    let config = Arc::new(ScanConfig {
-        base_url: args.url.clone(),
+    // Resolve secrets given as env:NAME or file:/path before they are used
+    let auth_header = args
+        .auth_header
+        .as_deref()
+        .map(|value| parser::resolve_secret("--auth-header", value))
+        .transpose()?;
+    let basic_auth = match &args.basic_auth_file {
+        Some(path) => Some(parser::resolve_secret(
+            "--basic-auth-file",
+            &format!("file:{path}"),
+        )?),
+        None => args
+            .basic_auth
+            .as_deref()
+            .map(|value| parser::resolve_secret("--basic-auth", value))
+            .transpose()?,
+    };
+    let bearer_token = match &args.bearer_token_env {
+        Some(name) => Some(parser::resolve_secret(
+            "--bearer-token-env",
+            &format!("env:{name}"),
+        )?),
+        None => args
+            .bearer_token
+            .as_deref()
+            .map(|value| parser::resolve_secret("--bearer-token", value))
+            .transpose()?,
+    };
+    let mut custom_headers = match &args.header_file {
+        Some(path) => match args.header_file_format {
+            HeaderFileFormat::Plain => parser::parse_headers_file(path),
+            HeaderFileFormat::Burp => parser::parse_burp_headers_file(path),
+        }
+        .map_err(|e| format!("--header-file: cannot read {path}: {e}"))?,
+        None => HashMap::new(),
+    };
+    let cli_headers = parser::parse_custom_headers(&parser::resolve_header_secrets(&args.headers)?);
+    // Header names are case-insensitive, so `user-agent` in the file loses to `User-Agent`
+    custom_headers.retain(|key, _| !cli_headers.keys().any(|cli| cli.eq_ignore_ascii_case(key)));
+    custom_headers.extend(cli_headers);
+    let header_templates = parser::header_templates(&custom_headers)?;
+
+    Ok(ScanConfig {
+        scan_id,
+        // Replaced by the URL that answers once the client is built
+        base_url: client::candidate_base_urls(&args.url).swap_remove(0),
+        path_prefix: args.path_prefix.clone(),
+        wordlist_min_len: args.wordlist_min_length,
+        wordlist_max_len: args.wordlist_max_length,
+        url_template,
+        threads_per_host: args
+            .threads_per_host
+            .map_or(args.threads, |threads| threads as usize),
         retries: args.retries,
+        retry_policy: args.retry_on.clone(),
+        max_retry_after: Duration::from_secs(args.max_retry_after),
+        respect_rate_limit_headers: args.rate_limit_headers,
         delay_min: args.delay_min,
         delay_max: args.delay_max,
         rotate_user_agent: args.rotate_user_agent,
         rotate_ip_headers: args.rotate_ip_headers,
+        cache_bust: args.cache_bust,
+        padding_body: args.padding_body.then_some(args.padding_body_size),
+        no_browser_headers: args.no_random_headers,
+        referer: args.referer.clone(),
+        accept_header: args.accept.as_deref().map(parser::expand_accept),
+        force_no_encoding: args.no_encoding,
+        force_encoding: args.force_encoding.clone(),
         user_agents,
-        auth_header: args.auth_header,
-        basic_auth: args.basic_auth,
-        bearer_token: args.bearer_token,
-        custom_headers: parser::parse_custom_headers(&args.headers),
-        filter_codes: args.filter_codes,
+        auth_header,
+        basic_auth,
+        bearer_token,
+        cookie: args
+            .cookie
+            .as_deref()
+            .map(|value| parser::resolve_secret("--cookie", value))
+            .transpose()?,
+        custom_headers,
+        header_templates,
+        signing: args
+            .sign_hmac
+            .as_deref()
+            .map(signing::parse_signing_spec)
+            .transpose()?,
+        filter_codes: args.filter_codes.clone(),
         filter_size: args
             .filter_size
             .as_ref()
             .and_then(|s| parser::parse_size_filter(s)),
+        filter_size_tolerance: args.filter_size_tolerance,
+        filter_size_exact: args.filter_size_exact,
+        min_response_size: args.min_response_size,
+        max_response_size: args.max_response_size,
         filter_time: args.filter_time,
+        exclude_length_zero: args.exclude_length_zero,
+        require_content_type: args.require_content_type.clone(),
         filter_words: args
             .filter_words
             .as_ref()
             .and_then(|s| parser::parse_word_filter(s)),
+        filter_html_tags: args.filter_html_tags.clone(),
         show_content_length: args.show_content_length,
+        show_line_count: args.show_line_count,
+        show_word_count: args.show_word_count,
+        show_http_version: args.show_http_version,
+        show_cookies: args.show_cookies,
+        http3: false,
+        verbose: args.verbose > 0,
         show_response_time: args.show_response_time,
+        show_errors_inline: args.show_errors_inline,
+        high_res_timing: args.high_res_timing,
+        timing_detail: args.timing_detail,
         detect_wildcards: args.detect_wildcards,
+        smart_filter: args.smart_filter,
+        dedup_body_hash: args.dedup_responses,
+        show_wildcard_confidence: args.show_wildcard_confidence,
+        debug_wildcards: args.debug_wildcards,
+        diff_baseline: args.diff_baseline.clone(),
+        diff_threshold: args.diff_threshold,
+        show_diff_score: args.show_diff_score,
+        extract_words: args.extract_words,
+        spider_depth: args.spider_depth.map_or(0, |depth| depth as usize),
+        word_sources: HashMap::new(),
+        redact_patterns,
+        encode_mode: parser::EncodeMode {
+            percent_passes: if args.encode_double {
+                2
+            } else {
+                u8::from(args.encode_url)
+            },
+            unicode_form: args.encode_unicode,
+        },
+        header_inject_mode: args.header_inject,
+        force_ipv4: args.ipv4,
+        force_ipv6: args.ipv6,
+        resolve_overrides: args.resolve.clone(),
+        bind_interface: args.interface.clone(),
+        bind_address: args.bind_address,
+        insecure: args.insecure,
+        ca_cert: args.ca_cert.clone(),
+        error_log: args.error_log.clone(),
+        proxied: !args.proxy.is_empty() || args.proxy_file.is_some(),
+        request_log: args.request_log.clone(),
+        log_sensitive_headers: args.log_sensitive_headers,
+        omit_errors_from_output: args.no_errors_in_main_output,
+        sort_by: args.sort_by,
+        sort_desc: args.sort_desc,
+        max_errors: args.max_errors,
+        max_total_errors: args.max_total_errors,
+        max_scan_time: args.max_scan_time,
+        max_bytes: args.max_bytes,
+        max_results: args.limit,
+        max_body_size: (args.max_body_size > 0).then_some(args.max_body_size),
+        max_error_rate: args.max_error_rate,
+    })
+}
+
+/// Runs the scan and returns the exit status describing how it ended.
+/// Errors are only returned during setup, when arguments or input files are invalid.
+async fn run(mut args: args::Args) -> Result<ExitStatus, Box<dyn Error>> {
+    console::set_quiet(args.quiet);
+
+    init_colors(args.no_color);
+    let redact_patterns = args
+        .redact_patterns
+        .iter()
+        .map(|pattern| regex::Regex::new(pattern))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("--redact: {e}"))?;
+    console::init_logging(
+        args.verbose,
+        args.quiet,
+        args.log_file.as_deref(),
+        &redact_patterns,
+    )?;
+
+    // The placeholders of a URL template come after the base URL, which is probed as usual
+    let (target_url, url_template) = parser::split_url_template(&args.url)?;
+    args.url = target_url;
+
+    let scan_id = args
+        .scan_id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    // Decide where the output goes before scanning, so a bad combination fails fast
+    let output_format =
+        output::OutputFormat::from_name(args.output_format.as_deref().unwrap_or("text"));
+    let output_mode = if args.output_append {
+        output::OutputMode::Append
+    } else if args.output_overwrite {
+        output::OutputMode::Overwrite
+    } else {
+        output::OutputMode::Create
+    };
+    // An explicit format without a file name saves to a file named after the scan ID
+    let requested_file = args.output_file.clone().or_else(|| {
+        args.output_format
+            .as_ref()
+            .map(|_| format!("{scan_id}.{}", output_format.extension()))
     });
+    let output_file = match &requested_file {
+        Some(path) => {
+            let resolved = output::resolve_output_path(path, output_format, output_mode)?;
+            if &resolved != path {
+                notice!("{path} already exists, writing results to {resolved}");
+            }
+            if output_mode == output::OutputMode::Append
+                && output_format == output::OutputFormat::Csv
+            {
+                output::check_csv_columns(&resolved, args.timing_detail)?;
+            }
+            Some(resolved)
+        }
+        None => None,
+    };
+
+    // A URL without a scheme is tried over HTTPS, then HTTP, once the client is built
+    let candidate_urls = client::candidate_base_urls(&args.url);
+
+    let skip_regex = args
+        .wordlist_skip_regex
+        .as_deref()
+        .map(parser::parse_skip_regex)
+        .transpose()?;
+    let mut word_list = parser::filter_word_list(
+        match (&args.import_burp, &args.from_report) {
+            (Some(burp_file), _) => parser::parse_burp_xml(burp_file, &candidate_urls)?,
+            (None, Some(report)) => output::load_report_words(report, args.select)?,
+            (None, None) => parser::merge_word_lists(&args.word_list, args.strip_comments)?,
+        },
+        &args.wordlist_skip_prefixes,
+        skip_regex.as_ref(),
+    );
+    // Length limits apply to the words as listed, before they are transformed
+    if args.wordlist_min_length.is_some() || args.wordlist_max_length.is_some() {
+        let total = word_list.len();
+        let (kept, removed) = parser::filter_word_lengths(
+            word_list,
+            args.wordlist_min_length,
+            args.wordlist_max_length,
+        );
+        word_list = kept;
+        notice!("Wordlist: {total} words, {removed} filtered by length");
+    }
+    if let Some(command) = &args.wordlist_transform {
+        let before = word_list.len();
+        word_list = parser::run_word_transform(command, &word_list)?;
+        if word_list.len() != before {
+            notice!(
+                "--wordlist-transform turned {before} words into {}",
+                word_list.len()
+            );
+        }
+    }
+    if let Some(transform) = args.wordlist_transform_builtin {
+        for word in &mut word_list {
+            *word = parser::transform_word(word, transform);
+        }
+    }
+    if args.dedup {
+        let before = word_list.len();
+        word_list = parser::dedup_words(word_list);
+        tracing::debug!("Removed {} duplicate words", before - word_list.len());
+    }
+    // Words a previous scan with another tool already covered are not requested again
+    if let Some(path) = &args.import_ffuf_json {
+        skip_previous_scan(&mut word_list, parser::parse_ffuf_json(path)?, "ffuf");
+    }
+    if let Some(path) = &args.import_gobuster {
+        skip_previous_scan(
+            &mut word_list,
+            parser::parse_gobuster_output(path)?,
+            "gobuster",
+        );
+    }
+    if let Some(path) = &args.resume {
+        let progress = ScanProgress::load_from_disk(path)
+            .map_err(|e| format!("Failed to read progress file {path}: {e}"))?;
+        skip_previous_scan(&mut word_list, progress.processed_words, "dirbuster-rs");
+    }
+    let mut config = scan_config(&args, scan_id.clone(), url_template, redact_patterns)?;
+    let errors = buster::validate_config(&config);
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+
+    let response_cache = if args.use_cache {
+        Some(
+            cache::ResponseCache::load(&args.cache_file)
+                .map_err(|e| format!("--cache-file: cannot load {}: {e}", args.cache_file))?,
+        )
+    } else {
+        None
+    };
+
+    let wl_len = word_list.len();
+    if wl_len == 0 {
+        notice!("No words to process!");
+        return Ok(if args.fail_on_empty {
+            ExitStatus::NoFindings
+        } else {
+            ExitStatus::Completed
+        });
+    }
+
+    notice!("Scan ID: {scan_id}");
 
-    // Semaphore to limit concurrency
-    let semaphore = Arc::new(Semaphore::new(args.threads));
+    // A dry run only shows what would be requested, without building clients or probing
+    if args.dry_run {
+        for word in word_list.iter().take(args.dry_run_count) {
+            println!("{}\n", output::format_planned_request(word, &config));
+        }
+        println!("{wl_len} paths would be scanned");
+        return Ok(ExitStatus::Completed);
+    }
 
-    // Configure the HTTP client
-    let mut client_builder = reqwest::Client::builder()
-        .timeout(Duration::from_secs(args.timeout))
-        .connect_timeout(Duration::from_secs(10))
-        .tcp_keepalive(Duration::from_secs(60))
-        .pool_idle_timeout(Duration::from_secs(90))
-        .pool_max_idle_per_host(args.threads.min(25))
-        .user_agent("dirbuster-rs/1.0 (+https://github.com/ConeDjordjic/dirbuster-rs)");
+    // With --ipv4 or --ipv6 the target host is resolved up front and pinned to those addresses
+    let ip_family = match (config.force_ipv4, config.force_ipv6) {
+        (true, _) => Some(IpFamily::V4),
+        (_, true) => Some(IpFamily::V6),
+        _ => None,
+    };
+    let mut resolve_overrides = Vec::new();
+    for entry in &config.resolve_overrides {
+        match client::target_resolve_override(entry, &candidate_urls, ip_family)? {
+            Some(entry) => {
+                notice!("Resolving {entry}");
+                resolve_overrides.push(entry);
+            }
+            None => eprintln!(
+                "{} --resolve {entry} is not used, the scan does not connect to that host and port",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+    config.resolve_overrides = resolve_overrides;
+    // A --resolve entry for the target host replaces its DNS lookup
+    let target_overridden = reqwest::Url::parse(&config.base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .is_some_and(|host| {
+            config
+                .resolve_overrides
+                .iter()
+                .any(|entry| entry.host == host)
+        });
+    let pinned = match ip_family {
+        Some(family) if !target_overridden => {
+            let pinned = client::resolve_host(&config.base_url, family).await?;
+            notice!(
+                "Connecting to {} over {family}: {}",
+                pinned.host,
+                pinned
+                    .addrs
+                    .iter()
+                    .map(|addr| addr.ip().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            Some(pinned)
+        }
+        _ => None,
+    };
+    let local_address = match (&config.bind_interface, config.bind_address) {
+        (Some(name), _) => {
+            let address = client::interface_address(name, ip_family)?;
+            notice!("Binding connections to {name} ({address})");
+            Some(address)
+        }
+        (None, address) => address,
+    };
+    if let Some(address) = local_address {
+        client::check_local_address(address)?;
+        config.bind_address = Some(address);
+    }
+    let root_certificate = args
+        .ca_cert
+        .as_deref()
+        .map(client::load_ca_cert)
+        .transpose()?;
+    if args.insecure {
+        eprintln!(
+            "{} --insecure: TLS certificates are NOT verified, anyone on the path can impersonate the target",
+            "Warning:".yellow().bold()
+        );
+    }
+    if args.http3 && !cfg!(feature = "http3") {
+        return Err(
+            "--http3: this build has no HTTP/3 support, rebuild with the http3 feature".into(),
+        );
+    }
+    let proxy_urls = proxy::load_proxy_urls(&args.proxy, args.proxy_file.as_deref())?;
+    if proxy_urls.len() > 1 && args.http_version_probe {
+        return Err("--http-version-probe cannot be combined with several proxies".into());
+    }
+    let proxies = proxy_urls
+        .iter()
+        .map(|url| proxy::build_proxy(url, args.proxy_auth.as_deref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let connect = client::ConnectOptions {
+        pinned_host: pinned,
+        local_address,
+        root_certificate,
+        identity: client::load_identity(&args)?,
+        proxy: proxies.first().cloned(),
+        resolve_overrides: config.resolve_overrides.clone(),
+        // A Cookie header on a request keeps the jar from sending its cookies, so with
+        // --cookie-jar the --cookie cookies go into the jar instead
+        seed_cookies: config.cookie.take_if(|_| args.cookie_jar),
+    };
+
+    // Configure the HTTP client, which goes through the first proxy and also sends the
+    // wildcard probes, so they are answered over the same HTTP version as the scan
+    let mut http_version = if args.http1 {
+        HttpVersion::Http1
+    } else if args.http2_prior_knowledge {
+        HttpVersion::Http2
+    } else if args.http3 {
+        HttpVersion::Http3
+    } else {
+        HttpVersion::Auto
+    };
+    let mut client = Arc::new(client::build_client(&args, http_version, &connect)?);
+
+    // HTTP/3 is only kept if the target answers over it, and --http3-only makes that required
+    if http_version == HttpVersion::Http3
+        && let Err(e) = client::check_http3(&client, &candidate_urls).await
+    {
+        if args.http3_only {
+            return Err(
+                format!("--http3-only: the target does not answer over HTTP/3 ({e})").into(),
+            );
+        }
+        notice!(
+            "The target does not answer over HTTP/3 ({e}), falling back to HTTP/1.1 and HTTP/2"
+        );
+        http_version = HttpVersion::Auto;
+        client = Arc::new(client::build_client(&args, http_version, &connect)?);
+    }
+    config.http3 = http_version == HttpVersion::Http3;
+
+    // With several proxies, the requests of the scan rotate among one client per proxy
+    let proxy_rotation = if proxies.len() > 1 {
+        let clients = proxy_urls
+            .iter()
+            .zip(proxies)
+            .map(|(url, proxy)| {
+                let connect = client::ConnectOptions {
+                    proxy: Some(proxy),
+                    ..connect.clone()
+                };
+                client::build_client(&args, http_version, &connect)
+                    .map(|client| (proxy::proxy_label(url), client))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        notice!("Rotating among {} proxies", clients.len());
+        Some(proxy::ProxyRotation::new(
+            clients,
+            Duration::from_secs(args.proxy_cooldown),
+        ))
+    } else {
+        None
+    };
 
-    if args.cookie_jar {
-        client_builder = client_builder.cookie_store(true);
+    // Pick the scheme, and follow an upgrade to HTTPS (or a downgrade) of the whole target
+    let (base_url, probe) = client::detect_base_url(&client, &candidate_urls).await?;
+    config.base_url = base_url;
+    // The response is only kept for the client certificate check
+    let probe = probe.filter(|_| connect.identity.is_some());
+    if config.base_url != args.url {
+        notice!("Using {} as the base URL", config.base_url);
+    }
+    if connect.identity.is_some() {
+        if config.base_url.starts_with("https://") {
+            let details = client::check_identity(&client, &config.base_url, probe).await?;
+            notice!("Client certificate accepted: {details}");
+        } else {
+            eprintln!(
+                "{} the client certificate is only presented over HTTPS",
+                "Warning:".yellow().bold()
+            );
+        }
     }
 
-    if let Some(proxy_url) = &args.proxy {
-        client_builder = client_builder.proxy(reqwest::Proxy::all(proxy_url)?);
+    // The robots.txt and sitemap paths are scanned first, and not a second time from the word list
+    let mut seeds = Vec::new();
+    if args.parse_robots {
+        match client::fetch_robots(&client, &config.base_url).await {
+            Ok(body) => {
+                let words = parser::parse_robots_txt(&body);
+                notice!("Found {} paths in robots.txt", words.len());
+                seeds.push(("robots", words));
+            }
+            Err(e) => tracing::warn!(error = %e, "Could not fetch robots.txt"),
+        }
+    }
+    if args.parse_sitemap {
+        let words =
+            client::fetch_sitemap_words(&client, &config.base_url, args.sitemap_max_urls).await;
+        notice!("Found {} paths in the sitemap", words.len());
+        seeds.push(("sitemap", words));
+    }
+    let mut seed_words = Vec::new();
+    for (source, words) in seeds {
+        for word in words {
+            if let Entry::Vacant(entry) = config.word_sources.entry(word.clone()) {
+                entry.insert(source);
+                seed_words.push(word);
+            }
+        }
     }
+    word_list.retain(|word| !config.word_sources.contains_key(word));
+    word_list.splice(0..0, seed_words);
+    let wl_len = word_list.len();
+    let config = Arc::new(config);
 
-    let client = Arc::new(client_builder.build()?);
+    // Version probing compares every path over HTTP/1.1 and HTTP/2
+    let version_clients = if args.http_version_probe {
+        Some(Arc::new(buster::VersionProbeClients {
+            http1: client::build_client(&args, HttpVersion::Http1, &connect)?,
+            http2: client::build_client(&args, HttpVersion::Http2, &connect)?,
+        }))
+    } else {
+        None
+    };
+
+    // Limit the concurrency, resized by the controller with --auto-threads and by the + and - keys
+    let concurrency_limit = Arc::new(autotune::AdaptiveLimit::new(
+        if args.auto_threads {
+            autotune::INITIAL_CONCURRENCY
+        } else {
+            args.threads
+        },
+        args.threads,
+    ));
+    let semaphore = concurrency_limit.semaphore();
 
     // Set up the progress bar
-    let progress_bar = if args.no_progress {
+    let progress_bar = if args.no_progress || args.quiet {
         None
     } else {
         let pb = ProgressBar::new(wl_len as u64);
         pb.set_style(
             ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({per_sec}) {msg}")
+                .template(if colored::control::SHOULD_COLORIZE.should_colorize() {
+                    "{spinner:.green} {prefix}[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({per_sec}) {msg}"
+                } else {
+                    // The bar is drawn on stderr, which indicatif colors on its own
+                    "{spinner} {prefix}[{elapsed_precise}] [{bar:40}] {pos}/{len} ({per_sec}) {msg}"
+                })
                 .unwrap()
                 .progress_chars("#>-"),
         );
-        pb.set_message("Scanning...");
+        pb.set_message(console::progress_message(0, 0, None, None));
         Some(pb)
     };
+    console::set_progress_bar(progress_bar.clone());
 
     // Build the wildcard detection profile
     let wildcard_profile = wildcard::build_wildcard_profile(&client, &config).await;
 
+    let baseline_body = match &config.diff_baseline {
+        Some(path) => Some(
+            wildcard::fetch_baseline(&client, &config, path)
+                .await
+                .map_err(|e| format!("--diff-baseline: cannot fetch {path}: {e}"))?,
+        ),
+        None => None,
+    };
+
     // Set up shared state
     let state = Arc::new(ScanState {
         global_delay: AtomicU64::new(0),
         found_count: AtomicUsize::new(0),
         error_count: AtomicUsize::new(0),
+        error_kinds: ErrorKindCounts::default(),
         filtered_count: AtomicUsize::new(0),
+        redirect_count: AtomicUsize::new(0),
+        response_count: AtomicUsize::new(0),
+        consecutive_errors: AtomicUsize::new(0),
+        failed_requests: AtomicUsize::new(0),
+        recent_outcomes: Mutex::new(VecDeque::new()),
         should_stop: AtomicBool::new(false),
+        paused: AtomicBool::new(false),
+        stop_reason: OnceLock::new(),
         wildcard_profile,
+        baseline_body,
+        rate_limiter: args.rate.map(RateLimiter::new),
+        metrics: autotune::RequestMetrics::default(),
+        response_cache,
+        waf_detector: args.waf_detect.then(|| {
+            WafDetector::new(
+                args.waf_codes.clone(),
+                args.waf_threshold,
+                Duration::from_secs(args.waf_cooldown),
+            )
+        }),
+        harvested_words: Mutex::new(HashSet::new()),
+        smart_filter: OnceLock::new(),
+        seen_body_hashes: Mutex::new(HashSet::new()),
+        deduplicated_count: AtomicUsize::new(0),
+        proxies: proxy_rotation,
+        reauth: args.reauth_command.clone().map(|command| {
+            Reauth::new(command, args.reauth_trigger.clone(), args.reauth_threshold)
+        }),
+        spider: args
+            .spider_depth
+            .map(|depth| Spider::new(args.spider_max_urls, depth as usize, &word_list)),
+        processed_words: Mutex::new(Vec::new()),
+        last_error: Mutex::new(None),
+        request_log: config
+            .request_log
+            .as_deref()
+            .map(RequestLog::spawn)
+            .transpose()?,
+        body_bytes: AtomicU64::new(0),
+        content_length_bytes: AtomicU64::new(0),
     });
 
-    // Handle Ctrl+C for graceful shutdown
-    let state_clone = state.clone();
-    tokio::spawn(async move {
-        if signal::ctrl_c().await.is_ok() {
-            println!("\nReceived Ctrl+C, stopping scan gracefully...");
-            state_clone.should_stop.store(true, Ordering::Relaxed);
-        }
+    let controller = args.auto_threads.then(|| {
+        tokio::spawn(autotune::run_controller(
+            concurrency_limit.clone(),
+            state.clone(),
+            progress_bar.clone(),
+        ))
     });
 
     let start = Instant::now();
-    let all_results: Arc<Mutex<Vec<BustResult>>> = Arc::new(Mutex::new(Vec::new()));
+    let start_time = chrono::Utc::now();
 
-    // Create a stream of tasks to be executed concurrently
-    let word_stream = stream::iter(word_list.into_iter().map(|word| {
-        let sem = semaphore.clone();
-        let client = client.clone();
-        let config = config.clone();
+    // Dump the scan status on SIGUSR1, for runs without a terminal to press s in
+    #[cfg(unix)]
+    tokio::spawn({
         let state = state.clone();
-        let pb = progress_bar.clone();
-        let all_results_clone = all_results.clone();
-
         async move {
-            let _permit = sem.acquire().await.expect("Semaphore error");
-            let result = buster::bust_url_with_retry(&client, word.clone(), &config, &state).await;
-
-            if let Some(ref pb) = pb {
-                pb.inc(1);
+            let Ok(mut usr1) = signal::unix::signal(signal::unix::SignalKind::user_defined1())
+            else {
+                return;
+            };
+            while usr1.recv().await.is_some() {
+                tracing::info!("Scan status: {}", state.status(wl_len, start.elapsed()));
             }
+        }
+    });
 
-            let result_clone = result.clone();
-            let mut unlocked_all_results_clone = all_results_clone.lock().await;
-
-            // Update counters based on the result
-            match &result {
-                BustResult::Success(_resp) => {
-                    state.found_count.fetch_add(1, Ordering::Relaxed);
-                    unlocked_all_results_clone.push(result_clone);
+    // Keyboard controls while scanning: p pauses, r resumes, s prints the current stats
+    // and + or - change the concurrency
+    let key_input = console::spawn_key_reader({
+        let state = state.clone();
+        let pb = progress_bar.clone();
+        let concurrency_limit = concurrency_limit.clone();
+        move |key| match key {
+            'p' if !state.paused.swap(true, Ordering::Relaxed) => {
+                if let Some(pb) = &pb {
+                    pb.set_message("Paused");
                 }
-                BustResult::NotFound(_resp) => {
-                    unlocked_all_results_clone.push(result_clone);
+                print_live(
+                    pb.as_ref(),
+                    "Scan paused, press r to resume".yellow().to_string(),
+                );
+            }
+            'r' if state.paused.swap(false, Ordering::Relaxed) => {
+                if let Some(pb) = &pb {
+                    pb.set_message(state.progress_message(Some(concurrency_limit.limit())));
                 }
-                BustResult::Error(_, _) => {
-                    let errors = state.error_count.fetch_add(1, Ordering::Relaxed) + 1;
-                    if let Some(ref pb) = pb {
-                        pb.set_message(format!("Scanning... Errors: {errors}"));
+                print_live(pb.as_ref(), "Scan resumed".green().to_string());
+            }
+            '+' | '=' | '-' => {
+                let step = autotune::CONCURRENCY_STEP as isize;
+                let delta = if key == '-' { -step } else { step };
+                if let Some(change) = concurrency_limit.adjust(delta) {
+                    tracing::info!(from = change.from, to = change.to, "Concurrency changed");
+                    if let Some(pb) = &pb
+                        && !state.paused.load(Ordering::Relaxed)
+                    {
+                        pb.set_message(state.progress_message(Some(change.to)));
                     }
-                    unlocked_all_results_clone.push(result_clone);
-                }
-                BustResult::Filtered(_resp) => {
-                    state.filtered_count.fetch_add(1, Ordering::Relaxed);
-                    unlocked_all_results_clone.push(result_clone);
+                    print_live(
+                        pb.as_ref(),
+                        format!("Concurrency: {} -> {}", change.from, change.to)
+                            .cyan()
+                            .to_string(),
+                    );
                 }
             }
+            's' => print_live(pb.as_ref(), live_stats(&state, start.elapsed())),
+            _ => {}
+        }
+    });
+    // The concurrency is only shown, and extra requests buffered, when it can change
+    let resizable = args.auto_threads || key_input.is_some();
+    if resizable && let Some(ref pb) = progress_bar {
+        pb.set_message(state.progress_message(Some(concurrency_limit.limit())));
+    }
+    let key_input = Arc::new(Mutex::new(key_input));
 
-            result
+    // The first Ctrl+C stops scheduling new words and lets the requests in flight finish,
+    // a second one quits right away
+    tokio::spawn({
+        let state = state.clone();
+        let key_input = key_input.clone();
+        async move {
+            if signal::ctrl_c().await.is_err() {
+                return;
+            }
+            tracing::info!(
+                "Received Ctrl+C, finishing requests in flight (press Ctrl+C again to quit)..."
+            );
+            state.request_stop(StopReason::Interrupted);
+
+            if signal::ctrl_c().await.is_ok() {
+                // Nothing else is cleaned up, but the terminal must not stay in cbreak mode
+                drop(key_input.lock().unwrap().take());
+                eprintln!("\n{}", "Quitting without saving results".red().bold());
+                std::process::exit(ExitStatus::Interrupted.code().into());
+            }
         }
-    }));
+    });
+
+    // Stop the scan once its time limit is reached, like Ctrl+C but keeping the exit code
+    if let Some(limit) = config.max_scan_time {
+        let state = state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(limit)).await;
+            tracing::info!("Scan time limit reached.");
+            state.request_stop(StopReason::TimeLimit(limit));
+        });
+    }
+
+    // Start the incremental output writer so results reach the disk as they arrive. It
+    // also writes the error log, which can be kept without an output file
+    let output_writer = if output_file.is_some() || config.error_log.is_some() {
+        Some(output::OutputWriter::spawn(
+            config.clone(),
+            output_file.clone(),
+            output_format,
+            output_mode == output::OutputMode::Append,
+            start_time,
+        )?)
+    } else {
+        None
+    };
 
-    // Buffer the stream to control the level of concurrency
-    let buffered_stream = word_stream.buffer_unordered(args.threads);
+    // Webhook notifications are batched by a background task
+    let notifier = args.notify_webhook.clone().map(|webhook_url| {
+        notify::Notifier::spawn(webhook_url, args.notify_codes.clone(), config.clone())
+    });
 
-    // Process the results as they come in
-    buffered_stream
-        .for_each(|result| {
-            let pb = progress_bar.clone();
-            let config_clone = config.clone();
+    // Results shown during the scan, kept to print them again by response time
+    let timed_results = Mutex::new(Vec::new());
+    // Latency and status statistics of every response, for the summary and the report
+    let scan_stats = Mutex::new(ScanStats::default());
+    // Records the statistics of a reported result and prints it
+    let show_result = |result: BustResult| {
+        scan_stats.lock().unwrap().record(&result);
+        if console::is_quiet() {
+            if let Some(line) = output::format_quiet(&result, &config) {
+                println!("{line}");
+            } else if let Some(line) = output::format_quiet_error(&result, &config) {
+                eprintln!("{line}");
+            }
+            return;
+        }
 
-            async move {
-                match result {
-                    BustResult::Success(_) => {
-                        let output = output::format_output(&result, &config_clone);
-                        if let Some(ref pb) = pb {
-                            pb.suspend(|| println!("{output}"));
-                        } else {
-                            println!("{output}");
-                        }
+        let visible = match result {
+            BustResult::Success(_) => true,
+            BustResult::Redirect(_, _) => args.show_redirects && !args.only_success,
+            BustResult::ClientError(_) | BustResult::NotFound(_) | BustResult::Error(..) => {
+                !args.only_success
+            }
+            // Filtered results are only printed to show their wildcard confidence
+            BustResult::Filtered(_) => config.show_wildcard_confidence && !args.only_success,
+        };
+
+        if visible {
+            if args.sort_by_time {
+                timed_results.lock().unwrap().push(result.clone());
+            }
+            let output = output::format_output(&result, &config);
+            if let Some(ref pb) = progress_bar {
+                pb.suspend(|| println!("{output}"));
+            } else {
+                println!("{output}");
+            }
+        }
+    };
+
+    // The words of the current pass: the word list, then with --retry-failed-pass the
+    // words that failed in it
+    let mut pass_words = word_list;
+    let mut retry_pass = false;
+    let mut spider_depth = 0;
+    // Failed words are collected over the word list and spider passes, for one retry pass
+    let retry = RetryPass::default();
+    // Requests to each host are limited by --threads-per-host, within the global limit
+    let host_semaphores = Mutex::new(HashMap::new());
+    // The results held back to learn the --smart-filter profile from, until it is learned
+    let mut smart_sample = config
+        .smart_filter
+        .then(wildcard::SmartFilterSample::default);
+    loop {
+        // The main pass buffers enough requests for the limit to be raised at runtime
+        let concurrency = if retry_pass {
+            RetryPass::concurrency(args.threads, args.retry_pass_threads)
+        } else if resizable {
+            args.threads.max(autotune::MAX_CONCURRENCY)
+        } else {
+            args.threads
+        };
+
+        // Create a stream of tasks to be executed concurrently, until the scan is stopped
+        let word_stream = stream::iter(pass_words)
+            .take_while(|_| future::ready(!state.should_stop.load(Ordering::Relaxed)))
+            .map(|word| {
+                let sem = semaphore.clone();
+                let host_sem = host_semaphore(&host_semaphores, &word, &config);
+                let client = client.clone();
+                let version_clients = version_clients.clone();
+                let config = config.clone();
+                let state = state.clone();
+                let concurrency_limit = concurrency_limit.as_ref();
+
+                async move {
+                    // Words still waiting for a permit when the scan is stopped are left unscanned.
+                    // The host permit comes first, so waiting on a busy host holds no global permit
+                    let _host_permit = host_sem.acquire().await.expect("Semaphore error");
+                    let permit = sem.acquire().await.expect("Semaphore error");
+                    if state.should_stop.load(Ordering::Relaxed) {
+                        return None;
                     }
-                    BustResult::NotFound(_) => {
-                        if !args.only_success {
-                            let output = output::format_output(&result, &config_clone);
-                            if let Some(ref pb) = pb {
-                                pb.suspend(|| println!("{output}"));
-                            } else {
-                                println!("{output}");
-                            }
+                    let results = match &version_clients {
+                        Some(clients) => {
+                            buster::bust_url_with_version_probe(clients, word, &config, &state).await
                         }
-                    }
-                    BustResult::Error(_, _) => {
-                        if !args.only_success {
-                            let output = output::format_output(&result, &config_clone);
-                            if let Some(ref pb) = pb {
-                                pb.suspend(|| println!("{output}"));
-                            } else {
-                                println!("{output}");
-                            }
+                        None => vec![buster::bust_url_with_retry(&client, word, &config, &state).await],
+                    };
+                    // Permits released after the limit was lowered are removed right away
+                    drop(permit);
+                    concurrency_limit.settle();
+
+                    // So is a word whose requests were cut short by the stop
+                    let stopped = |result: &BustResult| {
+                        matches!(result, BustResult::Error(_, message, _) if message == SCAN_STOPPED)
+                    };
+                    (!results.iter().any(stopped)).then_some(results)
+                }
+            });
+
+        // Reports the results of a word. Processed words are added to the scan state in
+        // batches
+        let mut processed_batch = Vec::with_capacity(buster::PROCESSED_BATCH_SIZE);
+        let concurrency_limit = concurrency_limit.as_ref();
+        let defer_errors = args.retry_failed_pass && !retry_pass;
+        let mut report = |mut results: Vec<BustResult>| {
+            // Errors of the main pass are only reported if the retry pass fails as well
+            let outcome = buster::word_outcome(&results);
+            if defer_errors && retry.defer(outcome) {
+                if let Some(ref pb) = progress_bar {
+                    pb.inc(1);
+                }
+                return;
+            }
+            let word = outcome.word().to_string();
+            // Results found after the --limit was reached are left unscanned
+            results.retain(|result| {
+                !matches!(result, BustResult::Success(_)) || state.record_found(&config)
+            });
+            if results.is_empty() {
+                return;
+            }
+
+            if let Some(ref pb) = progress_bar {
+                pb.inc(1);
+            }
+            if retry_pass {
+                retry.record_retry(buster::word_outcome(&results));
+            }
+            let shown_limit = resizable.then_some(concurrency_limit);
+            for result in results {
+                record_result(&result, &state, progress_bar.as_ref(), shown_limit, &config);
+                if let Some(writer) = &output_writer {
+                    writer.send(result.clone());
+                }
+                if let Some(notifier) = &notifier {
+                    notifier.send(&result);
+                }
+                show_result(result);
+            }
+
+            processed_batch.push(word);
+            if processed_batch.len() == buster::PROCESSED_BATCH_SIZE {
+                state.add_processed_words(&mut processed_batch);
+            }
+        };
+
+        // Process the results as they come in, buffering the stream to control the level
+        // of concurrency. With --smart-filter the results of the first words are held back
+        // until the typical response is learned from them
+        word_stream
+            .buffer_unordered(concurrency)
+            .for_each(|results| {
+                let Some(results) = results else {
+                    return future::ready(());
+                };
+                match &mut smart_sample {
+                    Some(sample) => {
+                        if sample.hold(results) {
+                            learn_smart_filter(smart_sample.take(), &state, &mut report);
                         }
                     }
-                    BustResult::Filtered(_) => {
-                        // Do not print filtered results to the console
-                    }
+                    None => report(results),
+                }
+                future::ready(())
+            })
+            .await;
+        // A scan shorter than the sample learns from what it got
+        learn_smart_filter(smart_sample.take(), &state, &mut report);
+        state.add_processed_words(&mut processed_batch);
+        if state.should_stop.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // The links found in this pass are scanned next, one level deeper
+        if let Some(spider) = &state.spider
+            && !retry_pass
+            && spider_depth < config.spider_depth
+        {
+            let links = spider.take_queue();
+            if !links.is_empty() {
+                spider_depth += 1;
+                notice!(
+                    "Spidering {} linked paths (depth {spider_depth})",
+                    links.len()
+                );
+                if let Some(ref pb) = progress_bar {
+                    pb.inc_length(links.len() as u64);
+                    pb.set_prefix(format!("spider depth {spider_depth} "));
                 }
+                pass_words = links;
+                continue;
             }
-        })
-        .await;
+        }
+
+        let failed_words = retry.take_words();
+        if retry_pass || failed_words.is_empty() {
+            break;
+        }
+        notice!(
+            "Retrying {} failed words at reduced concurrency",
+            failed_words.len()
+        );
+        if let Some(ref pb) = progress_bar {
+            pb.set_length(failed_words.len() as u64);
+            pb.set_position(0);
+            pb.set_prefix("retry pass ");
+        }
+        retry_pass = true;
+        pass_words = failed_words;
+    }
+
+    // The errors held back for a retry pass that the stop cut off are reported after all
+    let deferred = retry.take_errors();
+    if !deferred.is_empty() {
+        notice!(
+            "Scan stopped before the retry pass, reporting {} failed words",
+            deferred.len()
+        );
+        let shown_limit = resizable.then_some(concurrency_limit.as_ref());
+        let mut words = Vec::with_capacity(deferred.len());
+        for result in deferred {
+            record_result(&result, &state, progress_bar.as_ref(), shown_limit, &config);
+            if let Some(writer) = &output_writer {
+                writer.send(result.clone());
+            }
+            if let Some(notifier) = &notifier {
+                notifier.send(&result);
+            }
+            words.push(result.word().to_string());
+            show_result(result);
+        }
+        state.add_processed_words(&mut words);
+    }
 
     if let Some(ref pb) = progress_bar {
-        pb.finish_with_message("Scan complete!");
+        pb.finish_with_message(match state.stop_reason() {
+            Some(_) => "Scan stopped!",
+            None => "Scan complete!",
+        });
+    }
+    if let Some(controller) = controller {
+        controller.abort();
+    }
+    console::set_progress_bar(None);
+    drop(key_input.lock().unwrap().take());
+
+    if args.sort_by_time {
+        let timed_results = timed_results.into_inner().unwrap();
+        notice!(
+            "\n{}",
+            "Results by response time:".bold().underline().blue()
+        );
+        for result in output::sort_results(&timed_results, output::SortKey::Time, false) {
+            println!("{}", output::format_output(result, &config));
+        }
     }
 
     let elapsed = start.elapsed();
@@ -232,46 +1193,378 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let final_errors = state.error_count.load(Ordering::Relaxed);
     let final_filtered = state.filtered_count.load(Ordering::Relaxed);
 
-    // Save final results to a file if specified
-    if let Some(output_file) = &args.output_file {
-        output::save_results(
-            all_results,
-            &config,
-            output_file,
-            &args.output_format,
-            elapsed.as_secs_f64(),
-            wl_len,
-            final_found,
-            final_errors,
-            final_filtered,
-        )
-        .await?;
-        println!("Results saved to: {output_file}");
+    let mut status = ExitStatus::for_scan(&state, args.fail_on_empty);
+    let block_episodes = state
+        .waf_detector
+        .as_ref()
+        .map(WafDetector::episodes)
+        .unwrap_or_default();
+
+    let concurrency_changes = concurrency_limit.changes();
+
+    let progress = state.snapshot();
+    let processed = progress.processed_words.len();
+    let spidered = state.spider.as_ref().map_or(0, Spider::found_count);
+    let unscanned = (wl_len + spidered).saturating_sub(processed);
+
+    // Send the last batch of notifications
+    if let Some(notifier) = notifier {
+        notifier.finish().await;
+    }
+
+    let scan_stats = scan_stats.into_inner().unwrap();
+
+    let summary = output::ScanSummary {
+        start_time,
+        duration: elapsed.as_secs_f64(),
+        finished: true,
+        total_count: processed,
+        found_count: final_found,
+        error_count: final_errors,
+        error_kinds: state.error_kinds.breakdown(),
+        filtered_count: final_filtered,
+        block_episodes: block_episodes.clone(),
+        concurrency_changes: concurrency_changes.clone(),
+        stats: Some(scan_stats.report()),
+        bytes_downloaded: state.body_bytes.load(Ordering::Relaxed),
+        response_count: state.response_count.load(Ordering::Relaxed),
+        content_length_bytes: state.content_length_bytes.load(Ordering::Relaxed),
+    };
+
+    // Finalize the output file and the error log if specified
+    if let Some(writer) = output_writer {
+        let saved = writer.finish(summary.clone()).await;
+        match saved {
+            Ok(()) => {
+                if let Some(output_file) = &output_file {
+                    notice!("Results saved to: {output_file}");
+                }
+            }
+            Err(e) => {
+                eprintln!("{} failed to save results: {e}", "Error:".red().bold());
+                status = ExitStatus::Aborted;
+            }
+        }
+    }
+
+    if let (Some(log), Some(path)) = (&state.request_log, &config.request_log) {
+        match log.finish().await {
+            Ok(()) => notice!("Requests logged to: {path}"),
+            Err(e) => eprintln!(
+                "{} failed to write request log: {e}",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if let Some(cache) = &state.response_cache {
+        match cache.save(&args.cache_file) {
+            Ok(()) => notice!("Response cache saved to: {}", args.cache_file),
+            Err(e) => eprintln!(
+                "{} failed to save response cache: {e}",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    // A stopped scan saves its processed words so it can be continued with --resume
+    if state.stop_reason().is_some() && unscanned > 0 {
+        let progress_file = args
+            .progress_file
+            .clone()
+            .unwrap_or_else(|| format!("{scan_id}.progress.json"));
+        match progress.save_to_disk(&progress_file) {
+            Ok(()) => notice!(
+                "Progress saved to: {progress_file} (continue with --resume {progress_file})"
+            ),
+            Err(e) => eprintln!(
+                "{} failed to save progress: {e}",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if args.extract_words {
+        let mut words: Vec<String> = state.harvested_words.lock().unwrap().drain().collect();
+        words.sort();
+        let content: String = words.iter().map(|word| format!("{word}\n")).collect();
+        match std::fs::write(&args.extracted_words_output, content) {
+            Ok(()) => notice!(
+                "Extracted {} words to: {}",
+                words.len(),
+                args.extracted_words_output
+            ),
+            Err(e) => eprintln!(
+                "{} failed to save extracted words: {e}",
+                "Warning:".yellow().bold()
+            ),
+        }
     }
 
     // Print the final summary
-    println!("\n{}", "Summary:".bold().underline().blue());
-    println!(
+    notice!("\n{}", "Summary:".bold().underline().blue());
+    notice!(
         "{:<15}{}",
         "Total words:".bold(),
         wl_len.to_string().white()
     );
-    println!("{:<15}{}", "Found:".bold(), final_found.to_string().green());
-    println!("{:<15}{}", "Errors:".bold(), final_errors.to_string().red());
-    println!(
+    if state.spider.is_some() {
+        notice!("{:<15}{}", "Spidered:".bold(), spidered.to_string().white());
+    }
+    notice!("{:<15}{}", "Found:".bold(), final_found.to_string().green());
+    notice!(
+        "{:<15}{}",
+        "Redirects:".bold(),
+        state
+            .redirect_count
+            .load(Ordering::Relaxed)
+            .to_string()
+            .cyan()
+    );
+    notice!(
+        "{:<15}{}{}",
+        "Errors:".bold(),
+        final_errors.to_string().red(),
+        output::format_error_kinds(&state.error_kinds.breakdown())
+    );
+    notice!(
         "{:<15}{}",
         "Filtered:".bold(),
         final_filtered.to_string().yellow()
     );
-    println!("{:<15}{:?}", "Elapsed:".bold(), elapsed);
-    println!(
+    if config.dedup_body_hash {
+        notice!(
+            "{:<15}{}",
+            "Deduplicated:".bold(),
+            state
+                .deduplicated_count
+                .load(Ordering::Relaxed)
+                .to_string()
+                .yellow()
+        );
+    }
+    if args.retry_failed_pass {
+        notice!(
+            "{:<15}{}",
+            "Recovered:".bold(),
+            retry.recovered().to_string().green()
+        );
+    }
+    if let Some(reauth) = &state.reauth {
+        let (successes, failures) = reauth.stats();
+        notice!(
+            "{:<15}{}",
+            "Re-auths:".bold(),
+            format!("{successes} ({failures} failed)").cyan()
+        );
+    }
+    if let Some(rotation) = &state.proxies {
+        notice!("{}", "Proxies:".bold());
+        for stats in rotation.stats() {
+            notice!("  {stats}");
+        }
+    }
+    if let Some(reason) = state.stop_reason() {
+        notice!(
+            "{:<15}{}",
+            "Stopped:".bold(),
+            format!("{reason} ({unscanned} words not scanned)").red()
+        );
+    }
+    if args.waf_detect {
+        let paused: u64 = block_episodes.iter().map(|e| e.cooldown_secs).sum();
+        notice!(
+            "{:<15}{}",
+            "WAF blocks:".bold(),
+            format!("{} (paused {paused}s)", block_episodes.len()).red()
+        );
+    }
+    if let (Some(first), Some(last)) = (concurrency_changes.first(), concurrency_changes.last()) {
+        notice!(
+            "{:<15}{} -> {} ({} changes)",
+            "Concurrency:".bold(),
+            first.from,
+            last.to,
+            concurrency_changes.len()
+        );
+    }
+    notice!("{:<15}{:?}", "Elapsed:".bold(), elapsed);
+    notice!(
         "{:<15}{:.2} req/sec",
         "Rate:".bold(),
-        wl_len as f64 / elapsed.as_secs_f64()
+        processed as f64 / elapsed.as_secs_f64()
     );
+    if let Some(limiter) = &state.rate_limiter {
+        notice!("{:<15}{:.2} req/sec", "Rate limit:".bold(), limiter.rate());
+    }
+    notice!(
+        "{:<15}{} ({} per response)",
+        "Downloaded:".bold(),
+        output::format_bytes(summary.bytes_downloaded),
+        output::format_bytes(summary.avg_bytes_per_response().round() as u64)
+    );
+    if let Some([p50, p90, p99]) = scan_stats.percentiles([50, 90, 99]) {
+        let time = |time| output::format_response_time(time, true);
+        notice!(
+            "{:<15}p50 {}, p90 {}, p99 {}",
+            "Latency:".bold(),
+            time(p50),
+            time(p90),
+            time(p99)
+        );
+        notice!("{}", "Slowest:".bold());
+        for (word, response_time) in scan_stats.slowest() {
+            notice!(
+                "  {:<13}{}",
+                output::redact(word, &config.redact_patterns),
+                time(*response_time).yellow()
+            );
+        }
+        // --status-summary lists them one per line below instead
+        if !args.status_code_summary {
+            notice!("{:<15}{}", "Statuses:".bold(), scan_stats.status_line());
+        }
+    }
+    notice!("{:<15}{}", "Exit code:".bold(), status);
+    if args.status_code_summary {
+        notice!("\n{}", "Status codes:".bold().underline().blue());
+        for (code, count) in scan_stats.statuses() {
+            notice!("{:<15}{}", format!("{code}:").bold(), count);
+        }
+    }
+
+    Ok(status)
+}
+
+/// Prints a line while the scan is running, around the progress bar if there is one.
+fn print_live(pb: Option<&ProgressBar>, line: String) {
+    match pb {
+        Some(pb) => pb.suspend(|| println!("{line}")),
+        None => println!("{line}"),
+    }
+}
+
+/// Formats the counters of a running scan for the `s` key.
+fn live_stats(state: &ScanState, elapsed: std::time::Duration) -> String {
+    let requests =
+        state.response_count.load(Ordering::Relaxed) + state.error_count.load(Ordering::Relaxed);
+    format!(
+        "{} found: {}, errors: {}, filtered: {}, rate: {:.2} req/sec",
+        "Stats:".bold(),
+        state
+            .found_count
+            .load(Ordering::Relaxed)
+            .to_string()
+            .green(),
+        state.error_count.load(Ordering::Relaxed).to_string().red(),
+        state
+            .filtered_count
+            .load(Ordering::Relaxed)
+            .to_string()
+            .yellow(),
+        requests as f64 / elapsed.as_secs_f64()
+    )
+}
+
+/// Returns the semaphore limiting the concurrent requests to the host a word is sent to.
+fn host_semaphore(
+    semaphores: &Mutex<HashMap<String, Arc<Semaphore>>>,
+    word: &str,
+    config: &ScanConfig,
+) -> Arc<Semaphore> {
+    let url = buster::request_url(word, config);
+    let host = reqwest::Url::parse(&url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+    semaphores
+        .lock()
+        .unwrap()
+        .entry(host)
+        .or_insert_with(|| Arc::new(Semaphore::new(config.threads_per_host)))
+        .clone()
+}
 
-    Ok(())
+/// Removes the words another tool's scan already covered from the word list.
+fn skip_previous_scan(word_list: &mut Vec<String>, scanned: Vec<String>, tool: &str) {
+    let scanned: HashSet<String> = scanned.into_iter().collect();
+    let before = word_list.len();
+    word_list.retain(|word| !scanned.contains(word));
+    notice!(
+        "Skipping {} words from previous {tool} scan",
+        before - word_list.len()
+    );
 }
 
-#[cfg(test)]
-mod tests;
+/// Learns the `--smart-filter` profile from the results held back, if they are still held,
+/// then reports them with it applied.
+fn learn_smart_filter(
+    sample: Option<wildcard::SmartFilterSample>,
+    state: &ScanState,
+    report: &mut impl FnMut(Vec<BustResult>),
+) {
+    let Some(sample) = sample else {
+        return;
+    };
+    let (profile, held) = sample.learn();
+    match profile {
+        Some(profile) => {
+            notice!("Smart filter: filtering responses with {profile}");
+            let _ = state.smart_filter.set(profile);
+        }
+        None => notice!("Smart filter: the first responses vary too much, nothing is filtered"),
+    }
+    for results in held {
+        report(results);
+    }
+}
+
+/// The longest error message shown in the progress bar with `--show-errors-inline`.
+const INLINE_ERROR_WIDTH: usize = 50;
+
+/// Updates the shared counters and the progress bar message for a completed result.
+///
+/// The concurrency is shown in the message when a limit is given.
+fn record_result(
+    result: &BustResult,
+    state: &ScanState,
+    pb: Option<&ProgressBar>,
+    concurrency_limit: Option<&autotune::AdaptiveLimit>,
+    config: &ScanConfig,
+) {
+    match result {
+        // Found results are counted by `ScanState::record_found` against the --limit
+        BustResult::Success(_resp) => {
+            state.response_count.fetch_add(1, Ordering::Relaxed);
+        }
+        BustResult::Redirect(_resp, _) => {
+            state.redirect_count.fetch_add(1, Ordering::Relaxed);
+            state.response_count.fetch_add(1, Ordering::Relaxed);
+        }
+        BustResult::ClientError(_resp) | BustResult::NotFound(_resp) => {
+            state.response_count.fetch_add(1, Ordering::Relaxed);
+        }
+        BustResult::Error(word, error, kind) => {
+            state.error_count.fetch_add(1, Ordering::Relaxed);
+            state.error_kinds.record(*kind);
+            if config.show_errors_inline {
+                // Redacted before truncating, which could cut a secret short of its pattern
+                let patterns = &config.redact_patterns;
+                let error = output::redact(&error.replace('\n', " "), patterns);
+                let error = output::truncate(&error, INLINE_ERROR_WIDTH);
+                let word = output::redact(word, patterns);
+                *state.last_error.lock().unwrap() = Some(format!("{word} - {error}"));
+            }
+        }
+        BustResult::Filtered(_resp) => {
+            state.filtered_count.fetch_add(1, Ordering::Relaxed);
+            state.response_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    // Refreshed on every result for the running download total, unless the scan is paused
+    if let Some(pb) = pb
+        && !state.paused.load(Ordering::Relaxed)
+    {
+        pb.set_message(state.progress_message(concurrency_limit.map(|limit| limit.limit())));
+    }
+}
@@ -0,0 +1,168 @@
+//! This module sends webhook notifications for interesting results during a scan.
+//!
+//! Findings are queued to a background task that batches them, posting at most one
+//! message per [`NOTIFY_INTERVAL`]. The payload carries both a `text` (Slack) and a
+//! `content` (Discord) field, so either kind of incoming webhook accepts it.
+//! Webhook failures are logged and dropped; they never fail or slow down the scan.
+
+use crate::buster::{BustResult, DetailedResponse, ScanConfig, request_url};
+use crate::output::redact;
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{MissedTickBehavior, interval};
+use tracing::{debug, warn};
+
+/// The minimum time between two webhook messages.
+pub const NOTIFY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The status codes notified about unless `--notify-codes` is given.
+pub const DEFAULT_NOTIFY_CODES: [u16; 3] = [200, 401, 403];
+
+/// How many findings a single message lists before summarizing the rest.
+const MAX_LISTED_FINDINGS: usize = 20;
+
+/// A finding queued for notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub url: String,
+    pub status: u16,
+    pub size: Option<u64>,
+}
+
+/// A handle to the background notification task.
+pub struct Notifier {
+    tx: mpsc::UnboundedSender<Finding>,
+    handle: JoinHandle<()>,
+    codes: Vec<u16>,
+    config: Arc<ScanConfig>,
+}
+
+impl Notifier {
+    /// Spawns the notification task posting to the given webhook URL.
+    pub fn spawn(webhook_url: String, codes: Vec<u16>, config: Arc<ScanConfig>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let target = redact(&config.base_url, &config.redact_patterns);
+        let handle = tokio::spawn(run_notifier(rx, webhook_url, target));
+        Self {
+            tx,
+            handle,
+            codes,
+            config,
+        }
+    }
+
+    /// Queues a result for notification if it is a response with one of the notified status codes.
+    pub fn send(&self, result: &BustResult) {
+        if let Some(resp) = notifiable_response(result, &self.codes) {
+            let _ = self.tx.send(Finding {
+                url: redact(
+                    &request_url(&resp.word, &self.config),
+                    &self.config.redact_patterns,
+                ),
+                status: resp.status,
+                size: resp.content_length,
+            });
+        }
+    }
+
+    /// Sends any findings still waiting for the next batch and stops the task.
+    pub async fn finish(self) {
+        drop(self.tx);
+        let _ = self.handle.await;
+    }
+}
+
+/// Returns the response of a result that should be notified about.
+///
+/// Any reported response qualifies when its status is in `codes`, so access-denied
+/// paths such as 401 and 403 can be notified too. Filtered results never are.
+pub fn notifiable_response<'a>(
+    result: &'a BustResult,
+    codes: &[u16],
+) -> Option<&'a DetailedResponse> {
+    result
+        .reported_response()
+        .filter(|resp| codes.contains(&resp.status))
+}
+
+/// Builds the text of a notification message for a batch of findings.
+pub fn render_message(target: &str, findings: &[Finding]) -> String {
+    let mut message = format!(
+        "dirbuster-rs found {} result(s) on {target}:",
+        findings.len()
+    );
+    for finding in findings.iter().take(MAX_LISTED_FINDINGS) {
+        let size = finding
+            .size
+            .map_or_else(|| "-".to_string(), |size| format!("{size}B"));
+        message.push_str(&format!("\n{} {size} {}", finding.status, finding.url));
+    }
+    if findings.len() > MAX_LISTED_FINDINGS {
+        message.push_str(&format!(
+            "\n... and {} more",
+            findings.len() - MAX_LISTED_FINDINGS
+        ));
+    }
+    message
+}
+
+/// The body of the notification task.
+async fn run_notifier(
+    mut rx: mpsc::UnboundedReceiver<Finding>,
+    webhook_url: String,
+    target: String,
+) {
+    let client = match Client::builder().timeout(Duration::from_secs(10)).build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!(error = %e, "Could not create the webhook client, notifications are disabled");
+            while rx.recv().await.is_some() {}
+            return;
+        }
+    };
+
+    let mut pending: Vec<Finding> = Vec::new();
+    let mut ticker = interval(NOTIFY_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            finding = rx.recv() => match finding {
+                Some(finding) => pending.push(finding),
+                None => break,
+            },
+            _ = ticker.tick() => {
+                if !pending.is_empty() {
+                    post(&client, &webhook_url, &target, &pending).await;
+                    pending.clear();
+                }
+            }
+        }
+    }
+
+    if !pending.is_empty() {
+        post(&client, &webhook_url, &target, &pending).await;
+    }
+}
+
+/// Posts one notification message, logging instead of failing on errors.
+async fn post(client: &Client, webhook_url: &str, target: &str, findings: &[Finding]) {
+    let message = render_message(target, findings);
+    let payload = serde_json::json!({ "text": message, "content": message });
+
+    match client.post(webhook_url).json(&payload).send().await {
+        Ok(response) if response.status().is_success() => {
+            debug!(findings = findings.len(), "Sent webhook notification");
+        }
+        Ok(response) => {
+            warn!(
+                status = response.status().as_u16(),
+                "Webhook rejected the notification"
+            );
+        }
+        Err(e) => warn!(error = %e, "Failed to send webhook notification"),
+    }
+}
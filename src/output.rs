@@ -3,15 +3,36 @@
 //! saving results to files in various formats (JSON, CSV, XML, text),
 //! and managing the saving and loading of scan progress for resume functionality.
 
-use crate::buster::{BustResult, ScanConfig};
-use colored::Colorize;
-use std::fs::write;
+use crate::autotune::ConcurrencyChange;
+use crate::buster::{
+    BustResult, DetailedResponse, ErrorKind, ScanConfig, bypass_headers, request_headers,
+    request_url,
+};
+use crate::stats::StatsReport;
+use crate::waf::BlockEpisode;
+use chrono::{DateTime, Utc};
+use colored::{ColoredString, Colorize};
+use regex::Regex;
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions, rename, write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior, interval};
+
+/// How often the output writer flushes buffered results to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
 
 /// A struct that represents the full scan report for serialization, primarily for JSON output.
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct ScanReport {
+    scan_id: String,
     target: String,
     start_time: String,
     end_time: String,
@@ -19,185 +40,1404 @@ pub struct ScanReport {
     total_requests: usize,
     success_count: usize,
     error_count: usize,
+    /// The failed requests by kind of error, e.g. `timeout` or `connect`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    error_kinds: BTreeMap<ErrorKind, usize>,
     filtered_count: usize,
     rate: f64,
+    /// The decoded body bytes downloaded, and their average over the responses.
+    #[serde(default)]
+    bytes_downloaded: u64,
+    #[serde(default)]
+    avg_bytes_per_response: f64,
+    /// The sum of the `Content-Length` headers of the responses that had one.
+    #[serde(default)]
+    content_length_bytes: u64,
+    /// Set when TLS certificates were not verified.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    insecure: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ca_cert: Option<String>,
+    /// The local address connections were made from, with `--bind-address` or `--interface`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bind_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interface: Option<String>,
+    /// The `--resolve` overrides, as `host:port:addr[,addr]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resolve: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    block_episodes: Vec<BlockEpisode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    concurrency_changes: Vec<ConcurrencyChange>,
+    /// The number of reported responses per status code.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    status_breakdown: BTreeMap<u16, usize>,
+    /// Latency percentiles, the slowest words and the status counts of every response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stats: Option<StatsReport>,
     results: Vec<ReportEntry>,
+    /// The words whose requests failed, so they can be scanned again.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<ReportError>,
 }
 
 /// A struct that represents a single entry in the scan report.
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct ReportEntry {
     word: String,
     status: u16,
     content_length: Option<u64>,
     response_time_ms: u64,
+    #[serde(default)]
+    response_time_us: u64,
+    /// The time to first byte and the body read time, with `--timing-detail`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttfb_us: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_read_us: Option<u64>,
     word_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line_count: Option<usize>,
     url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bypass_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_version: Option<String>,
+    /// The HTTP version the response was received over, e.g. `HTTP/2`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    negotiated_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wildcard_confidence: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redirect_location: Option<String>,
+    /// Where the word came from when it was not in the word list, e.g. `robots`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    /// The `Content-Length` header and the length of the body as read, when they differ.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    header_length: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_length: Option<u64>,
+    /// Set when only the first `--max-body-size` bytes of the body were read.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    body_truncated: bool,
+    /// The `Set-Cookie` headers of the response, with `--show-cookies`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    set_cookies: Vec<String>,
+}
+
+/// A failed request in the scan report.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ReportError {
+    word: String,
+    error: String,
+    #[serde(default)]
+    kind: ErrorKind,
+}
+
+/// A failed request, written as one JSON line of the `--error-log` file.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ErrorLogEntry {
+    /// When the failure was recorded, in RFC 3339 format.
+    pub timestamp: String,
+    pub word: String,
+    pub error: String,
+    #[serde(default)]
+    pub kind: ErrorKind,
+}
+
+/// The entries of a previous report to scan again with `--from-report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportSelection {
+    /// Every reported response and failed request.
+    All,
+    /// Successful (2xx) responses.
+    Found,
+    /// Failed requests.
+    Errors,
+    /// Responses with a status in the inclusive range.
+    Status(u16, u16),
+}
+
+/// Loads a JSON scan report and returns the words of the selected entries.
+pub fn load_report_words(path: &str, selection: ReportSelection) -> std::io::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("cannot read {path}: {e}")))?;
+    let report: ScanReport = serde_json::from_str(&content).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{path} is not a dirbuster-rs JSON report ({e}); reports are written with --output-format json"),
+        )
+    })?;
+
+    let in_range = |min: u16, max: u16| {
+        report
+            .results
+            .iter()
+            .filter(move |entry| (min..=max).contains(&entry.status))
+            .map(|entry| entry.word.clone())
+    };
+    let errors = report.errors.iter().map(|error| error.word.clone());
+    let words = match selection {
+        ReportSelection::All => in_range(0, u16::MAX).chain(errors).collect(),
+        ReportSelection::Found => in_range(200, 299).collect(),
+        ReportSelection::Errors => errors.collect(),
+        ReportSelection::Status(min, max) => in_range(min, max).collect(),
+    };
+    Ok(words)
+}
+
+/// The supported formats for the output file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Xml,
+    Csv,
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Parses a format name, falling back to plain text for unknown values.
+    pub fn from_name(name: &str) -> Self {
+        Self::parse(name).unwrap_or(OutputFormat::Text)
+    }
+
+    /// Parses a format name, returning `None` for unknown values.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "xml" => Some(OutputFormat::Xml),
+            "csv" => Some(OutputFormat::Csv),
+            "markdown" | "md" => Some(OutputFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    /// The name of the format as given on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Markdown => "markdown",
+        }
+    }
+
+    /// The file extension used for the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Markdown => "md",
+            other => other.name(),
+        }
+    }
+
+    /// Whether the format has a header/trailer and must be rewritten as a whole document.
+    /// Line-oriented formats are appended to as results arrive instead, unless sorted.
+    fn is_structured(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Json | OutputFormat::Xml | OutputFormat::Markdown
+        )
+    }
+
+    /// Whether the output must be rewritten as a whole document on every flush.
+    fn is_snapshot(self, config: &ScanConfig) -> bool {
+        self.is_structured() || config.sort_by.is_some()
+    }
+}
+
+/// The fields report entries can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// The word, which orders the entries like their URLs.
+    #[value(alias = "url")]
+    Word,
+    Status,
+    /// Content length; responses without a known length sort as 0 bytes.
+    Size,
+    Time,
+}
+
+/// Sorts results for the output file.
+///
+/// Reported responses (found, redirect and error statuses) come first, ordered by the key.
+/// Filtered results follow, then errors, each group ordered by the same key where it
+/// applies. Descending order reverses the key but keeps the grouping. Entries with the same
+/// status are ordered by word, so the output does not depend on the order responses arrived
+/// in. The sort is stable.
+pub fn sort_results(results: &[BustResult], key: SortKey, descending: bool) -> Vec<&BustResult> {
+    let group = |result: &BustResult| match result {
+        BustResult::Filtered(_) => 1,
+        BustResult::Error(..) => 2,
+        _ => 0,
+    };
+
+    let compare_key = |a: &BustResult, b: &BustResult| -> Ordering {
+        match (a.response(), b.response()) {
+            (Some(a), Some(b)) => match key {
+                SortKey::Word => a.word.cmp(&b.word),
+                SortKey::Status => a.status.cmp(&b.status),
+                SortKey::Size => a
+                    .content_length
+                    .unwrap_or(0)
+                    .cmp(&b.content_length.unwrap_or(0)),
+                SortKey::Time => a.response_time.cmp(&b.response_time),
+            },
+            _ => a.word().cmp(b.word()),
+        }
+    };
+
+    let mut sorted: Vec<&BustResult> = results.iter().collect();
+    sorted.sort_by(|a, b| {
+        group(a)
+            .cmp(&group(b))
+            .then_with(|| {
+                let ordering = compare_key(a, b);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .then_with(|| match key {
+                SortKey::Status => a.word().cmp(b.word()),
+                _ => Ordering::Equal,
+            })
+    });
+    sorted
+}
+
+/// How an output file that already exists should be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Never touch an existing file; pick a fresh, suffixed file name instead.
+    Create,
+    /// Replace an existing file.
+    Overwrite,
+    /// Add the new results to the end of an existing file.
+    Append,
+}
+
+/// Determines the path the output should be written to.
+///
+/// Appending is only possible for line-oriented formats. In `Create` mode an existing
+/// file is never overwritten: `report.json` becomes `report-2.json`, `report-3.json`, etc.
+pub fn resolve_output_path(
+    output_file: &str,
+    format: OutputFormat,
+    mode: OutputMode,
+) -> Result<String, String> {
+    match mode {
+        OutputMode::Append if format.is_structured() => Err(format!(
+            "--output-append is not supported for {} output, since the file must stay a single valid document; use a text or csv output format, or a different --output-file",
+            format.name()
+        )),
+        OutputMode::Create if Path::new(output_file).exists() => {
+            Ok(next_available_path(output_file))
+        }
+        _ => Ok(output_file.to_string()),
+    }
+}
+
+/// Checks that a CSV file being appended to has the columns this scan writes, so rows with
+/// and without the `--timing-detail` columns are not mixed. A missing file has no columns yet.
+pub fn check_csv_columns(path: &str, timing_detail: bool) -> Result<(), String> {
+    let Ok(file) = File::open(path) else {
+        return Ok(());
+    };
+    let header = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .find(|line| !line.starts_with('#'));
+    let expected = if timing_detail {
+        format!("{CSV_HEADER}{CSV_TIMING_HEADER}")
+    } else {
+        CSV_HEADER.to_string()
+    };
+    match header {
+        Some(header) if !header.is_empty() && header != expected => Err(format!(
+            "--output-append: {path} was written {} --timing-detail, use the same setting or a different --output-file",
+            if timing_detail { "without" } else { "with" }
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Finds the first `<stem>-N.<ext>` path (starting at 2) that does not exist yet.
+fn next_available_path(output_file: &str) -> String {
+    let path = Path::new(output_file);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|n| path.with_file_name(format!("{stem}-{n}{extension}")))
+        .find(|candidate| !candidate.exists())
+        .map(|candidate| candidate.to_string_lossy().into_owned())
+        .expect("an unused file name always exists")
+}
+
+/// Aggregate counters and timing for a scan, written into the report headers.
+#[derive(Debug, Clone, Default)]
+pub struct ScanSummary {
+    /// The wall-clock time the scan started.
+    pub start_time: DateTime<Utc>,
+    /// The scan duration in seconds.
+    pub duration: f64,
+    /// Whether the scan has finished, as opposed to a snapshot of a scan in progress.
+    pub finished: bool,
+    pub total_count: usize,
+    pub found_count: usize,
+    pub error_count: usize,
+    pub filtered_count: usize,
+    /// The WAF blocks detected during the scan.
+    pub block_episodes: Vec<BlockEpisode>,
+    /// The changes of the concurrency limit made from the keyboard.
+    pub concurrency_changes: Vec<ConcurrencyChange>,
+    /// The response statistics, once the scan has finished.
+    pub stats: Option<StatsReport>,
+    /// The failed requests by kind of error.
+    pub error_kinds: BTreeMap<ErrorKind, usize>,
+    /// The decoded body bytes downloaded.
+    pub bytes_downloaded: u64,
+    /// The requests that received a response, which the downloaded bytes are averaged over.
+    pub response_count: usize,
+    /// The sum of the `Content-Length` headers of the responses that had one.
+    pub content_length_bytes: u64,
+}
+
+impl ScanSummary {
+    /// The wall-clock time the scan ended, derived from the start time and duration.
+    pub fn end_time(&self) -> DateTime<Utc> {
+        self.start_time + chrono::Duration::microseconds((self.duration * 1_000_000.0) as i64)
+    }
+
+    /// The average body bytes downloaded per received response.
+    pub fn avg_bytes_per_response(&self) -> f64 {
+        match self.response_count {
+            0 => 0.0,
+            responses => self.bytes_downloaded as f64 / responses as f64,
+        }
+    }
+
+    /// Updates the counters with a single result.
+    fn record(&mut self, result: &BustResult) {
+        self.total_count += 1;
+        match result {
+            BustResult::Success(_) => self.found_count += 1,
+            BustResult::Error(_, _, kind) => {
+                self.error_count += 1;
+                *self.error_kinds.entry(*kind).or_insert(0) += 1;
+            }
+            BustResult::Filtered(_) => self.filtered_count += 1,
+            BustResult::Redirect(_, _) | BustResult::ClientError(_) | BustResult::NotFound(_) => {}
+        }
+    }
 }
 
 /// Formats a `BustResult` into a colorized string for console output.
 pub fn format_output(result: &BustResult, config: &ScanConfig) -> String {
+    render_output(result, config, true)
+}
+
+/// Formats a `BustResult` like [`format_output`], but without any color codes.
+/// Used for text output files, which must stay plain regardless of the terminal.
+pub fn format_output_plain(result: &BustResult, config: &ScanConfig) -> String {
+    render_output(result, config, false)
+}
+
+/// Shared implementation of the console and plain result formats.
+fn render_output(result: &BustResult, config: &ScanConfig, colored: bool) -> String {
+    let patterns = &config.redact_patterns;
+    // With -v results are shown by their full URL instead of the word
+    let label = |word: &str| {
+        if config.verbose {
+            redact(&request_url(word, config), patterns)
+        } else {
+            redact(word, patterns)
+        }
+    };
+    let paint = |text: ColoredString| {
+        if colored {
+            text.to_string()
+        } else {
+            text.clear().to_string()
+        }
+    };
+
+    // The optional annotations shared by all reported responses
+    let details = |resp: &DetailedResponse| {
+        let mut details = String::new();
+
+        if config.show_content_length
+            && let Some(len) = resp.content_length
+        {
+            details.push_str(&paint(format!(" [{len}B]").cyan()));
+        }
+
+        // Bodies too large to read in full stand out, as they are often worth a look
+        if resp.body_truncated {
+            let read = format_bytes(resp.body_length.unwrap_or_default());
+            details.push_str(&paint(format!(" [truncated at {read}]").magenta().bold()));
+        }
+
+        if config.show_word_count
+            && let Some(words) = resp.word_count
+        {
+            details.push_str(&paint(format!(" [{words}W]").cyan()));
+        }
+
+        if config.show_line_count
+            && let Some(lines) = resp.line_count
+        {
+            details.push_str(&paint(format!(" [Lc:{lines}]").cyan()));
+        }
+
+        if config.show_response_time || config.high_res_timing {
+            let time = format_response_time(resp.response_time, config.high_res_timing);
+            details.push_str(&paint(format!(" [{time}]").yellow()));
+        }
+
+        if let Some(body_time) = resp.body_read_time {
+            let ttfb = format_response_time(resp.response_time, true);
+            let body_time = format_response_time(body_time, true);
+            details.push_str(&paint(
+                format!(" [TTFB: {ttfb}, body: {body_time}]").yellow(),
+            ));
+        }
+
+        if let Some(mode) = resp.bypass_mode {
+            details.push_str(&paint(format!(" [bypass: {mode}]").magenta()));
+        }
+
+        if let Some(version) = resp.probed_version {
+            details.push_str(&paint(format!(" [{version}]").blue()));
+        }
+
+        if config.show_http_version
+            && let Some(version) = resp.http_version
+        {
+            details.push_str(&paint(format!(" [{version}]").blue()));
+        }
+
+        if config.show_diff_score
+            && let Some(score) = resp.diff_score
+        {
+            details.push_str(&paint(format!(" [diff: {score:.2}]").purple()));
+        }
+
+        if let Some(source) = resp.source {
+            details.push_str(&paint(format!(" [{source}]").dimmed()));
+        }
+
+        // Only the name and value of each cookie, the attributes are in the report
+        if !resp.set_cookies.is_empty() {
+            let cookies: Vec<&str> = resp
+                .set_cookies
+                .iter()
+                .map(|cookie| cookie.split(';').next().unwrap_or_default().trim())
+                .collect();
+            let cookies = redact(&cookies.join(", "), patterns);
+            details.push_str(&paint(format!(" [cookies: {cookies}]").yellow()));
+        }
+
+        details
+    };
+
     match result {
         BustResult::Success(resp) => {
+            format!(
+                "{word}: {status}{details} {check}",
+                word = paint(label(&resp.word).green().bold()),
+                status = paint(resp.status.to_string().green()),
+                details = details(resp),
+                check = paint("✓".green().bold())
+            )
+        }
+        BustResult::Redirect(resp, location) => {
+            format!(
+                "{word}: {status}{details} {arrow} {location}",
+                word = paint(label(&resp.word).cyan()),
+                status = paint(resp.status.to_string().cyan()),
+                details = details(resp),
+                arrow = paint("->".cyan()),
+                location = paint(redact(location, patterns).cyan().italic())
+            )
+        }
+        BustResult::ClientError(resp) | BustResult::NotFound(resp) => {
+            format!(
+                "{word}: {status}{details}",
+                word = paint(label(&resp.word).dimmed()),
+                status = paint(resp.status.to_string().red()),
+                details = details(resp)
+            )
+        }
+        BustResult::Error(word, error, kind) => {
+            format!(
+                "{word}: {error_type} ({kind}) - {err_msg}",
+                word = paint(label(word).red().bold()),
+                error_type = paint("ERROR".red().bold()),
+                err_msg = paint(redact(error, patterns).red())
+            )
+        }
+        BustResult::Filtered(resp) => {
             let mut output = format!(
-                "{word}: {status}",
-                word = resp.word.green().bold(),
-                status = resp.status.to_string().green()
+                "{word}: {status} {tag}",
+                word = paint(label(&resp.word).yellow().bold()),
+                status = paint(resp.status.to_string().yellow()),
+                tag = paint("[FILTERED]".yellow().italic())
             );
 
-            if config.show_content_length {
-                if let Some(len) = resp.content_length {
-                    output.push_str(&format!(" [{len}B]").cyan().to_string());
-                }
+            if config.show_wildcard_confidence
+                && let Some(confidence) = resp.wildcard_confidence
+            {
+                output.push_str(&paint(format!(" [WC: {confidence:.2}]").purple()));
             }
 
-            if config.show_response_time {
-                output.push_str(
-                    &format!(" [{}ms]", resp.response_time.as_millis())
-                        .yellow()
-                        .to_string(),
-                );
-            }
-
-            output.push_str(&format!(" {}", "✓".green().bold()));
             output
         }
-        BustResult::NotFound(resp) => {
-            let mut output = format!(
-                "{word}: {status}",
-                word = resp.word.dimmed(),
-                status = resp.status.to_string().red()
-            );
+    }
+}
 
-            if config.show_content_length {
-                if let Some(len) = resp.content_length {
-                    output.push_str(&format!(" [{len}B]").cyan().to_string());
-                }
-            }
+/// Formats a response time in whole milliseconds, or with `high_res` at microsecond
+/// resolution: below 1 ms in `µs`, above with three decimals in `ms`.
+pub fn format_response_time(time: Duration, high_res: bool) -> String {
+    match high_res {
+        true if time < Duration::from_millis(1) => format!("{}µs", time.as_micros()),
+        true => format!("{:.3}ms", time.as_secs_f64() * 1000.0),
+        false => format!("{}ms", time.as_millis()),
+    }
+}
 
-            if config.show_response_time {
-                output.push_str(
-                    &format!(" [{}ms]", resp.response_time.as_millis())
-                        .yellow()
-                        .to_string(),
-                );
-            }
+/// Formats a byte count in B, KiB, MiB or GiB, with one decimal above bytes.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
 
-            output
-        }
-        BustResult::Error(word, error) => {
-            format!(
-                "{word}: {error_type} - {err_msg}",
-                word = word.red().bold(),
-                error_type = "ERROR".red().bold(),
-                err_msg = error.red()
+/// Formats the failed requests by kind as ` (timeout: 10, connect: 2)`, or nothing without
+/// failed requests.
+pub fn format_error_kinds(kinds: &BTreeMap<ErrorKind, usize>) -> String {
+    if kinds.is_empty() {
+        return String::new();
+    }
+    let counts: Vec<_> = kinds
+        .iter()
+        .map(|(kind, count)| format!("{kind}: {count}"))
+        .collect();
+    format!(" ({})", counts.join(", "))
+}
+
+/// Formats a found result as a plain `<status> <size> <url>` line for quiet mode.
+/// The size is `-` when the response had no content length. Other results produce no line.
+pub fn format_quiet(result: &BustResult, config: &ScanConfig) -> Option<String> {
+    match result {
+        BustResult::Success(resp) => Some(format!(
+            "{} {} {}",
+            resp.status,
+            resp.content_length
+                .map_or_else(|| "-".to_string(), |len| len.to_string()),
+            result_url(resp, config)
+        )),
+        _ => None,
+    }
+}
+
+/// Formats a failed request as a plain `ERROR <url> <kind>: <message>` line, written to
+/// stderr in quiet mode so failures are not dropped silently. Other results produce no line.
+pub fn format_quiet_error(result: &BustResult, config: &ScanConfig) -> Option<String> {
+    match result {
+        BustResult::Error(word, error, kind) => Some(format!(
+            "ERROR {} {kind}: {}",
+            redact(&request_url(word, config), &config.redact_patterns),
+            redact(error, &config.redact_patterns)
+        )),
+        _ => None,
+    }
+}
+
+/// Formats the request that would be sent for a word in dry-run mode: the URL followed by
+/// one indented line per header. Sensitive values such as `Authorization` are masked.
+pub fn format_planned_request(word: &str, config: &ScanConfig) -> String {
+    let mut headers = request_headers(config);
+    headers.extend(bypass_headers(word, config.header_inject_mode));
+
+    let mut lines = vec![format!(
+        "GET {}",
+        redact(&request_url(word, config), &config.redact_patterns)
+    )];
+    for (name, value) in &headers {
+        let value = if value.is_sensitive() {
+            "********"
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        lines.push(format!(
+            "    {name}: {}",
+            redact(value, &config.redact_patterns)
+        ));
+    }
+    lines.join("\n")
+}
+
+/// The longest header value printed with `-v` before it is shortened with `...`.
+const VERBOSE_HEADER_WIDTH: usize = 200;
+
+/// Formats a request about to be sent with `-v`: the method and URL followed by one
+/// indented line per header. Sensitive values are masked and long ones shortened.
+pub fn format_request_headers(
+    method: &str,
+    url: &str,
+    headers: &HeaderMap,
+    config: &ScanConfig,
+) -> String {
+    let first = format!("> {method} {}", redact(url, &config.redact_patterns));
+    header_lines(first, '>', headers, config)
+}
+
+/// Formats a received response with `-v`: the status line followed by one indented line
+/// per header. Sensitive values are masked and long ones shortened.
+pub fn format_response_headers(
+    version: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+    config: &ScanConfig,
+) -> String {
+    let first = format!("< {version} {status}");
+    header_lines(first, '<', headers, config)
+}
+
+/// Joins a first line with the header lines of a `-v` request or response dump.
+fn header_lines(first: String, marker: char, headers: &HeaderMap, config: &ScanConfig) -> String {
+    let mut lines = vec![first];
+    for (name, value) in headers {
+        let value = if value.is_sensitive() {
+            "********".to_string()
+        } else {
+            truncate(
+                &String::from_utf8_lossy(value.as_bytes()),
+                VERBOSE_HEADER_WIDTH,
             )
-        }
-        BustResult::Filtered(resp) => {
+        };
+        lines.push(format!(
+            "{marker}   {name}: {}",
+            redact(&value, &config.redact_patterns)
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Builds a report entry for reported responses, which belong in structured reports.
+fn report_entry(result: &BustResult, config: &ScanConfig) -> Option<ReportEntry> {
+    let resp = result.reported_response()?;
+    let length_mismatch = resp
+        .header_length
+        .zip(resp.body_length)
+        .filter(|(header, body)| header != body);
+    Some(ReportEntry {
+        word: redact(&resp.word, &config.redact_patterns),
+        status: resp.status,
+        content_length: resp.content_length,
+        response_time_ms: resp.response_time.as_millis() as u64,
+        response_time_us: resp.response_time.as_micros() as u64,
+        ttfb_us: resp
+            .body_read_time
+            .map(|_| resp.response_time.as_micros() as u64),
+        body_read_us: resp.body_read_time.map(|time| time.as_micros() as u64),
+        word_count: resp.word_count,
+        line_count: resp.line_count,
+        url: result_url(resp, config),
+        bypass_mode: resp.bypass_mode.map(|mode| mode.to_string()),
+        http_version: resp.probed_version.map(str::to_string),
+        negotiated_version: resp.http_version.map(str::to_string),
+        wildcard_confidence: resp
+            .wildcard_confidence
+            .map(|confidence| (confidence * 100.0).round() / 100.0),
+        redirect_location: match result {
+            BustResult::Redirect(_, location) => Some(redact(location, &config.redact_patterns)),
+            _ => None,
+        },
+        source: resp.source.map(str::to_string),
+        header_length: length_mismatch.map(|(header, _)| header),
+        body_length: length_mismatch.map(|(_, body)| body),
+        body_truncated: resp.body_truncated,
+        set_cookies: resp
+            .set_cookies
+            .iter()
+            .map(|cookie| redact(cookie, &config.redact_patterns))
+            .collect(),
+    })
+}
+
+/// Returns the full URL that was requested for a response.
+fn result_url(resp: &DetailedResponse, config: &ScanConfig) -> String {
+    let url = match resp.requested_url.as_str() {
+        "" => request_url(&resp.word, config),
+        url => url.to_string(),
+    };
+    redact(&url, &config.redact_patterns)
+}
+
+/// Shortens a string to at most `max_chars` characters, ending it with `...` if it was cut.
+pub fn truncate(s: &str, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        return s.to_string();
+    }
+    let kept: String = s.chars().take(max_chars.saturating_sub(3)).collect();
+    format!("{kept}...")
+}
+
+/// Replaces every match of the redact patterns in a string with `[REDACTED]`.
+pub fn redact(s: &str, patterns: &[Regex]) -> String {
+    patterns.iter().fold(s.to_string(), |redacted, pattern| {
+        pattern.replace_all(&redacted, "[REDACTED]").into_owned()
+    })
+}
+
+/// The header line written at the top of CSV output.
+const CSV_HEADER: &str =
+    "Word,Status,Content-Length,Response-Time-MS,Word-Count,URL,Wildcard-Confidence,Line-Count";
+
+/// The columns added to CSV output with `--timing-detail`.
+const CSV_TIMING_HEADER: &str = ",TTFB-US,Body-Read-US";
+
+/// Formats a result as a CSV row. Results without a response produce no row.
+fn csv_row(result: &BustResult, config: &ScanConfig) -> Option<String> {
+    result.reported_response().map(|resp| {
+        let timing = if config.timing_detail {
             format!(
-                "{word}: {status} {tag}",
-                word = resp.word.yellow().bold(),
-                status = resp.status.to_string().yellow(),
-                tag = "[FILTERED]".yellow().italic()
+                ",{},{}",
+                resp.response_time.as_micros(),
+                resp.body_read_time
+                    .map(|time| time.as_micros().to_string())
+                    .unwrap_or_default()
             )
+        } else {
+            String::new()
+        };
+        format!(
+            "{},{},{},{},{},{},{},{}{timing}\n",
+            redact(&resp.word, &config.redact_patterns),
+            resp.status,
+            resp.content_length.unwrap_or(0),
+            resp.response_time.as_millis(),
+            resp.word_count.unwrap_or(0),
+            result_url(resp, config),
+            resp.wildcard_confidence
+                .map(|confidence| format!("{confidence:.2}"))
+                .unwrap_or_default(),
+            resp.line_count
+                .map(|count| count.to_string())
+                .unwrap_or_default()
+        )
+    })
+}
+
+/// Escapes the characters that are special in XML text and attribute values.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
         }
     }
+    escaped
 }
 
-/// Saves the collected scan results to a file in the specified format.
-#[allow(clippy::too_many_arguments)] // TODO: refactor later by grouping args into a struct
-pub async fn save_results(
-    results: Arc<Mutex<Vec<BustResult>>>,
+/// The longest URL shown in a Markdown table before it is shortened with `...`.
+const MARKDOWN_URL_WIDTH: usize = 80;
+
+/// Formats a result as a row of the Markdown table. Results without a response produce no row.
+///
+/// 2xx statuses are shown as code so GitHub renders them without emphasis.
+fn markdown_row(result: &BustResult, config: &ScanConfig) -> Option<String> {
+    result.reported_response().map(|resp| {
+        let url = truncate(&result_url(resp, config), MARKDOWN_URL_WIDTH);
+        let status = if (200..300).contains(&resp.status) {
+            format!("`{}`", resp.status)
+        } else {
+            resp.status.to_string()
+        };
+        format!(
+            "| {} | {status} | {} | {}ms |\n",
+            url.replace('|', "\\|"),
+            resp.content_length
+                .map(|length| length.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            resp.response_time.as_millis()
+        )
+    })
+}
+
+/// The lines written before the results of a line-oriented format.
+///
+/// Both text and CSV start with a banner naming the scan ID, target and start time
+/// (a `#` comment line for CSV). When continuing an existing file, CSV omits the repeated column header.
+fn stream_header(
+    format: OutputFormat,
     config: &ScanConfig,
-    output_file: &str,
-    format: &str,
-    scan_duration: f64,
-    total_count: usize,
-    found_count: usize,
-    error_count: usize,
-    filtered_count: usize,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let unlocked_results = results.lock().await;
+    summary: &ScanSummary,
+    continuing: bool,
+) -> String {
+    let separator = if continuing { "\n" } else { "" };
+    let started = summary.start_time.to_rfc3339();
+    match format {
+        OutputFormat::Text => format!(
+            "{separator}=== dirbuster-rs scan {} of {} started at {started} ===\n",
+            config.scan_id,
+            redact(&config.base_url, &config.redact_patterns)
+        ),
+        OutputFormat::Csv => {
+            let mut header = format!(
+                "# dirbuster-rs scan {} of {} started at {started}\n",
+                config.scan_id,
+                redact(&config.base_url, &config.redact_patterns)
+            );
+            if !continuing {
+                header.push_str(CSV_HEADER);
+                if config.timing_detail {
+                    header.push_str(CSV_TIMING_HEADER);
+                }
+                header.push('\n');
+            }
+            header
+        }
+        _ => String::new(),
+    }
+}
 
+/// The line written after the results of a line-oriented format, once the scan is done.
+fn stream_trailer(format: OutputFormat, summary: &ScanSummary) -> String {
+    let ended = summary.end_time().to_rfc3339();
     match format {
-        "json" => {
-            let report_entries: Vec<ReportEntry> = unlocked_results
+        OutputFormat::Text => format!(
+            "=== scan finished at {ended} ({:.2}s) ===\n",
+            summary.duration
+        ),
+        OutputFormat::Csv => format!("# scan finished at {ended} ({:.2}s)\n", summary.duration),
+        _ => String::new(),
+    }
+}
+
+/// Counts a result in a histogram of status codes, if a response was received and reported.
+pub fn count_status(histogram: &mut BTreeMap<u16, usize>, result: &BustResult) {
+    if let Some(resp) = result.reported_response() {
+        *histogram.entry(resp.status).or_insert(0) += 1;
+    }
+}
+
+/// Counts the reported responses of each status code, in ascending order of status.
+pub fn status_code_histogram(results: &[BustResult]) -> BTreeMap<u16, usize> {
+    let mut histogram = BTreeMap::new();
+    for result in results {
+        count_status(&mut histogram, result);
+    }
+    histogram
+}
+
+/// Renders the full output document for the given results.
+pub fn render_results(
+    results: &[BustResult],
+    config: &ScanConfig,
+    format: OutputFormat,
+    summary: &ScanSummary,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let status_breakdown = status_code_histogram(results);
+    let results: Vec<&BustResult> = match config.sort_by {
+        Some(key) => sort_results(results, key, config.sort_desc),
+        None => results.iter().collect(),
+    };
+    // Snapshots of a running scan have no "finished" trailer yet
+    let trailer = if summary.finished {
+        stream_trailer(format, summary)
+    } else {
+        String::new()
+    };
+
+    let content = match format {
+        OutputFormat::Json => {
+            let report_entries: Vec<ReportEntry> = results
+                .iter()
+                .filter_map(|r| report_entry(r, config))
+                .collect();
+            let errors = results
                 .iter()
-                .filter_map(|r| match r {
-                    BustResult::Success(resp) | BustResult::NotFound(resp) => Some(ReportEntry {
-                        word: resp.word.clone(),
-                        status: resp.status,
-                        content_length: resp.content_length,
-                        response_time_ms: resp.response_time.as_millis() as u64,
-                        word_count: resp.word_count,
-                        url: format!("{}/{}", config.base_url.trim_end_matches('/'), resp.word),
+                .filter_map(|result| match result {
+                    BustResult::Error(word, error, kind) => Some(ReportError {
+                        word: redact(word, &config.redact_patterns),
+                        error: redact(error, &config.redact_patterns),
+                        kind: *kind,
                     }),
                     _ => None,
                 })
                 .collect();
 
             let report = ScanReport {
-                target: config.base_url.clone(),
-                start_time: chrono::Utc::now().to_rfc3339(),
-                end_time: chrono::Utc::now().to_rfc3339(),
-                duration: scan_duration,
-                total_requests: total_count,
-                success_count: found_count,
-                error_count,
-                filtered_count,
-                rate: total_count as f64 / scan_duration,
+                scan_id: config.scan_id.clone(),
+                target: redact(&config.base_url, &config.redact_patterns),
+                start_time: summary.start_time.to_rfc3339(),
+                end_time: summary.end_time().to_rfc3339(),
+                duration: summary.duration,
+                total_requests: summary.total_count,
+                success_count: summary.found_count,
+                error_count: summary.error_count,
+                error_kinds: summary.error_kinds.clone(),
+                filtered_count: summary.filtered_count,
+                rate: summary.total_count as f64 / summary.duration,
+                bytes_downloaded: summary.bytes_downloaded,
+                avg_bytes_per_response: (summary.avg_bytes_per_response() * 10.0).round() / 10.0,
+                content_length_bytes: summary.content_length_bytes,
+                insecure: config.insecure,
+                ca_cert: config.ca_cert.clone(),
+                bind_address: config.bind_address.map(|address| address.to_string()),
+                interface: config.bind_interface.clone(),
+                resolve: config
+                    .resolve_overrides
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                block_episodes: summary.block_episodes.clone(),
+                concurrency_changes: summary.concurrency_changes.clone(),
+                status_breakdown,
+                stats: summary.stats.clone().map(|mut stats| {
+                    for slow in &mut stats.slowest {
+                        slow.word = redact(&slow.word, &config.redact_patterns);
+                    }
+                    stats
+                }),
                 results: report_entries,
+                errors,
             };
 
-            let json_output = serde_json::to_string_pretty(&report)?;
-            write(output_file, json_output)?;
-        }
-        "csv" => {
-            let mut csv_content =
-                String::from("Word,Status,Content-Length,Response-Time-MS,Word-Count,URL\n");
-            for result in unlocked_results.iter() {
-                if let BustResult::Success(resp) | BustResult::NotFound(resp) = result {
-                    csv_content.push_str(&format!(
-                        "{},{},{},{},{},{}/{}\n",
-                        resp.word,
-                        resp.status,
-                        resp.content_length.unwrap_or(0),
-                        resp.response_time.as_millis(),
-                        resp.word_count.unwrap_or(0),
-                        config.base_url.trim_end_matches('/'),
-                        resp.word
-                    ));
-                }
+            serde_json::to_string_pretty(&report)?
+        }
+        OutputFormat::Csv => {
+            let mut csv_content = stream_header(format, config, summary, false);
+            for row in results.iter().filter_map(|r| csv_row(r, config)) {
+                csv_content.push_str(&row);
             }
-            write(output_file, csv_content)?;
+            csv_content.push_str(&trailer);
+            csv_content
         }
-        "xml" => {
-            let mut xml_content =
-                String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<scan_results>\n");
-            for result in unlocked_results.iter() {
-                if let BustResult::Success(resp) | BustResult::NotFound(resp) = result {
+        OutputFormat::Xml => {
+            let mut xml_content = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<scan_results scan_id=\"{}\" start_time=\"{}\" end_time=\"{}\">\n",
+                xml_escape(&config.scan_id),
+                summary.start_time.to_rfc3339(),
+                summary.end_time().to_rfc3339()
+            );
+            for result in results.iter() {
+                if let Some(resp) = result.reported_response() {
+                    // Lines are only counted on request, so the element is left out otherwise
+                    let line_count = resp
+                        .line_count
+                        .map(|count| format!("    <line_count>{count}</line_count>\n"))
+                        .unwrap_or_default();
                     xml_content.push_str(&format!(
-                        "  <result>\n    <word>{}</word>\n    <status>{}</status>\n    <content_length>{}</content_length>\n    <response_time_ms>{}</response_time_ms>\n    <url>{}/{}</url>\n  </result>\n",
-                        resp.word,
+                        "  <result>\n    <word>{}</word>\n    <status>{}</status>\n    <content_length>{}</content_length>\n    <response_time_ms>{}</response_time_ms>\n{line_count}    <url>{}</url>\n  </result>\n",
+                        xml_escape(&redact(&resp.word, &config.redact_patterns)),
                         resp.status,
                         resp.content_length.unwrap_or(0),
                         resp.response_time.as_millis(),
-                        config.base_url.trim_end_matches('/'),
-                        resp.word
+                        xml_escape(&result_url(resp, config))
                     ));
                 }
             }
             xml_content.push_str("</scan_results>\n");
-            write(output_file, xml_content)?;
+            xml_content
         }
-        _ => {
-            // Default to plain text format
-            let mut text_content = String::new();
-            for result in unlocked_results.iter() {
-                text_content.push_str(&format!("{}\n", format_output(result, config)));
+        OutputFormat::Markdown => {
+            let mut markdown =
+                String::from("| URL | Status | Size | Time |\n| --- | --- | --- | --- |\n");
+            for row in results.iter().filter_map(|r| markdown_row(r, config)) {
+                markdown.push_str(&row);
+            }
+            markdown.push_str(&format!(
+                "\n## Summary\n\n- **Scan ID:** {}\n- **Target:** {}\n- **Started:** {}\n",
+                config.scan_id,
+                redact(&config.base_url, &config.redact_patterns),
+                summary.start_time.to_rfc3339()
+            ));
+            if summary.finished {
+                markdown.push_str(&format!(
+                    "- **Finished:** {}\n",
+                    summary.end_time().to_rfc3339()
+                ));
             }
-            write(output_file, text_content)?;
+            markdown.push_str(&format!(
+                "- **Duration:** {:.2}s\n- **Requests:** {}\n- **Found:** {}\n- **Errors:** {}\n- **Filtered:** {}\n",
+                summary.duration,
+                summary.total_count,
+                summary.found_count,
+                summary.error_count,
+                summary.filtered_count
+            ));
+            markdown
+        }
+        OutputFormat::Text => {
+            let mut text_content = stream_header(format, config, summary, false);
+            for result in results.iter() {
+                text_content.push_str(&format!("{}\n", format_output_plain(result, config)));
+            }
+            text_content.push_str(&trailer);
+            text_content
+        }
+    };
+    Ok(content)
+}
+
+/// Saves the collected scan results to a file in the specified format.
+///
+/// The file is written to a temporary path and renamed into place, so readers never
+/// observe a half-written document.
+pub fn save_results(
+    results: &[BustResult],
+    config: &ScanConfig,
+    output_file: &str,
+    format: OutputFormat,
+    summary: &ScanSummary,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = render_results(results, config, format, summary)?;
+    let tmp_path = format!("{output_file}.tmp");
+    write(&tmp_path, content)?;
+    rename(&tmp_path, output_file)?;
+    Ok(())
+}
+
+/// A URL found in only one of two compared scans.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ComparedUrl {
+    pub url: String,
+    pub status: u16,
+}
+
+/// A URL found in both compared scans with a different status code.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct StatusChange {
+    pub url: String,
+    pub old_status: u16,
+    pub new_status: u16,
+}
+
+/// The differences between two JSON scan results, each list ordered by URL.
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct ComparisonReport {
+    /// URLs only in the second file.
+    pub new: Vec<ComparedUrl>,
+    /// URLs only in the first file.
+    pub removed: Vec<ComparedUrl>,
+    pub changed: Vec<StatusChange>,
+}
+
+/// The part of a saved JSON report needed to compare it with another.
+#[derive(serde::Deserialize)]
+struct SavedReport {
+    results: Vec<SavedEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct SavedEntry {
+    url: String,
+    status: u16,
+}
+
+/// Reads the URLs and their status codes from a JSON result file.
+fn read_report_statuses(path: &str) -> std::io::Result<BTreeMap<String, u16>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("cannot read {path}: {e}")))?;
+    let report: SavedReport = serde_json::from_str(&content).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{path} is not a JSON scan result: {e}"),
+        )
+    })?;
+    Ok(report
+        .results
+        .into_iter()
+        .map(|entry| (entry.url, entry.status))
+        .collect())
+}
+
+/// Compares two JSON scan results: URLs new in `file2`, URLs removed since `file1`, and
+/// URLs whose status code changed.
+pub fn compare_results(file1: &str, file2: &str) -> std::io::Result<ComparisonReport> {
+    let before = read_report_statuses(file1)?;
+    let after = read_report_statuses(file2)?;
+    let mut report = ComparisonReport::default();
+
+    for (url, &status) in &after {
+        match before.get(url) {
+            None => report.new.push(ComparedUrl {
+                url: url.clone(),
+                status,
+            }),
+            Some(&old_status) if old_status != status => report.changed.push(StatusChange {
+                url: url.clone(),
+                old_status,
+                new_status: status,
+            }),
+            Some(_) => {}
+        }
+    }
+    for (url, &status) in &before {
+        if !after.contains_key(url) {
+            report.removed.push(ComparedUrl {
+                url: url.clone(),
+                status,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Formats a comparison for the console: new URLs in green, removed ones in red and
+/// changed status codes in yellow, followed by the counts.
+pub fn format_comparison(report: &ComparisonReport) -> String {
+    let mut lines = Vec::new();
+    for entry in &report.new {
+        lines.push(
+            format!("+ {} [{}]", entry.url, entry.status)
+                .green()
+                .to_string(),
+        );
+    }
+    for entry in &report.removed {
+        lines.push(
+            format!("- {} [{}]", entry.url, entry.status)
+                .red()
+                .to_string(),
+        );
+    }
+    for change in &report.changed {
+        lines.push(
+            format!(
+                "~ {} [{} -> {}]",
+                change.url, change.old_status, change.new_status
+            )
+            .yellow()
+            .to_string(),
+        );
+    }
+    lines.push(format!(
+        "{} new, {} removed, {} changed",
+        report.new.len(),
+        report.removed.len(),
+        report.changed.len()
+    ));
+    lines.join("\n")
+}
+
+/// Messages sent from the scan loop to the output writer task.
+enum WriterMessage {
+    Result(Box<BustResult>),
+    Finish(Box<ScanSummary>),
+}
+
+/// Writes results to the output file incrementally while the scan is running.
+///
+/// Results are sent over a channel to a background task. Line-oriented formats (text, CSV)
+/// are appended to the file as they arrive; structured formats (JSON, XML) keep the report
+/// entries in memory and periodically rewrite the file as a complete, valid document.
+/// Either way the file is flushed every few seconds, so an interrupted scan still leaves
+/// the results gathered so far on disk. Without an output file only the error log is
+/// written.
+pub struct OutputWriter {
+    tx: mpsc::UnboundedSender<WriterMessage>,
+    handle: JoinHandle<Result<(), String>>,
+}
+
+impl OutputWriter {
+    /// Creates (or, when appending, opens) the output file and spawns the writer task.
+    ///
+    /// Line-oriented output starts with a banner line naming the target and start time;
+    /// appended CSV output continues the existing rows without repeating the header.
+    pub fn spawn(
+        config: Arc<ScanConfig>,
+        output_file: Option<String>,
+        format: OutputFormat,
+        append: bool,
+        start_time: DateTime<Utc>,
+    ) -> std::io::Result<Self> {
+        let summary = ScanSummary {
+            start_time,
+            ..Default::default()
+        };
+
+        let file = match &output_file {
+            Some(path) if !format.is_snapshot(&config) => {
+                let continuing = append
+                    && std::fs::metadata(path)
+                        .map(|m| m.len() > 0)
+                        .unwrap_or(false);
+                let file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(append)
+                    .truncate(!append)
+                    .open(path)?;
+                let mut file = BufWriter::new(file);
+                file.write_all(stream_header(format, &config, &summary, continuing).as_bytes())?;
+                Some(file)
+            }
+            _ => None,
+        };
+
+        let error_log = match &config.error_log {
+            Some(path) => Some(BufWriter::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(async move {
+            run_writer(rx, file, error_log, config, output_file, format, summary)
+                .await
+                .map_err(|e| e.to_string())
+        });
+
+        Ok(Self { tx, handle })
+    }
+
+    /// Queues a result to be written.
+    pub fn send(&self, result: BustResult) {
+        // The writer only stops early if it failed, which `finish` reports.
+        let _ = self.tx.send(WriterMessage::Result(Box::new(result)));
+    }
+
+    /// Writes the final summary, flushes and closes the file.
+    pub async fn finish(self, summary: ScanSummary) -> Result<(), Box<dyn std::error::Error>> {
+        let _ = self.tx.send(WriterMessage::Finish(Box::new(summary)));
+        drop(self.tx);
+        self.handle.await??;
+        Ok(())
+    }
+}
+
+/// The body of the output writer task.
+async fn run_writer(
+    mut rx: mpsc::UnboundedReceiver<WriterMessage>,
+    mut file: Option<BufWriter<File>>,
+    mut error_log: Option<BufWriter<File>>,
+    config: Arc<ScanConfig>,
+    output_file: Option<String>,
+    format: OutputFormat,
+    mut summary: ScanSummary,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let mut results: Vec<BustResult> = Vec::new();
+    let mut dirty = false;
+
+    let mut ticker = interval(FLUSH_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some(WriterMessage::Result(result)) => {
+                    summary.record(&result);
+                    // Failed requests also go to the error log, or only there
+                    if let (Some(log), BustResult::Error(word, error, kind)) =
+                        (error_log.as_mut(), &*result)
+                    {
+                        let entry = ErrorLogEntry {
+                            timestamp: Utc::now().to_rfc3339(),
+                            word: redact(word, &config.redact_patterns),
+                            error: redact(error, &config.redact_patterns),
+                            kind: *kind,
+                        };
+                        writeln!(log, "{}", serde_json::to_string(&entry)?)?;
+                        dirty = true;
+                        if config.omit_errors_from_output {
+                            continue;
+                        }
+                    }
+                    match file.as_mut() {
+                        Some(file) => {
+                            let line = match format {
+                                OutputFormat::Csv => csv_row(&result, &config),
+                                _ => Some(format!("{}\n", format_output_plain(&result, &config))),
+                            };
+                            if let Some(line) = line {
+                                file.write_all(line.as_bytes())?;
+                            }
+                        }
+                        None if output_file.is_none() => {}
+                        None => {
+                            // Plain text lists every result, JSON also failed requests and
+                            // the other formats only responses
+                            let listed = match format {
+                                OutputFormat::Text => true,
+                                OutputFormat::Json => result.response().is_none() || report_entry(&result, &config).is_some(),
+                                _ => report_entry(&result, &config).is_some(),
+                            };
+                            if listed {
+                                results.push(*result);
+                            }
+                        }
+                    }
+                    dirty = true;
+                }
+                Some(WriterMessage::Finish(final_summary)) => {
+                    summary = *final_summary;
+                    break;
+                }
+                None => {
+                    summary.duration = start.elapsed().as_secs_f64();
+                    break;
+                }
+            },
+            _ = ticker.tick() => {
+                if dirty {
+                    summary.duration = start.elapsed().as_secs_f64();
+                    if let Some(log) = error_log.as_mut() {
+                        log.flush()?;
+                    }
+                    match (file.as_mut(), &output_file) {
+                        (Some(file), _) => file.flush()?,
+                        (None, Some(path)) => save_results(&results, &config, path, format, &summary)?,
+                        (None, None) => {}
+                    }
+                    dirty = false;
+                }
+            }
+        }
+    }
+
+    if let Some(log) = error_log.as_mut() {
+        log.flush()?;
+    }
+    match (file.as_mut(), &output_file) {
+        (Some(file), _) => {
+            file.write_all(stream_trailer(format, &summary).as_bytes())?;
+            file.flush()?;
         }
+        (None, Some(path)) => save_results(&results, &config, path, format, &summary)?,
+        (None, None) => {}
     }
     Ok(())
 }
@@ -1,23 +1,474 @@
 //! This module contains various parsing functions used throughout the application.
-//! It handles parsing of wordlists, user-agent files, custom headers, and filter strings.
+//! It handles parsing of wordlists, user-agent files, custom headers, and filter strings,
+//! as well as encoding words before they are appended to the URL path.
 
-use crate::buster::{DetailedResponse, ScanConfig};
-use std::collections::HashMap;
+use crate::buster::{DetailedResponse, RetryPolicy, ScanConfig};
+use crate::output::ReportSelection;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
+use once_cell::sync::Lazy;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use regex::Regex;
+use sha1::Sha1;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
+
+/// A Unicode normalization form applied to words before encoding.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnicodeForm {
+    /// Canonical composition, e.g. `é` as a single code point.
+    Nfc,
+    /// Canonical decomposition, e.g. `é` as `e` followed by a combining accent.
+    Nfd,
+}
+
+/// Describes how a word is transformed before being appended to the URL path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeMode {
+    /// How many times the word is percent-encoded: 0 leaves it as-is, 2 double-encodes (`%25XX`).
+    pub percent_passes: u8,
+    /// The Unicode normalization applied before percent-encoding, if any.
+    pub unicode_form: Option<UnicodeForm>,
+}
+
+/// Encodes a word for use in a URL path according to the given mode.
+///
+/// Normalization runs first, then every non-alphanumeric byte is percent-encoded
+/// once per pass. A second pass only re-encodes the `%` signs of the first.
+pub fn encode_word(word: &str, mode: EncodeMode) -> String {
+    let mut encoded = match mode.unicode_form {
+        Some(UnicodeForm::Nfc) => word.nfc().collect(),
+        Some(UnicodeForm::Nfd) => word.nfd().collect(),
+        None => word.to_string(),
+    };
+    for _ in 0..mode.percent_passes {
+        encoded = utf8_percent_encode(&encoded, NON_ALPHANUMERIC).to_string();
+    }
+    encoded
+}
 
 /// Parses a wordlist file into a vector of strings.
 ///
 /// Each line in the file is treated as a separate word. Empty lines are ignored.
-pub fn parse_word_list(wl_arg: &str) -> Result<Vec<String>, std::io::Error> {
+/// With `strip_comments`, lines starting with `#` are skipped and anything after ` # ` is
+/// cut off, while a `#` inside a word (e.g. `page#top`) is kept.
+pub fn parse_word_list(wl_arg: &str, strip_comments: bool) -> Result<Vec<String>, std::io::Error> {
     let content = read_to_string(wl_arg)?;
     let words: Vec<String> = content
         .lines()
+        .map(|line| {
+            if strip_comments {
+                strip_comment(line)
+            } else {
+                line
+            }
+        })
         .map(|line| line.trim().to_string())
         .filter(|line| !line.is_empty())
         .collect();
     Ok(words)
 }
 
+/// Removes a wordlist comment from a line, leaving an empty line if it is all comment.
+fn strip_comment(line: &str) -> &str {
+    if line.trim_start().starts_with('#') {
+        return "";
+    }
+    line.split(" # ").next().unwrap_or_default()
+}
+
+/// Reads several wordlist files and concatenates their words in the order given.
+///
+/// An error names the file that could not be read.
+pub fn merge_word_lists(
+    paths: &[String],
+    strip_comments: bool,
+) -> Result<Vec<String>, std::io::Error> {
+    let mut words = Vec::new();
+    for path in paths {
+        let list = parse_word_list(path, strip_comments)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{path}: {e}")))?;
+        words.extend(list);
+    }
+    Ok(words)
+}
+
+/// Removes repeated words, keeping the first occurrence of each in its place.
+pub fn dedup_words(words: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    words
+        .into_iter()
+        .filter(|word| seen.insert(word.clone()))
+        .collect()
+}
+
+/// A transformation applied natively to every word of the list with
+/// `--wordlist-transform-builtin`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordTransform {
+    /// Standard base64 with padding, e.g. `admin` as `YWRtaW4=`.
+    Base64,
+    /// The lowercase hex MD5 digest.
+    Md5,
+    /// The lowercase hex SHA-1 digest.
+    Sha1,
+    /// The characters in reverse order.
+    Reverse,
+}
+
+/// Applies a built-in transformation to a word.
+pub fn transform_word(word: &str, transform: WordTransform) -> String {
+    match transform {
+        WordTransform::Base64 => BASE64_STANDARD.encode(word),
+        WordTransform::Md5 => format!("{:x}", Md5::digest(word)),
+        WordTransform::Sha1 => format!("{:x}", Sha1::digest(word)),
+        WordTransform::Reverse => word.chars().rev().collect(),
+    }
+}
+
+/// Pipes the words, one per line, through a shell command and reads the transformed words
+/// back from its standard output.
+///
+/// The command may print more or fewer words than it was given, e.g. to filter or expand
+/// them; empty output lines are skipped. It fails if the command exits unsuccessfully.
+pub fn run_word_transform(command: &str, words: &[String]) -> Result<Vec<String>, String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("--wordlist-transform: cannot run `{command}`: {e}"))?;
+
+    // Written from another thread, so a command printing as it reads cannot block on a full pipe
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input: String = words.iter().map(|word| format!("{word}\n")).collect();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("--wordlist-transform: cannot read the output of `{command}`: {e}"))?;
+    // A command that does not read all of its input closes the pipe early, which is fine
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(format!(
+            "--wordlist-transform: `{command}` failed with {}",
+            output.status
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// A pre-compiled regex matching CDATA sections, whose content may contain markup.
+static CDATA_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!\[CDATA\[(.*?)\]\]>").unwrap());
+
+/// A pre-compiled regex matching a Burp `<item>` element.
+static BURP_ITEM_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<item>(.*?)</item>").unwrap());
+
+/// A pre-compiled regex matching the `<url>` and `<path>` children of a Burp item.
+static BURP_FIELD_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<(url|path)>([^<]*)</(?:url|path)>").unwrap());
+
+/// Reads the paths of a Burp Suite XML export ("Save items") to use as the word list.
+///
+/// Works with Community and Professional exports, with or without CDATA-wrapped values.
+/// Only items under one of the `base_urls`, the candidates for the target, are kept: other
+/// hosts and paths outside the base path are skipped. The base path, query string and
+/// leading slash are stripped, and duplicates removed.
+pub fn parse_burp_xml(path: &str, base_urls: &[String]) -> Result<Vec<String>, std::io::Error> {
+    let bases = base_urls
+        .iter()
+        .map(|url| reqwest::Url::parse(url))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // The candidates only differ in their scheme
+    let base_path = bases
+        .first()
+        .map_or("", |base| base.path())
+        .trim_end_matches('/');
+
+    // Escape CDATA content first, so markup inside recorded bodies cannot look like elements
+    let content = read_to_string(path)?;
+    let content = CDATA_RE.replace_all(&content, |caps: &regex::Captures| {
+        caps[1]
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    });
+
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for item in BURP_ITEM_RE.captures_iter(&content) {
+        let mut item_url = None;
+        let mut item_path = None;
+        for field in BURP_FIELD_RE.captures_iter(&item[1]) {
+            let value = unescape_xml(field[2].trim());
+            match &field[1] {
+                "url" => item_url = item_url.or(Some(value)),
+                _ => item_path = item_path.or(Some(value)),
+            }
+        }
+
+        if let Some(url) = item_url
+            .as_deref()
+            .and_then(|url| reqwest::Url::parse(url).ok())
+            && !bases.iter().any(|base| base.origin() == url.origin())
+        {
+            continue;
+        }
+        let Some(item_path) = item_path else {
+            continue;
+        };
+        let item_path = item_path.split(['?', '#']).next().unwrap_or_default();
+        let Some(relative) = item_path.strip_prefix(base_path) else {
+            continue;
+        };
+        if !relative.is_empty() && !relative.starts_with('/') {
+            continue;
+        }
+
+        let word = relative.trim_start_matches('/');
+        if !word.is_empty() && seen.insert(word.to_string()) {
+            words.push(word.to_string());
+        }
+    }
+    Ok(words)
+}
+
+/// Reads the words an ffuf JSON output file (`-of json`) recorded results for.
+///
+/// The word is the `FUZZ` input of each entry in `results`, or the only other input when
+/// a custom keyword was used. Duplicates are removed.
+pub fn parse_ffuf_json(path: &str) -> Result<Vec<String>, std::io::Error> {
+    let content = read_to_string(path)?;
+    let report: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let results = report["results"].as_array().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "no \"results\" array in ffuf output",
+        )
+    })?;
+
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for result in results {
+        let Some(input) = result["input"].as_object() else {
+            continue;
+        };
+        let word = input.get("FUZZ").or_else(|| {
+            input
+                .iter()
+                .find(|(keyword, _)| keyword.as_str() != "FFUFHASH")
+                .map(|(_, value)| value)
+        });
+        if let Some(word) = word.and_then(|word| word.as_str())
+            && seen.insert(word.to_string())
+        {
+            words.push(word.to_string());
+        }
+    }
+    Ok(words)
+}
+
+/// Reads the paths found in gobuster `dir` output, saved with `-o` or from the console.
+///
+/// Result lines look like `/admin (Status: 301) [Size: 178]`, or start with the full URL
+/// when gobuster ran with `-e`. Banner and progress lines are ignored.
+pub fn parse_gobuster_output(path: &str) -> Result<Vec<String>, std::io::Error> {
+    let content = read_to_string(path)?;
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for line in content.lines().filter(|line| line.contains("(Status:")) {
+        let Some(found) = line.split_whitespace().next() else {
+            continue;
+        };
+        let found_path = match reqwest::Url::parse(found) {
+            Ok(url) => url.path().to_string(),
+            Err(_) => found.to_string(),
+        };
+        let word = found_path.trim_start_matches('/');
+        if !word.is_empty() && seen.insert(word.to_string()) {
+            words.push(word.to_string());
+        }
+    }
+    Ok(words)
+}
+
+/// Reads the paths of the `Disallow`, `Allow` and `Sitemap` lines of a robots.txt file.
+///
+/// Comments and empty rules are ignored. A rule with a `*` or `$` is cut before the first
+/// one, so `/private*.php` becomes `private`, and sitemaps given as full URLs give their path.
+pub fn parse_robots_txt(content: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let Some((field, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let rule_path = match field.trim().to_ascii_lowercase().as_str() {
+            "disallow" | "allow" => value.to_string(),
+            "sitemap" => match reqwest::Url::parse(value) {
+                Ok(url) => url.path().to_string(),
+                Err(_) => value.to_string(),
+            },
+            _ => continue,
+        };
+        let end = rule_path.find(['*', '$']).unwrap_or(rule_path.len());
+        let word = rule_path[..end].trim_start_matches('/');
+        if !word.is_empty() && seen.insert(word.to_string()) {
+            words.push(word.to_string());
+        }
+    }
+    words
+}
+
+/// A pre-compiled regex matching a sitemap `<loc>` element, with or without CDATA.
+static SITEMAP_LOC_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<loc>\s*(?:<!\[CDATA\[)?(.*?)(?:\]\]>)?\s*</loc>").unwrap());
+
+/// The URLs listed in a sitemap file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sitemap {
+    /// A sitemap index, listing further sitemaps.
+    Index(Vec<String>),
+    /// A URL set, listing pages.
+    Urls(Vec<String>),
+}
+
+/// The largest decompressed sitemap read, the 50 MiB the sitemaps protocol allows.
+pub const MAX_SITEMAP_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Parses a sitemap or sitemap index, decompressing it first if it is gzipped.
+///
+/// A gzipped sitemap that decompresses to more than `MAX_SITEMAP_SIZE` is rejected, so a
+/// small download cannot fill the memory.
+pub fn parse_sitemap(body: &[u8]) -> Result<Sitemap, String> {
+    let content = if body.starts_with(&[0x1f, 0x8b]) {
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(body)
+            .take(MAX_SITEMAP_SIZE + 1)
+            .read_to_string(&mut content)
+            .map_err(|e| format!("cannot decompress the sitemap: {e}"))?;
+        if content.len() as u64 > MAX_SITEMAP_SIZE {
+            return Err(format!(
+                "the sitemap decompresses to more than {} MiB",
+                MAX_SITEMAP_SIZE >> 20
+            ));
+        }
+        content
+    } else {
+        String::from_utf8_lossy(body).into_owned()
+    };
+    let locs = SITEMAP_LOC_RE
+        .captures_iter(&content)
+        .map(|caps| unescape_xml(caps[1].trim()))
+        .collect();
+    if content.contains("<sitemapindex") {
+        Ok(Sitemap::Index(locs))
+    } else if content.contains("<urlset") {
+        Ok(Sitemap::Urls(locs))
+    } else {
+        Err("no <urlset> or <sitemapindex> element".to_string())
+    }
+}
+
+/// Turns absolute URLs, e.g. from a sitemap, into words to scan under `base_url`.
+///
+/// URLs on other hosts or outside the base path are skipped; the base path only matches
+/// whole segments. The base path, query string and leading slash are stripped, and
+/// duplicates removed.
+pub fn url_words(urls: &[String], base_url: &str) -> Vec<String> {
+    let Ok(base) = reqwest::Url::parse(base_url) else {
+        return Vec::new();
+    };
+    let base_path = base.path().trim_end_matches('/');
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for url in urls.iter().filter_map(|url| reqwest::Url::parse(url).ok()) {
+        if url.host_str() != base.host_str() {
+            continue;
+        }
+        let Some(relative) = url.path().strip_prefix(base_path) else {
+            continue;
+        };
+        if !relative.is_empty() && !relative.starts_with('/') {
+            continue;
+        }
+        let word = relative.trim_start_matches('/');
+        if !word.is_empty() && seen.insert(word.to_string()) {
+            words.push(word.to_string());
+        }
+    }
+    words
+}
+
+/// Replaces the predefined XML entities with the characters they stand for.
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Removes words that start with any of the given prefixes or fully match the regex.
+///
+/// This runs on the raw word list, so skipped words never produce a request.
+pub fn filter_word_list(
+    words: Vec<String>,
+    skip_prefixes: &[String],
+    skip_regex: Option<&Regex>,
+) -> Vec<String> {
+    words
+        .into_iter()
+        .filter(|word| {
+            !skip_prefixes
+                .iter()
+                .any(|prefix| word.starts_with(prefix.as_str()))
+        })
+        .filter(|word| !skip_regex.is_some_and(|regex| regex.is_match(word)))
+        .collect()
+}
+
+/// Removes words shorter than `min` or longer than `max` characters, counting Unicode
+/// characters rather than bytes. Returns the kept words and how many were removed.
+pub fn filter_word_lengths(
+    words: Vec<String>,
+    min: Option<usize>,
+    max: Option<usize>,
+) -> (Vec<String>, usize) {
+    let before = words.len();
+    let kept: Vec<String> = words
+        .into_iter()
+        .filter(|word| {
+            let length = word.chars().count();
+            min.is_none_or(|min| length >= min) && max.is_none_or(|max| length <= max)
+        })
+        .collect();
+    let removed = before - kept.len();
+    (kept, removed)
+}
+
+/// Compiles a word skip pattern so that it must match the whole word.
+pub fn parse_skip_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{pattern})$"))
+}
+
 /// Parses a user-agents file into a vector of strings.
 ///
 /// If the provided path is empty, it returns a default list of common user agents.
@@ -56,6 +507,255 @@ pub fn parse_custom_headers(headers: &[String]) -> HashMap<String, String> {
     header_map
 }
 
+/// The layout of a `--header-file`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderFileFormat {
+    /// One `Key: Value` header per line.
+    #[default]
+    Plain,
+    /// A raw HTTP request copied from Burp: a request line, then the headers.
+    Burp,
+}
+
+/// Reads a file of custom headers, one `Key: Value` per line like `--headers`.
+///
+/// Blank lines, `#` comments and lines without a colon are skipped.
+pub fn parse_headers_file(path: &str) -> Result<HashMap<String, String>, std::io::Error> {
+    let content = read_to_string(path)?;
+    let lines: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    Ok(parse_custom_headers(&lines))
+}
+
+/// The headers of a captured request that describe that request or its connection rather
+/// than the scan: the host, the body, the encodings the client could decode and the
+/// hop-by-hop headers.
+const BURP_SKIPPED_HEADERS: [&str; 11] = [
+    "Host",
+    "Content-Length",
+    "Accept-Encoding",
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Connection",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+/// Reads the headers of a raw HTTP request captured with Burp.
+///
+/// The request line is skipped, and reading stops at the blank line before the body.
+/// The headers in `BURP_SKIPPED_HEADERS` are left out, so the client sets its own.
+pub fn parse_burp_headers_file(path: &str) -> Result<HashMap<String, String>, std::io::Error> {
+    let content = read_to_string(path)?;
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty());
+    let mut headers = Vec::new();
+    if let Some(first) = lines.next()
+        && !first.contains(" HTTP/")
+    {
+        headers.push(first.to_string());
+    }
+    headers.extend(
+        lines
+            .take_while(|line| !line.is_empty())
+            .map(str::to_string),
+    );
+    let mut headers = parse_custom_headers(&headers);
+    headers.retain(|key, _| {
+        !BURP_SKIPPED_HEADERS
+            .iter()
+            .any(|skipped| key.eq_ignore_ascii_case(skipped))
+    });
+    Ok(headers)
+}
+
+/// Resolves a secret value given as `env:NAME` or `file:/path`; other values are returned as is.
+///
+/// File contents are used without their trailing newline. Errors name the `option` the value
+/// was given for, so a missing variable or unreadable file is easy to track down.
+pub fn resolve_secret(option: &str, value: &str) -> Result<String, String> {
+    if let Some(name) = value.strip_prefix("env:") {
+        std::env::var(name)
+            .map_err(|e| format!("{option}: cannot read environment variable {name}: {e}"))
+    } else if let Some(path) = value.strip_prefix("file:") {
+        read_to_string(path)
+            .map(|content| content.trim_end_matches(['\r', '\n']).to_string())
+            .map_err(|e| format!("{option}: cannot read secret file {path}: {e}"))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Resolves `env:`/`file:` references in the values of "key:value" header strings.
+pub fn resolve_header_secrets(headers: &[String]) -> Result<Vec<String>, String> {
+    headers
+        .iter()
+        .map(|header| match header.split_once(':') {
+            Some((key, value)) => {
+                let value = resolve_secret(&format!("--headers {}", key.trim()), value.trim())?;
+                Ok(format!("{key}:{value}"))
+            }
+            None => Ok(header.clone()),
+        })
+        .collect()
+}
+
+/// Parses a `--rate` value, which must be a positive number of requests per second.
+pub fn parse_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        // The slot spacing `1 / rate` has to fit in a `Duration`
+        Ok(rate) if rate.is_finite() && Duration::try_from_secs_f64(1.0 / rate).is_ok() => Ok(rate),
+        _ => Err(format!(
+            "`{value}` is not a positive number of requests per second"
+        )),
+    }
+}
+
+/// Parses a `--retry-on` list such as `429,500-599,conn` into a retry policy.
+///
+/// Items are status codes, inclusive status ranges, or `conn`, `timeout` and `dns` for the
+/// corresponding network errors. Network errors are only retried when listed.
+pub fn parse_retry_policy(value: &str) -> Result<RetryPolicy, String> {
+    let mut policy = RetryPolicy {
+        statuses: Vec::new(),
+        conn: false,
+        timeout: false,
+        dns: false,
+    };
+    for item in value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        match item {
+            "conn" => policy.conn = true,
+            "timeout" => policy.timeout = true,
+            "dns" => policy.dns = true,
+            _ => {
+                let range = match item.split_once('-') {
+                    Some((min, max)) => min.parse().ok().zip(max.parse().ok()),
+                    None => item.parse().ok().map(|status| (status, status)),
+                };
+                match range {
+                    Some((min, max)) if min <= max => policy.statuses.push((min, max)),
+                    _ => {
+                        return Err(format!(
+                            "`{item}` is not a status code, status range or one of conn, timeout, dns"
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    Ok(policy)
+}
+
+/// Parses a `--select` value: `all`, `found`, `errors`, or `status:` followed by a status
+/// code or an inclusive range such as `status:400-499`.
+pub fn parse_report_selection(value: &str) -> Result<ReportSelection, String> {
+    let status_range = |codes: &str| match codes.split_once('-') {
+        Some((min, max)) => min.parse().ok().zip(max.parse().ok()),
+        None => codes.parse().ok().map(|status| (status, status)),
+    };
+    match value {
+        "all" => Ok(ReportSelection::All),
+        "found" => Ok(ReportSelection::Found),
+        "errors" => Ok(ReportSelection::Errors),
+        _ => match value.strip_prefix("status:").and_then(status_range) {
+            Some((min, max)) if min <= max => Ok(ReportSelection::Status(min, max)),
+            _ => Err(format!(
+                "`{value}` is not one of all, found, errors or status:<code>[-<code>]"
+            )),
+        },
+    }
+}
+
+/// Expands the `--accept` shortcuts `json`, `xml` and `all` into MIME types.
+/// Any other value is used as given.
+pub fn expand_accept(value: &str) -> String {
+    match value {
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "all" => "*/*",
+        mime => mime,
+    }
+    .to_string()
+}
+
+/// Parses a `Retry-After` header value into the time to wait from `now`.
+///
+/// Both the delta-seconds form (`120`) and the HTTP-date form
+/// (`Wed, 21 Oct 2015 07:28:00 GMT`) are accepted; a date in the past means no wait.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Parses an `X-RateLimit-Reset` header value into the time left until the limit resets.
+///
+/// Servers send either the seconds left or a Unix timestamp in seconds. Values too large to
+/// be a countdown are read as a timestamp, and one in the past means no wait.
+pub fn parse_rate_limit_reset(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let seconds = value.trim().parse::<u64>().ok()?;
+    // No rate limit window lasts the 31 years it takes to count down to a 2001 timestamp
+    if seconds < 1_000_000_000 {
+        return Some(Duration::from_secs(seconds));
+    }
+    let now = now.timestamp().max(0) as u64;
+    Some(Duration::from_secs(seconds.saturating_sub(now)))
+}
+
+/// Extracts identifier-like words from a response body, in order of first appearance.
+///
+/// The body is split on characters other than ASCII letters, digits and hyphens. Tokens of
+/// 3 to 20 characters are kept when they start with a lowercase letter and are lowercase,
+/// camelCase, or lowercase words joined by single hyphens.
+pub fn extract_words_from_body(body: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    body.split(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .map(|token| token.trim_matches('-'))
+        .filter(|token| is_identifier(token))
+        .filter(|token| seen.insert(*token))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns true if a token looks like a lowercase, camelCase or hyphenated identifier.
+fn is_identifier(token: &str) -> bool {
+    let hyphenated = token.contains('-');
+    (3..=20).contains(&token.len())
+        && token.starts_with(|c: char| c.is_ascii_lowercase())
+        && !token.contains("--")
+        && !(hyphenated && token.contains(|c: char| c.is_ascii_uppercase()))
+}
+
+/// Returns true if the body contains an opening `<tag` element, ignoring case.
+/// `<scripts>` does not count as a `script` tag.
+pub fn body_contains_tag(body: &str, tag: &str) -> bool {
+    let body = body.to_ascii_lowercase();
+    let needle = format!("<{}", tag.to_ascii_lowercase());
+    body.match_indices(&needle).any(|(start, _)| {
+        !body[start + needle.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
 /// Parses a size filter string (e.g., "100-500" or "404") into a min/max tuple.
 pub fn parse_size_filter(filter: &str) -> Option<(u64, u64)> {
     if let Some((min, max)) = filter.split_once('-') {
@@ -68,6 +768,119 @@ pub fn parse_size_filter(filter: &str) -> Option<(u64, u64)> {
     }
 }
 
+/// A curl-style `--resolve` entry pointing a host at fixed addresses instead of its DNS records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<IpAddr>,
+}
+
+impl std::fmt::Display for ResolveOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let addrs: Vec<String> = self
+            .addrs
+            .iter()
+            .map(|addr| match addr {
+                IpAddr::V6(addr) => format!("[{addr}]"),
+                IpAddr::V4(addr) => addr.to_string(),
+            })
+            .collect();
+        write!(f, "{}:{}:{}", self.host, self.port, addrs.join(","))
+    }
+}
+
+/// Parses a `--resolve` entry such as `example.com:443:10.0.0.5,10.0.0.6`.
+///
+/// IPv6 addresses are given in brackets, e.g. `example.com:443:[2001:db8::1]`.
+pub fn parse_resolve(value: &str) -> Result<ResolveOverride, String> {
+    let invalid = |reason: &str| format!("`{value}` is not HOST:PORT:ADDR[,ADDR...]: {reason}");
+    let mut parts = value.splitn(3, ':');
+    let (Some(host), Some(port), Some(addrs)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("missing fields"));
+    };
+    if host.is_empty() {
+        return Err(invalid("empty host"));
+    }
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| invalid(&format!("bad port `{port}`")))?;
+    let addrs = addrs
+        .split(',')
+        .map(|addr| {
+            addr.trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map_err(|_| invalid(&format!("bad address `{addr}`")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ResolveOverride {
+        host: host.to_ascii_lowercase(),
+        port,
+        addrs,
+    })
+}
+
+/// Parses a `--filter-size-tolerance` value, a percentage from 0 to 100.
+pub fn parse_tolerance(value: &str) -> Result<f32, String> {
+    match value.trim_end_matches('%').parse::<f32>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("`{value}` is not a percentage from 0 to 100")),
+    }
+}
+
+/// Parses a `--padding-body-size` range of bytes, e.g. `10-50`, or a single size.
+pub fn parse_padding_size(value: &str) -> Result<(usize, usize), String> {
+    let parse = |bytes: &str| bytes.trim().parse::<usize>().ok();
+    let range = match value.split_once('-') {
+        Some((min, max)) => parse(min).zip(parse(max)),
+        None => parse(value).map(|size| (size, size)),
+    };
+    match range {
+        Some((min, max)) if min <= max => Ok((min, max)),
+        _ => Err(format!("`{value}` is not a byte range like 10-50")),
+    }
+}
+
+/// Parses a `--max-bytes` budget: a positive number of bytes with an optional `K`, `M` or
+/// `G` suffix in powers of 1024, e.g. `500M`.
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (digits, multiplier) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 1 << 10),
+        Some((i, 'M')) => (&digits[..i], 1 << 20),
+        Some((i, 'G')) => (&digits[..i], 1 << 30),
+        _ => (digits, 1),
+    };
+    match digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+    {
+        Some(bytes) if bytes > 0 => Ok(bytes),
+        _ => Err(format!("`{value}` is not a byte size like 500M")),
+    }
+}
+
+/// Parses a `--max-body-size` cap: a byte size like [`parse_byte_size`], or `0` for no cap.
+pub fn parse_body_size_cap(value: &str) -> Result<u64, String> {
+    match value.trim() {
+        "0" => Ok(0),
+        _ => parse_byte_size(value),
+    }
+}
+
+/// Widens a size range by `percent` of each endpoint, rounding outward.
+pub fn tolerant_range(min: u64, max: u64, percent: f32) -> (u64, u64) {
+    let margin = |size: u64| (size as f64 * f64::from(percent) / 100.0).ceil() as u64;
+    (
+        min.saturating_sub(margin(min)),
+        max.saturating_add(margin(max)),
+    )
+}
+
 /// Parses a word count filter string (e.g., "50-200" or "10") into a min/max tuple.
 pub fn parse_word_filter(filter: &str) -> Option<(usize, usize)> {
     if let Some((min, max)) = filter.split_once('-') {
@@ -82,34 +895,319 @@ pub fn parse_word_filter(filter: &str) -> Option<(usize, usize)> {
 
 /// Determines if a response should be filtered based on the scan configuration.
 ///
-/// Checks against status codes, content length, response time, and word count filters.
+/// Checks against status codes, content length (range and min/max bounds), empty bodies,
+/// content type, response time, and word count filters.
 pub fn should_filter_response(response: &DetailedResponse, config: &ScanConfig) -> bool {
     // Filter by status code
     if config.filter_codes.contains(&response.status) {
         return true;
     }
 
-    // Filter by content length
+    // Filter empty bodies, going by the word count when the length is unknown
+    if config.exclude_length_zero
+        && (response.content_length == Some(0)
+            || response.content_length.is_none() && response.word_count == Some(0))
+    {
+        return true;
+    }
+
+    // Filter responses of another type, ignoring case and parameters like `charset`
+    if let Some(required) = &config.require_content_type
+        && !response
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| {
+                content_type
+                    .trim()
+                    .to_ascii_lowercase()
+                    .starts_with(&required.trim().to_ascii_lowercase())
+            })
+    {
+        return true;
+    }
+
+    // Filter by content length, with the range widened by the tolerance
+    let tolerance = config.filter_size_tolerance.unwrap_or(0.0);
     if let (Some(content_length), Some((min, max))) = (response.content_length, config.filter_size)
     {
+        let (min, max) = tolerant_range(min, max, tolerance);
         if content_length < min || content_length > max {
             return true;
         }
     }
 
-    // Filter by response time
-    if let Some(max_time) = config.filter_time {
-        if response.response_time.as_millis() > max_time as u128 {
+    // Filter out responses of one size, e.g. a soft 404 page, give or take the tolerance
+    if let (Some(content_length), Some(size)) = (response.content_length, config.filter_size_exact)
+    {
+        let (min, max) = tolerant_range(size, size, tolerance);
+        if (min..=max).contains(&content_length) {
             return true;
         }
     }
 
-    // Filter by word count
-    if let (Some(word_count), Some((min, max))) = (response.word_count, config.filter_words) {
-        if word_count < min || word_count > max {
+    // Filter by explicit size bounds, which combine with the range above
+    if let Some(content_length) = response.content_length {
+        if config
+            .min_response_size
+            .is_some_and(|min| content_length < min)
+        {
             return true;
         }
+        if config
+            .max_response_size
+            .is_some_and(|max| content_length > max)
+        {
+            return true;
+        }
+    }
+
+    // Filter responses too similar to the diff baseline
+    if let Some(score) = response.diff_score
+        && score < config.diff_threshold
+    {
+        return true;
+    }
+
+    // Filter by response time
+    if let Some(max_time) = config.filter_time
+        && response.response_time.as_millis() > max_time as u128
+    {
+        return true;
+    }
+
+    // Filter by word count
+    if let (Some(word_count), Some((min, max))) = (response.word_count, config.filter_words)
+        && (word_count < min || word_count > max)
+    {
+        return true;
     }
 
     false
 }
+
+/// The longest random string a `{{rand:N}}` placeholder may produce.
+const MAX_RANDOM_PLACEHOLDER_LEN: usize = 256;
+
+/// A piece of a request template: literal text or a placeholder filled in per request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplatePart {
+    Literal(String),
+    /// `{{word}}`, the encoded word of the request.
+    Word,
+    /// `{{timestamp}}`, the Unix time in seconds.
+    Timestamp,
+    /// `{{uuid}}`, a random version 4 UUID.
+    Uuid,
+    /// `{{rand:N}}`, N random alphanumeric characters.
+    Random(usize),
+    /// `{{method}}`, the request method, in `--sign-hmac` payloads only.
+    Method,
+    /// `{{path}}`, the path and query of the request, in `--sign-hmac` payloads only.
+    Path,
+}
+
+impl std::fmt::Display for TemplatePart {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TemplatePart::Literal(text) => write!(f, "{text}"),
+            TemplatePart::Word => write!(f, "{{{{word}}}}"),
+            TemplatePart::Timestamp => write!(f, "{{{{timestamp}}}}"),
+            TemplatePart::Uuid => write!(f, "{{{{uuid}}}}"),
+            TemplatePart::Random(len) => write!(f, "{{{{rand:{len}}}}}"),
+            TemplatePart::Method => write!(f, "{{{{method}}}}"),
+            TemplatePart::Path => write!(f, "{{{{path}}}}"),
+        }
+    }
+}
+
+/// The values a template is filled in with for one request.
+///
+/// The timestamp is taken once per request, so the headers, the URL and a signature
+/// computed over it all agree.
+#[derive(Debug, Clone)]
+pub struct TemplateValues<'a> {
+    pub word: &'a str,
+    pub timestamp: i64,
+    pub method: &'a str,
+    pub path: &'a str,
+}
+
+impl<'a> TemplateValues<'a> {
+    /// The values of a GET request for a word, at the current time.
+    pub fn new(word: &'a str) -> Self {
+        TemplateValues {
+            word,
+            timestamp: Utc::now().timestamp(),
+            method: "GET",
+            path: "",
+        }
+    }
+}
+
+/// A header value or URL with `{{...}}` placeholders, parsed once at startup.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Template {
+    parts: Vec<TemplatePart>,
+}
+
+impl Template {
+    /// Returns true if the template has placeholders, i.e. is not plain text.
+    pub fn has_placeholders(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| !matches!(part, TemplatePart::Literal(_)))
+    }
+
+    /// Returns true if the template places the word with `{{word}}`.
+    pub fn has_word(&self) -> bool {
+        self.parts.contains(&TemplatePart::Word)
+    }
+
+    /// Returns true if the template has a `{{timestamp}}` placeholder.
+    pub fn has_timestamp(&self) -> bool {
+        self.parts.contains(&TemplatePart::Timestamp)
+    }
+
+    /// Fills in the placeholders for one request.
+    pub fn expand(&self, values: &TemplateValues) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => out.push_str(text),
+                TemplatePart::Word => out.push_str(values.word),
+                TemplatePart::Timestamp => out.push_str(&values.timestamp.to_string()),
+                TemplatePart::Method => out.push_str(values.method),
+                TemplatePart::Path => out.push_str(values.path),
+                TemplatePart::Uuid => out.push_str(&uuid::Uuid::new_v4().to_string()),
+                TemplatePart::Random(len) => out.extend(
+                    rand::Rng::sample_iter(rand::rng(), rand::distr::Alphanumeric)
+                        .take(*len)
+                        .map(char::from),
+                ),
+            }
+        }
+        out
+    }
+
+    /// Fills in only the word, keeping the other placeholders as written, for output that
+    /// must stay the same across requests.
+    pub fn display(&self, word: &str) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Word => word.to_string(),
+                other => other.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Parses a template with `{{word}}`, `{{timestamp}}`, `{{uuid}}` and `{{rand:N}}`
+/// placeholders. A backslash before a brace makes it literal, so `\{{word}}` is sent as is.
+///
+/// Unknown or unterminated placeholders are errors.
+pub fn parse_template(s: &str) -> Result<Template, String> {
+    parse_template_parts(s, false)
+}
+
+/// Parses the payload template of `--sign-hmac`, which may also use `{{method}}` and
+/// `{{path}}`.
+pub fn parse_signing_template(s: &str) -> Result<Template, String> {
+    parse_template_parts(s, true)
+}
+
+/// Parses a template, allowing the request placeholders of signing payloads if `signing`.
+fn parse_template_parts(s: &str, signing: bool) -> Result<Template, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if let Some(escaped) = rest.strip_prefix('\\')
+            && let Some(brace) = escaped.chars().next().filter(|c| matches!(c, '{' | '}'))
+        {
+            literal.push(brace);
+            rest = &escaped[1..];
+        } else if let Some(inner) = rest.strip_prefix("{{") {
+            let end = inner
+                .find("}}")
+                .ok_or_else(|| format!("unterminated placeholder in {s:?}"))?;
+            let name = inner[..end].trim();
+            let part = match name.split_once(':') {
+                None if name == "word" => TemplatePart::Word,
+                None if name == "timestamp" => TemplatePart::Timestamp,
+                None if name == "uuid" => TemplatePart::Uuid,
+                None if signing && name == "method" => TemplatePart::Method,
+                None if signing && name == "path" => TemplatePart::Path,
+                Some(("rand", len)) => match len.trim().parse::<usize>() {
+                    Ok(len @ 1..=MAX_RANDOM_PLACEHOLDER_LEN) => TemplatePart::Random(len),
+                    _ => {
+                        return Err(format!(
+                            "invalid length in {{{{{name}}}}}, expected 1-{MAX_RANDOM_PLACEHOLDER_LEN}"
+                        ));
+                    }
+                },
+                _ => return Err(format!("unknown placeholder {{{{{name}}}}} in {s:?}")),
+            };
+            if !literal.is_empty() {
+                parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(part);
+            rest = &inner[end + 2..];
+        } else {
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+    Ok(Template { parts })
+}
+
+/// Splits a `--url` with placeholders into the base URL, up to the last `/` before the
+/// first placeholder, and the template of the path after it. A URL without placeholders
+/// is returned as is.
+pub fn split_url_template(url: &str) -> Result<(String, Option<Template>), String> {
+    let template = parse_template(url).map_err(|e| format!("--url: {e}"))?;
+    if !template.has_placeholders() {
+        return Ok((url.to_string(), None));
+    }
+    if !template.has_word() {
+        return Err("--url: a URL with placeholders must place the word with {{word}}".into());
+    }
+
+    let mut parts = template.parts;
+    let prefix = match parts.first() {
+        Some(TemplatePart::Literal(text)) => text.clone(),
+        _ => String::new(),
+    };
+    let host_start = prefix.find("://").map_or(0, |i| i + 3);
+    let slash = prefix
+        .rfind('/')
+        .filter(|&slash| slash > host_start)
+        .ok_or("--url: placeholders must come after the host")?;
+    let rest = &prefix[slash + 1..];
+    match rest.is_empty() {
+        true => {
+            parts.remove(0);
+        }
+        false => parts[0] = TemplatePart::Literal(rest.to_string()),
+    }
+    Ok((prefix[..slash].to_string(), Some(Template { parts })))
+}
+
+/// Parses the custom header values with placeholders into templates, leaving out the
+/// plain ones.
+pub fn header_templates(
+    headers: &HashMap<String, String>,
+) -> Result<Vec<(String, Template)>, String> {
+    let mut templates = Vec::new();
+    for (name, value) in headers {
+        let template = parse_template(value).map_err(|e| format!("--headers {name}: {e}"))?;
+        if template.has_placeholders() {
+            templates.push((name.clone(), template));
+        }
+    }
+    templates.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(templates)
+}
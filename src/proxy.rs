@@ -0,0 +1,192 @@
+//! This module implements rotation among several upstream proxies.
+//!
+//! One client is built per proxy, and every request attempt takes the next one in turn. A
+//! proxy failing `PROXY_FAILURE_LIMIT` requests in a row (failed connections or 407
+//! responses) is quarantined: it is skipped for a cool-down, unless every proxy is.
+
+use reqwest::{Client, Proxy, Url};
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The number of failed requests in a row that quarantines a proxy.
+pub const PROXY_FAILURE_LIMIT: usize = 5;
+
+/// Collects the proxy URLs of `--proxy` and `--proxy-file`, in that order.
+///
+/// The file has one URL per line; empty lines and lines starting with `#` are skipped.
+pub fn load_proxy_urls(proxies: &[String], file: Option<&str>) -> Result<Vec<String>, String> {
+    let mut urls = proxies.to_vec();
+    if let Some(path) = file {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("--proxy-file: cannot read {path}: {e}"))?;
+        urls.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+    }
+    Ok(urls)
+}
+
+/// Builds a proxy for a URL, authenticating with the `user:pass` of `--proxy-auth` if given.
+pub fn build_proxy(url: &str, auth: Option<&str>) -> Result<Proxy, String> {
+    let proxy = Proxy::all(url).map_err(|e| format!("--proxy: invalid proxy URL {url}: {e}"))?;
+    match auth {
+        Some(auth) => {
+            let (user, pass) = auth
+                .split_once(':')
+                .ok_or("--proxy-auth: expected user:pass")?;
+            Ok(proxy.basic_auth(user, pass))
+        }
+        None => Ok(proxy),
+    }
+}
+
+/// Returns a proxy URL without the credentials it may embed, for output.
+pub fn proxy_label(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string().trim_end_matches('/').to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+/// A client sending requests through one proxy, with the outcomes of its requests.
+#[derive(Debug)]
+pub struct ProxyClient {
+    /// The proxy URL without credentials.
+    pub label: String,
+    pub client: Client,
+    requests: AtomicUsize,
+    errors: AtomicUsize,
+    consecutive_failures: AtomicUsize,
+    quarantines: AtomicUsize,
+    /// The end of the current quarantine, if one is in progress.
+    quarantined_until: Mutex<Option<Instant>>,
+}
+
+impl ProxyClient {
+    /// Returns true while the proxy is quarantined.
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantined_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| until > Instant::now())
+    }
+
+    /// Records the outcome of a request and returns true if it quarantines the proxy.
+    pub fn record(&self, ok: bool, cooldown: Duration) -> bool {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if ok {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            return false;
+        }
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        if self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1 < PROXY_FAILURE_LIMIT {
+            return false;
+        }
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        self.quarantines.fetch_add(1, Ordering::Relaxed);
+        *self.quarantined_until.lock().unwrap() = Some(Instant::now() + cooldown);
+        true
+    }
+
+    /// The request counts of the proxy so far, for the summary.
+    pub fn stats(&self) -> ProxyStats {
+        ProxyStats {
+            proxy: self.label.clone(),
+            requests: self.requests.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            quarantines: self.quarantines.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The requests sent through a proxy, shown in the summary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyStats {
+    pub proxy: String,
+    pub requests: usize,
+    pub errors: usize,
+    /// How many times the proxy was quarantined.
+    pub quarantines: usize,
+}
+
+impl fmt::Display for ProxyStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} requests, {} errors",
+            self.proxy, self.requests, self.errors
+        )?;
+        if self.quarantines > 0 {
+            let plural = if self.quarantines == 1 { "" } else { "s" };
+            write!(f, ", quarantined {} time{plural}", self.quarantines)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hands out the proxy clients in turn, skipping quarantined proxies.
+#[derive(Debug)]
+pub struct ProxyRotation {
+    proxies: Vec<ProxyClient>,
+    cooldown: Duration,
+    next: AtomicUsize,
+}
+
+impl ProxyRotation {
+    /// Creates a rotation over `(label, client)` pairs, quarantining failing proxies for
+    /// `cooldown`. There must be at least one client.
+    pub fn new(clients: Vec<(String, Client)>, cooldown: Duration) -> Self {
+        ProxyRotation {
+            proxies: clients
+                .into_iter()
+                .map(|(label, client)| ProxyClient {
+                    label,
+                    client,
+                    requests: AtomicUsize::new(0),
+                    errors: AtomicUsize::new(0),
+                    consecutive_failures: AtomicUsize::new(0),
+                    quarantines: AtomicUsize::new(0),
+                    quarantined_until: Mutex::new(None),
+                })
+                .collect(),
+            cooldown,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the next proxy that is not quarantined, or simply the next one if all are.
+    pub fn next(&self) -> &ProxyClient {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let count = self.proxies.len();
+        (0..count)
+            .map(|offset| &self.proxies[(start + offset) % count])
+            .find(|proxy| !proxy.is_quarantined())
+            .unwrap_or(&self.proxies[start % count])
+    }
+
+    /// Records the outcome of a request sent through a proxy and returns true if it
+    /// quarantines the proxy.
+    pub fn record(&self, proxy: &ProxyClient, ok: bool) -> bool {
+        proxy.record(ok, self.cooldown)
+    }
+
+    /// How long a failing proxy is quarantined for.
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// The request counts of every proxy so far.
+    pub fn stats(&self) -> Vec<ProxyStats> {
+        self.proxies.iter().map(ProxyClient::stats).collect()
+    }
+}
@@ -0,0 +1,49 @@
+//! This module implements the requests-per-second limit set with `--rate`.
+//!
+//! The limiter hands out evenly spaced send slots to all tasks, independent of the
+//! concurrency limit. While the scan is backing off after rate-limit responses, the backoff
+//! is added to the slot spacing, so 429s lower the effective rate further.
+
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{Instant, sleep_until};
+
+/// A shared limiter spacing requests `1 / rate` seconds apart.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// The configured rate in requests per second.
+    rate: f64,
+    /// The time between two send slots at the configured rate.
+    interval: Duration,
+    /// The earliest time the next request may be sent.
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter for the given positive rate, which may be fractional (e.g. 0.5).
+    pub fn new(rate: f64) -> Self {
+        RateLimiter {
+            rate,
+            interval: Duration::from_secs_f64(1.0 / rate),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// The configured rate in requests per second.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Waits until the caller may send a request.
+    ///
+    /// `backoff` is the current global backoff delay, which widens the gap to the next slot.
+    pub async fn acquire(&self, backoff: Duration) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval + backoff;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
@@ -0,0 +1,210 @@
+//! This module implements `--reauth-command`, which renews an expired session mid-scan.
+//!
+//! Responses matching a `--reauth-trigger` (a status, optionally redirecting to a path like
+//! the login page) are counted. Once `--reauth-threshold` of them arrive in a row, new
+//! requests wait while the command runs, and the `Cookie` header or bearer token it prints
+//! replaces the one sent so far. Requests answered by a trigger after the session was
+//! renewed are sent again.
+
+use reqwest::header::{AUTHORIZATION, COOKIE, HeaderMap, HeaderValue};
+use std::fmt;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// The default number of trigger responses in a row that runs the command.
+pub const DEFAULT_REAUTH_THRESHOLD: u32 = 3;
+
+/// A response showing that the session expired: a status, and for redirects optionally
+/// a substring of their `Location`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReauthTrigger {
+    pub status: u16,
+    pub location: Option<String>,
+}
+
+impl ReauthTrigger {
+    /// Returns true if a response with this status and `Location` header matches.
+    pub fn matches(&self, status: u16, location: Option<&str>) -> bool {
+        self.status == status
+            && self
+                .location
+                .as_deref()
+                .is_none_or(|expected| location.is_some_and(|location| location.contains(expected)))
+    }
+}
+
+impl fmt::Display for ReauthTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}:{location}", self.status),
+            None => write!(f, "{}", self.status),
+        }
+    }
+}
+
+/// Parses one `--reauth-trigger` entry, a status code with an optional `:location` part.
+pub fn parse_reauth_trigger(s: &str) -> Result<ReauthTrigger, String> {
+    let (status, location) = match s.trim().split_once(':') {
+        Some((status, location)) => (status, Some(location.trim())),
+        None => (s.trim(), None),
+    };
+    let status = status
+        .trim()
+        .parse::<u16>()
+        .ok()
+        .filter(|status| (100..=599).contains(status))
+        .ok_or_else(|| format!("invalid status code in {s:?}"))?;
+    if location == Some("") {
+        return Err(format!("empty location in {s:?}"));
+    }
+    Ok(ReauthTrigger {
+        status,
+        location: location.map(str::to_string),
+    })
+}
+
+/// Reads the credential printed by the command: its first non-empty line, either a
+/// `Cookie: ...` header or a bearer token, with or without a `Bearer ` prefix.
+///
+/// Returns the header that replaces the one sent so far.
+pub fn parse_credential(stdout: &str) -> Result<HeaderMap, String> {
+    let line = stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .ok_or("the command printed nothing")?;
+    let (name, value) = match line.split_once(':') {
+        Some((name, cookie)) if name.trim().eq_ignore_ascii_case("cookie") => {
+            (COOKIE, cookie.trim().to_string())
+        }
+        _ => {
+            let token = line.strip_prefix("Bearer ").unwrap_or(line).trim();
+            (AUTHORIZATION, format!("Bearer {token}"))
+        }
+    };
+    let mut value =
+        HeaderValue::from_str(&value).map_err(|_| "the command printed an invalid header value")?;
+    value.set_sensitive(true);
+    let mut headers = HeaderMap::new();
+    headers.insert(name, value);
+    Ok(headers)
+}
+
+/// Runs the command with `sh -c` and reads the credential it prints.
+async fn run_command(command: &str) -> Result<HeaderMap, String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .await
+        .map_err(|e| format!("cannot run the command: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("the command exited with {}", output.status));
+    }
+    parse_credential(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The state of the re-authentication hook, shared by all requests of a scan.
+#[derive(Debug)]
+pub struct Reauth {
+    command: String,
+    triggers: Vec<ReauthTrigger>,
+    threshold: u32,
+    /// The trigger responses received in a row.
+    streak: AtomicUsize,
+    /// The number of times the command was run, successfully or not.
+    episodes: AtomicUsize,
+    successes: AtomicUsize,
+    /// The header of the last credential, sent in place of the configured one.
+    credential: RwLock<HeaderMap>,
+    /// Held for writing while the command runs, so new requests wait for it.
+    running: tokio::sync::RwLock<()>,
+}
+
+impl Reauth {
+    /// Creates the hook, running `command` after `threshold` trigger responses in a row.
+    pub fn new(command: String, triggers: Vec<ReauthTrigger>, threshold: u32) -> Self {
+        Reauth {
+            command,
+            triggers,
+            threshold,
+            streak: AtomicUsize::new(0),
+            episodes: AtomicUsize::new(0),
+            successes: AtomicUsize::new(0),
+            credential: RwLock::new(HeaderMap::new()),
+            running: tokio::sync::RwLock::new(()),
+        }
+    }
+
+    /// Waits while the command is running.
+    pub async fn wait(&self) {
+        drop(self.running.read().await);
+    }
+
+    /// The number of times the command was run, to tell later whether it ran since.
+    pub fn episodes(&self) -> usize {
+        self.episodes.load(Ordering::Relaxed)
+    }
+
+    /// The header of the renewed credential, empty until the command succeeded once.
+    pub fn headers(&self) -> HeaderMap {
+        self.credential.read().unwrap().clone()
+    }
+
+    /// Returns true if a response matches one of the triggers.
+    pub fn is_trigger(&self, status: u16, location: Option<&str>) -> bool {
+        self.triggers
+            .iter()
+            .any(|trigger| trigger.matches(status, location))
+    }
+
+    /// Ends the streak of trigger responses, on a response that matches no trigger.
+    pub fn clear_streak(&self) {
+        self.streak.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a trigger response to a request sent after `seen` episodes, running the
+    /// command once it completes a streak. Returns true if the command ran since the
+    /// request was sent, so it is worth sending again.
+    pub async fn on_trigger(&self, seen: usize) -> bool {
+        if self.streak.fetch_add(1, Ordering::Relaxed) + 1 < self.threshold as usize {
+            // The session may have been renewed while the request was in flight
+            self.wait().await;
+            return self.episodes() != seen;
+        }
+
+        let _running = self.running.write().await;
+        // Another request completed a streak and ran the command in the meantime
+        if self.episodes() != seen {
+            return true;
+        }
+        info!(
+            "{} responses in a row show the session expired, running --reauth-command",
+            self.threshold
+        );
+        let result = run_command(&self.command).await;
+        self.episodes.fetch_add(1, Ordering::Relaxed);
+        self.streak.store(0, Ordering::Relaxed);
+        match result {
+            Ok(headers) => {
+                let name = headers.keys().next().map_or("", |name| name.as_str());
+                info!("Re-authenticated, sending the new {name} header");
+                *self.credential.write().unwrap() = headers;
+                self.successes.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(e) => {
+                warn!("--reauth-command failed: {e}");
+                false
+            }
+        }
+    }
+
+    /// The number of successful and failed runs of the command, for the summary.
+    pub fn stats(&self) -> (usize, usize) {
+        let successes = self.successes.load(Ordering::Relaxed);
+        (successes, self.episodes() - successes)
+    }
+}
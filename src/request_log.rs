@@ -0,0 +1,163 @@
+//! This module implements `--request-log`, an audit trail of every request a scan sends.
+//!
+//! Each request is written as one JSON line with its method, URL and headers, the time it
+//! was sent, and the status and `Content-Length` of its response (or the error it failed
+//! with). Lines are queued to a background task, so the scan never waits for the disk.
+//! Sensitive header values, e.g. `Authorization` and `Cookie`, are masked unless
+//! `--log-sensitive-headers` is set, and `--redact` patterns apply as everywhere else.
+
+use crate::buster::ScanConfig;
+use crate::client::DEFAULT_USER_AGENT;
+use crate::output::redact;
+use chrono::Utc;
+use reqwest::header::USER_AGENT;
+use reqwest::{Request, Response};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{MissedTickBehavior, interval};
+
+/// How often the log is flushed to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The value written in place of a sensitive header.
+const MASKED_VALUE: &str = "********";
+
+/// Headers masked even when they were not marked sensitive, e.g. when given with `-H`.
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+
+/// One request of the log.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RequestLogEntry {
+    /// When the request was sent, in RFC 3339 format.
+    pub timestamp: String,
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_length: Option<u64>,
+    /// Why the request failed without a response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RequestLogEntry {
+    /// Describes a request about to be sent.
+    pub fn new(request: &Request, config: &ScanConfig) -> Self {
+        let mut headers: BTreeMap<_, _> = request
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let sensitive = value.is_sensitive() || SENSITIVE_HEADERS.contains(&name.as_str());
+                let value = if sensitive && !config.log_sensitive_headers {
+                    MASKED_VALUE.to_string()
+                } else {
+                    redact(
+                        &String::from_utf8_lossy(value.as_bytes()),
+                        &config.redact_patterns,
+                    )
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        // The client adds its own User-Agent when the request has none
+        headers
+            .entry(USER_AGENT.to_string())
+            .or_insert_with(|| DEFAULT_USER_AGENT.to_string());
+        RequestLogEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            method: request.method().to_string(),
+            url: redact(request.url().as_str(), &config.redact_patterns),
+            headers,
+            status: None,
+            content_length: None,
+            error: None,
+        }
+    }
+
+    /// Adds the outcome of the request.
+    pub fn with_outcome(mut self, sent: &reqwest::Result<Response>, config: &ScanConfig) -> Self {
+        match sent {
+            Ok(response) => {
+                self.status = Some(response.status().as_u16());
+                self.content_length = response.content_length();
+            }
+            Err(e) => self.error = Some(redact(&e.to_string(), &config.redact_patterns)),
+        }
+        self
+    }
+}
+
+/// Messages sent to the log writer task.
+enum LogMessage {
+    Entry(Box<RequestLogEntry>),
+    Finish,
+}
+
+/// The `--request-log` file, written by a background task.
+pub struct RequestLog {
+    tx: mpsc::UnboundedSender<LogMessage>,
+    handle: Mutex<Option<JoinHandle<io::Result<()>>>>,
+}
+
+impl RequestLog {
+    /// Opens the log file for appending and spawns the writer task.
+    ///
+    /// The file is opened right away, so an unwritable path is reported before the scan.
+    pub fn spawn(path: &str) -> io::Result<Self> {
+        let file = File::from_std(OpenOptions::new().create(true).append(true).open(path)?);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(run_log_writer(rx, BufWriter::new(file)));
+        Ok(Self {
+            tx,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    /// Queues a request to be written.
+    pub fn record(&self, entry: RequestLogEntry) {
+        // The writer only stops early if it failed, which `finish` reports
+        let _ = self.tx.send(LogMessage::Entry(Box::new(entry)));
+    }
+
+    /// Writes the queued requests, flushes the file and stops the task.
+    pub async fn finish(&self) -> io::Result<()> {
+        let _ = self.tx.send(LogMessage::Finish);
+        let handle = self.handle.lock().unwrap().take();
+        match handle {
+            Some(handle) => handle.await.map_err(io::Error::other)?,
+            None => Ok(()),
+        }
+    }
+}
+
+/// The body of the log writer task. Lines go to the file through tokio, so writing them
+/// never blocks the workers sending the requests.
+async fn run_log_writer(
+    mut rx: mpsc::UnboundedReceiver<LogMessage>,
+    mut file: BufWriter<File>,
+) -> io::Result<()> {
+    let mut ticker = interval(FLUSH_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some(LogMessage::Entry(entry)) => {
+                    let line = serde_json::to_string(&entry)? + "\n";
+                    file.write_all(line.as_bytes()).await?;
+                }
+                Some(LogMessage::Finish) | None => break,
+            },
+            _ = ticker.tick() => file.flush().await?,
+        }
+    }
+    file.flush().await
+}
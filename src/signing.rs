@@ -0,0 +1,170 @@
+//! This module implements `--sign-hmac`, which signs every request with an HMAC header.
+//!
+//! The spec names the signature header, the secret, the payload and the algorithm, e.g.
+//! `header=X-Signature;secret=env:SIGKEY;payload={{method}}{{path}}{{timestamp}};algo=sha256`.
+//! The payload is a template filled in once the final URL of a request is known. When it
+//! uses `{{timestamp}}`, the same timestamp is sent in a header of its own, so the server
+//! can recompute the signature.
+
+use crate::parser::{Template, TemplateValues, parse_signing_template, resolve_secret};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+/// The hash function of the HMAC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HmacAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// How the signature bytes are written into the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureEncoding {
+    Hex,
+    Base64,
+}
+
+/// Computes the HMAC of a payload with a key.
+pub fn compute_hmac(algorithm: HmacAlgorithm, key: &[u8], payload: &[u8]) -> Vec<u8> {
+    // HMAC accepts keys of any length
+    match algorithm {
+        HmacAlgorithm::Sha1 => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("any key length is valid");
+            mac.update(payload);
+            mac.finalize().into_bytes().to_vec()
+        }
+        HmacAlgorithm::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length is valid");
+            mac.update(payload);
+            mac.finalize().into_bytes().to_vec()
+        }
+        HmacAlgorithm::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("any key length is valid");
+            mac.update(payload);
+            mac.finalize().into_bytes().to_vec()
+        }
+    }
+}
+
+/// A parsed `--sign-hmac` spec.
+#[derive(Clone)]
+pub struct SigningSpec {
+    pub header: HeaderName,
+    secret: Vec<u8>,
+    pub payload: Template,
+    pub algorithm: HmacAlgorithm,
+    pub encoding: SignatureEncoding,
+    pub timestamp_header: HeaderName,
+}
+
+// The secret is left out, so it cannot end up in debug logs
+impl std::fmt::Debug for SigningSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SigningSpec")
+            .field("header", &self.header)
+            .field("payload", &self.payload)
+            .field("algorithm", &self.algorithm)
+            .field("encoding", &self.encoding)
+            .field("timestamp_header", &self.timestamp_header)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningSpec {
+    /// Computes the signature of one request, encoded for its header.
+    pub fn sign(&self, values: &TemplateValues) -> String {
+        let payload = self.payload.expand(values);
+        let signature = compute_hmac(self.algorithm, &self.secret, payload.as_bytes());
+        match self.encoding {
+            SignatureEncoding::Hex => signature.iter().map(|b| format!("{b:02x}")).collect(),
+            SignatureEncoding::Base64 => BASE64_STANDARD.encode(signature),
+        }
+    }
+
+    /// The headers added to one request: the signature, and the timestamp it covers if the
+    /// payload has one.
+    pub fn headers(&self, values: &TemplateValues) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let mut signature =
+            HeaderValue::from_str(&self.sign(values)).expect("hex and base64 are valid in headers");
+        signature.set_sensitive(true);
+        headers.insert(self.header.clone(), signature);
+        if self.payload.has_timestamp() {
+            headers.insert(self.timestamp_header.clone(), values.timestamp.into());
+        }
+        headers
+    }
+}
+
+/// Parses a `--sign-hmac` spec of `;`-separated `key=value` pairs.
+///
+/// `header`, `secret` and `payload` are required; `algo` (sha1, sha256 or sha512),
+/// `encoding` (hex or base64) and `timestamp-header` default to sha256, hex and
+/// `X-Timestamp`. The secret must be given as `env:NAME` or `file:/path`, so it never
+/// appears on the command line.
+pub fn parse_signing_spec(spec: &str) -> Result<SigningSpec, String> {
+    let header_name = |value: &str| {
+        HeaderName::from_bytes(value.trim().as_bytes())
+            .map_err(|_| format!("--sign-hmac: invalid header name {value:?}"))
+    };
+    let mut header = None;
+    let mut secret = None;
+    let mut payload = None;
+    let mut algorithm = HmacAlgorithm::Sha256;
+    let mut encoding = SignatureEncoding::Hex;
+    // The header carrying the timestamp of the payload, unless the spec names another
+    let mut timestamp_header = HeaderName::from_static("x-timestamp");
+
+    for field in spec.split(';').map(str::trim).filter(|f| !f.is_empty()) {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("--sign-hmac: expected key=value, got {field:?}"))?;
+        let value = value.trim();
+        match key.trim() {
+            "header" => header = Some(header_name(value)?),
+            "secret" => {
+                if !value.starts_with("env:") && !value.starts_with("file:") {
+                    return Err(
+                        "--sign-hmac: the secret must be given as env:NAME or file:/path".into(),
+                    );
+                }
+                secret = Some(resolve_secret("--sign-hmac secret", value)?);
+            }
+            "payload" => {
+                payload =
+                    Some(parse_signing_template(value).map_err(|e| format!("--sign-hmac: {e}"))?)
+            }
+            "algo" => {
+                algorithm = match value.trim().to_ascii_lowercase().as_str() {
+                    "sha1" => HmacAlgorithm::Sha1,
+                    "sha256" => HmacAlgorithm::Sha256,
+                    "sha512" => HmacAlgorithm::Sha512,
+                    other => return Err(format!("--sign-hmac: unknown algorithm {other:?}")),
+                }
+            }
+            "encoding" => {
+                encoding = match value.trim().to_ascii_lowercase().as_str() {
+                    "hex" => SignatureEncoding::Hex,
+                    "base64" => SignatureEncoding::Base64,
+                    other => return Err(format!("--sign-hmac: unknown encoding {other:?}")),
+                }
+            }
+            "timestamp-header" => timestamp_header = header_name(value)?,
+            other => return Err(format!("--sign-hmac: unknown key {other:?}")),
+        }
+    }
+
+    Ok(SigningSpec {
+        header: header.ok_or("--sign-hmac: missing header=")?,
+        secret: secret.ok_or("--sign-hmac: missing secret=")?.into_bytes(),
+        payload: payload.ok_or("--sign-hmac: missing payload=")?,
+        algorithm,
+        encoding,
+        timestamp_header,
+    })
+}
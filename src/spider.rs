@@ -0,0 +1,130 @@
+//! This module implements `--spider-depth`, which follows the links of found pages.
+//!
+//! The `href` and `src` attributes of successful HTML responses are resolved against the
+//! page URL, and the paths on the target host are queued. Once a pass over the word list is
+//! done, the queued paths are scanned in a pass of their own, whose pages can queue links
+//! for the next level, up to the configured depth.
+
+use crate::parser;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Url;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The default cap on the paths queued by following links.
+pub const DEFAULT_SPIDER_MAX_URLS: usize = 1000;
+
+/// A pre-compiled regex matching `href` and `src` attributes, quoted or not.
+static LINK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\b(?:href|src)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+
+/// Link schemes that never point at a page of the target.
+const IGNORED_SCHEMES: [&str; 5] = ["mailto:", "javascript:", "tel:", "data:", "about:"];
+
+/// Extracts the words of the same-host links in an HTML page, in order of first appearance.
+///
+/// Links are resolved against `page_url`, and only those on the host of `base_url` and under
+/// its path are kept. Fragments and query strings are stripped, and fragment-only, `mailto:`,
+/// `javascript:` and similar links are skipped.
+pub fn extract_links(body: &str, page_url: &str, base_url: &str) -> Vec<String> {
+    let Ok(page) = Url::parse(page_url) else {
+        return Vec::new();
+    };
+    let urls: Vec<String> = LINK_RE
+        .captures_iter(body)
+        .filter_map(|caps| caps.get(1).or(caps.get(2)).or(caps.get(3)))
+        .map(|link| link.as_str().trim().replace("&amp;", "&"))
+        .filter(|link| !link.is_empty() && !link.starts_with('#'))
+        .filter(|link| {
+            let lower = link.to_ascii_lowercase();
+            !IGNORED_SCHEMES
+                .iter()
+                .any(|scheme| lower.starts_with(scheme))
+        })
+        .filter_map(|link| page.join(&link).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(String::from)
+        .collect();
+    parser::url_words(&urls, base_url)
+}
+
+/// The paths found by following links, waiting for their pass or already scanned.
+#[derive(Debug)]
+pub struct Spider {
+    max_urls: usize,
+    max_depth: usize,
+    /// The depth of the pass being scanned, 0 for the word list.
+    depth: AtomicUsize,
+    /// The number of words taken from the queue to be scanned.
+    taken: AtomicUsize,
+    /// Every word scanned or queued, so no path is requested twice.
+    seen: Mutex<HashSet<String>>,
+    /// The words found by following links, tagged as `spider` in output and reports.
+    found: Mutex<HashSet<String>>,
+    /// The words queued for the next pass.
+    queue: Mutex<Vec<String>>,
+}
+
+impl Spider {
+    /// Creates a spider that queues at most `max_urls` paths not among `words`, following
+    /// links up to `max_depth` levels from the word list.
+    pub fn new(max_urls: usize, max_depth: usize, words: &[String]) -> Self {
+        Spider {
+            max_urls,
+            max_depth,
+            depth: AtomicUsize::new(0),
+            taken: AtomicUsize::new(0),
+            seen: Mutex::new(words.iter().cloned().collect()),
+            found: Mutex::new(HashSet::new()),
+            queue: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queues the words of links that were not scanned or queued before, until the cap is
+    /// reached. Returns the number of words queued.
+    ///
+    /// Nothing is queued during the pass at the maximum depth, as it is the last one.
+    pub fn queue_links(&self, links: Vec<String>) -> usize {
+        if self.depth.load(Ordering::Relaxed) >= self.max_depth {
+            return 0;
+        }
+        let mut seen = self.seen.lock().unwrap();
+        let mut found = self.found.lock().unwrap();
+        let mut queue = self.queue.lock().unwrap();
+        let before = queue.len();
+        for link in links {
+            if found.len() >= self.max_urls {
+                break;
+            }
+            if seen.insert(link.clone()) {
+                found.insert(link.clone());
+                queue.push(link);
+            }
+        }
+        queue.len() - before
+    }
+
+    /// Takes the queued words, to be scanned in the next pass, one level deeper.
+    pub fn take_queue(&self) -> Vec<String> {
+        let words = std::mem::take(&mut *self.queue.lock().unwrap());
+        if !words.is_empty() {
+            self.depth.fetch_add(1, Ordering::Relaxed);
+            self.taken.fetch_add(words.len(), Ordering::Relaxed);
+        }
+        words
+    }
+
+    /// Returns true if the word was found by following a link.
+    pub fn is_spidered(&self, word: &str) -> bool {
+        self.found.lock().unwrap().contains(word)
+    }
+
+    /// The number of words found by following links and taken to be scanned. Words queued
+    /// too late for a pass, e.g. during the retry pass, are not counted.
+    pub fn found_count(&self) -> usize {
+        self.taken.load(Ordering::Relaxed)
+    }
+}
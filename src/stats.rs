@@ -0,0 +1,144 @@
+//! This module collects the latency percentiles, slowest words and status counts shown in
+//! the summary at the end of a scan and embedded in JSON reports.
+//!
+//! Response times are kept in a reservoir of at most `RESERVOIR_SIZE` samples, so long scans
+//! do not hold every duration in memory: below that size every response counts, above it a
+//! uniform random sample does. The slowest words and the status counts are always exact.
+
+use crate::buster::BustResult;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// The most response times kept for the percentiles.
+pub const RESERVOIR_SIZE: usize = 10_000;
+
+/// The number of slowest words listed in the summary.
+pub const SLOWEST_COUNT: usize = 5;
+
+/// Statistics of the responses received during a scan.
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    samples: Vec<Duration>,
+    /// The number of response times offered to the reservoir.
+    seen: usize,
+    /// The slowest responses, slowest first.
+    slowest: Vec<(String, Duration)>,
+    statuses: BTreeMap<u16, usize>,
+}
+
+impl ScanStats {
+    /// Records a result. Every received response counts, including filtered ones; failed
+    /// requests have no response and are skipped.
+    pub fn record(&mut self, result: &BustResult) {
+        if let Some(resp) = result.response() {
+            self.record_response(&resp.word, resp.status, resp.response_time);
+        }
+    }
+
+    /// Records one response.
+    pub fn record_response(&mut self, word: &str, status: u16, time: Duration) {
+        *self.statuses.entry(status).or_insert(0) += 1;
+
+        // Reservoir sampling: the n-th time replaces a random sample with probability size/n
+        self.seen += 1;
+        if self.samples.len() < RESERVOIR_SIZE {
+            self.samples.push(time);
+        } else {
+            let index = rand::rng().random_range(0..self.seen);
+            if index < RESERVOIR_SIZE {
+                self.samples[index] = time;
+            }
+        }
+
+        if self.slowest.len() < SLOWEST_COUNT
+            || self
+                .slowest
+                .last()
+                .is_some_and(|(_, slowest)| time > *slowest)
+        {
+            let position = self.slowest.partition_point(|(_, other)| *other >= time);
+            self.slowest.insert(position, (word.to_string(), time));
+            self.slowest.truncate(SLOWEST_COUNT);
+        }
+    }
+
+    /// The response time at or below which `percent` of the sampled responses fall, by nearest
+    /// rank. `None` before the first response.
+    pub fn percentile(&self, percent: usize) -> Option<Duration> {
+        self.percentiles([percent]).map(|[time]| time)
+    }
+
+    /// The response times of several percentiles, sorting the samples only once.
+    pub fn percentiles<const N: usize>(&self, percents: [usize; N]) -> Option<[Duration; N]> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut samples = self.samples.clone();
+        samples.sort_unstable();
+        let rank = |percent: usize| (samples.len() * percent).div_ceil(100).saturating_sub(1);
+        Some(percents.map(|percent| samples[rank(percent).min(samples.len() - 1)]))
+    }
+
+    /// The slowest responses with their words, slowest first.
+    pub fn slowest(&self) -> &[(String, Duration)] {
+        &self.slowest
+    }
+
+    /// The number of responses per status code, in ascending order of status.
+    pub fn statuses(&self) -> &BTreeMap<u16, usize> {
+        &self.statuses
+    }
+
+    /// Formats the status counts as `200: 14, 301: 3, 404: 51234`.
+    pub fn status_line(&self) -> String {
+        self.statuses
+            .iter()
+            .map(|(status, count)| format!("{status}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The statistics as written into JSON reports, whose status counts are in the
+    /// `status_breakdown` of the report already.
+    pub fn report(&self) -> StatsReport {
+        let [p50_ms, p90_ms, p99_ms] = match self.percentiles([50, 90, 99]) {
+            Some(times) => times.map(|time| Some(millis(time))),
+            None => [None; 3],
+        };
+        StatsReport {
+            p50_ms,
+            p90_ms,
+            p99_ms,
+            slowest: self
+                .slowest
+                .iter()
+                .map(|(word, time)| SlowResponse {
+                    word: word.clone(),
+                    response_time_ms: millis(*time),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A duration in milliseconds, at microsecond precision.
+fn millis(time: Duration) -> f64 {
+    time.as_micros() as f64 / 1000.0
+}
+
+/// The statistics of a scan in the JSON report.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StatsReport {
+    pub p50_ms: Option<f64>,
+    pub p90_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+    pub slowest: Vec<SlowResponse>,
+}
+
+/// One of the slowest responses of a scan.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SlowResponse {
+    pub word: String,
+    pub response_time_ms: f64,
+}
@@ -0,0 +1,119 @@
+//! This module implements `--waf-detect`, which pauses the scan while a WAF blocks it.
+//!
+//! The outcome of every request is recorded in a sliding window. Once the share of blocked
+//! outcomes (block statuses such as 403, or failed connections) in a full window reaches the
+//! threshold, all new requests wait out a cool-down. The scan then resumes with a raised
+//! global delay, which halves with every later response that is not rate limited.
+
+use crate::buster::BustResult;
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{Instant, sleep_until};
+
+/// The number of most recent requests the block percentage is computed over.
+pub const WAF_WINDOW: usize = 50;
+
+/// The statuses counted as blocked unless `--waf-codes` is given.
+pub const DEFAULT_WAF_CODES: [u16; 3] = [403, 406, 429];
+
+/// The global delay in milliseconds the scan resumes with after a cool-down.
+pub const RESUME_DELAY_MS: u64 = 1000;
+
+/// A period in which the target blocked the scan, recorded for the summary and report.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BlockEpisode {
+    /// When the block was detected, in RFC 3339 format.
+    pub started_at: String,
+    /// The percentage of the last `WAF_WINDOW` requests that were blocked.
+    pub blocked_percent: u8,
+    /// How long new requests were paused for.
+    pub cooldown_secs: u64,
+}
+
+/// Watches request outcomes for signs of a WAF block and holds requests during cool-downs.
+#[derive(Debug)]
+pub struct WafDetector {
+    codes: Vec<u16>,
+    threshold: u8,
+    cooldown: Duration,
+    /// Whether each of the recent requests was blocked.
+    window: Mutex<VecDeque<bool>>,
+    /// The end of the current cool-down, if one is in progress.
+    paused_until: Mutex<Option<Instant>>,
+    episodes: Mutex<Vec<BlockEpisode>>,
+}
+
+impl WafDetector {
+    /// Creates a detector counting `codes` as blocked, which pauses for `cooldown` once
+    /// `threshold` percent of the window is blocked.
+    pub fn new(codes: Vec<u16>, threshold: u8, cooldown: Duration) -> Self {
+        WafDetector {
+            codes,
+            threshold,
+            cooldown,
+            window: Mutex::new(VecDeque::with_capacity(WAF_WINDOW)),
+            paused_until: Mutex::new(None),
+            episodes: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns true if the result looks like the target blocked the request.
+    fn is_blocked(&self, result: &BustResult) -> bool {
+        match result.response() {
+            Some(resp) => self.codes.contains(&resp.status),
+            None => true,
+        }
+    }
+
+    /// Records a request outcome and returns the new episode if it starts a cool-down.
+    ///
+    /// Outcomes arriving during a cool-down are ignored; they were sent before it began.
+    pub fn record(&self, result: &BustResult) -> Option<BlockEpisode> {
+        if self.is_cooling_down() {
+            return None;
+        }
+
+        let mut window = self.window.lock().unwrap();
+        if window.len() == WAF_WINDOW {
+            window.pop_front();
+        }
+        window.push_back(self.is_blocked(result));
+        let blocked = window.iter().filter(|&&blocked| blocked).count();
+        if window.len() < WAF_WINDOW || blocked * 100 < WAF_WINDOW * self.threshold as usize {
+            return None;
+        }
+        window.clear();
+
+        *self.paused_until.lock().unwrap() = Some(Instant::now() + self.cooldown);
+        let episode = BlockEpisode {
+            started_at: Utc::now().to_rfc3339(),
+            blocked_percent: (blocked * 100 / WAF_WINDOW) as u8,
+            cooldown_secs: self.cooldown.as_secs(),
+        };
+        self.episodes.lock().unwrap().push(episode.clone());
+        Some(episode)
+    }
+
+    /// Returns true while a cool-down is in progress.
+    pub fn is_cooling_down(&self) -> bool {
+        self.paused_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| until > Instant::now())
+    }
+
+    /// Waits until the current cool-down, if any, is over.
+    pub async fn wait(&self) {
+        let until = *self.paused_until.lock().unwrap();
+        if let Some(until) = until {
+            sleep_until(until).await;
+        }
+    }
+
+    /// The block episodes detected so far.
+    pub fn episodes(&self) -> Vec<BlockEpisode> {
+        self.episodes.lock().unwrap().clone()
+    }
+}
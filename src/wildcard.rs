@@ -5,12 +5,14 @@
 //! This module builds a profile of what a "not found" page looks like by making requests
 //! to known non-existent paths, and then compares subsequent responses against this profile.
 
-use crate::buster::ScanConfig;
+use crate::buster::{BustResult, DetailedResponse, ScanConfig, get_request};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use tracing::{debug, info};
 
 /// A pre-compiled regex to extract the content of a <title> tag.
 static TITLE_REGEX: Lazy<Regex> =
@@ -44,6 +46,12 @@ pub struct WildcardProfile {
     pub html_tag_count_range: Option<(usize, usize)>,
 }
 
+impl Default for WildcardProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WildcardProfile {
     /// Creates a new, empty `WildcardProfile`.
     pub fn new() -> Self {
@@ -65,9 +73,7 @@ impl WildcardProfile {
         self.common_status_codes.insert(resp.status_code);
         self.sha256_hashes.insert(resp.sha256.clone());
 
-        let tol = (resp.size as f64 * 0.05).ceil() as usize;
-        let min_size = resp.size.saturating_sub(tol);
-        let max_size = resp.size + tol;
+        let (min_size, max_size) = tolerance_range(resp.size, 0.05);
 
         for (k, v) in &resp.headers {
             self.header_patterns
@@ -83,13 +89,8 @@ impl WildcardProfile {
             self.error_message_patterns.insert(err.clone());
         }
 
-        let line_tol = (resp.line_count as f64 * 0.1).ceil() as usize;
-        let min_line = resp.line_count.saturating_sub(line_tol);
-        let max_line = resp.line_count + line_tol;
-
-        let word_tol = (resp.word_count as f64 * 0.1).ceil() as usize;
-        let min_word = resp.word_count.saturating_sub(word_tol);
-        let max_word = resp.word_count + word_tol;
+        let (min_line, max_line) = tolerance_range(resp.line_count, 0.1);
+        let (min_word, max_word) = tolerance_range(resp.word_count, 0.1);
 
         Self::merge_range(&mut self.size_ranges, min_size, max_size);
         Self::merge_range(&mut self.line_count_ranges, min_line, max_line);
@@ -98,6 +99,40 @@ impl WildcardProfile {
         self.update_tag_count_range(resp.html_tag_count);
     }
 
+    /// Merges another profile into this one, e.g. one built for another host with the
+    /// same wildcard behaviour.
+    pub fn merge(&mut self, other: &WildcardProfile) {
+        self.sha256_hashes
+            .extend(other.sha256_hashes.iter().cloned());
+        self.common_status_codes
+            .extend(other.common_status_codes.iter().copied());
+        self.title_patterns
+            .extend(other.title_patterns.iter().cloned());
+        self.error_message_patterns
+            .extend(other.error_message_patterns.iter().cloned());
+        for (k, values) in &other.header_patterns {
+            self.header_patterns
+                .entry(k.clone())
+                .or_default()
+                .extend(values.iter().cloned());
+        }
+
+        for &(min, max) in &other.size_ranges {
+            Self::merge_range(&mut self.size_ranges, min, max);
+        }
+        for &(min, max) in &other.line_count_ranges {
+            Self::merge_range(&mut self.line_count_ranges, min, max);
+        }
+        for &(min, max) in &other.word_count_ranges {
+            Self::merge_range(&mut self.word_count_ranges, min, max);
+        }
+
+        if let Some((min, max)) = other.html_tag_count_range {
+            self.update_tag_count_range(min);
+            self.update_tag_count_range(max);
+        }
+    }
+
     /// Merges a new min/max pair into a vector of ranges.
     pub fn merge_range(ranges: &mut Vec<(usize, usize)>, min: usize, max: usize) {
         let mut merged = false;
@@ -130,39 +165,49 @@ impl WildcardProfile {
 
     /// Checks if a given response sample is likely a wildcard based on the profile.
     pub fn is_likely_wildcard(&self, resp: &WildcardSample) -> bool {
-        let mut match_count = 0;
-        let mut confidence = 0.0;
+        self.classify(resp).0
+    }
+
+    /// Classifies a response sample against the profile.
+    ///
+    /// Returns whether the sample is likely a wildcard, along with the confidence score
+    /// the decision was based on, capped at 1.0.
+    pub fn classify(&self, resp: &WildcardSample) -> (bool, f32) {
+        let report = self.score(resp);
+        (report.is_wildcard, report.confidence)
+    }
+
+    /// Scores a response sample against the profile, recording what each criterion
+    /// contributed to the confidence.
+    pub fn score(&self, resp: &WildcardSample) -> WildcardScoreReport {
+        let mut report = WildcardScoreReport::default();
+        let contribution = |matched: bool, weight: f32| if matched { weight } else { 0.0 };
 
         // 1. Exact SHA256 match
-        if self.sha256_hashes.contains(&resp.sha256) {
-            confidence += 0.9;
-        }
+        report.sha256 = contribution(self.sha256_hashes.contains(&resp.sha256), 0.9);
 
         // 2. Title pattern match
-        if let Some(title) = &resp.title {
-            if self.title_patterns.contains(title) {
-                confidence += 0.7;
-                match_count += 1;
-            }
-        }
+        report.title = contribution(
+            resp.title
+                .as_ref()
+                .is_some_and(|title| self.title_patterns.contains(title)),
+            0.7,
+        );
 
         // 3. Error message pattern match
-        if let Some(err) = &resp.error_message {
-            if self.error_message_patterns.contains(err) {
-                confidence += 0.8;
-                match_count += 1;
-            }
-        }
+        report.error_message = contribution(
+            resp.error_message
+                .as_ref()
+                .is_some_and(|err| self.error_message_patterns.contains(err)),
+            0.8,
+        );
 
         // 4. Size range match
         let size_match = self
             .size_ranges
             .iter()
             .any(|(min, max)| resp.size >= *min && resp.size <= *max);
-        if size_match {
-            confidence += 0.3;
-            match_count += 1;
-        }
+        report.size = contribution(size_match, 0.3);
 
         // 5. Multiple metrics matching
         let line_match = self
@@ -178,36 +223,107 @@ impl WildcardProfile {
         } else {
             false
         };
-
-        if line_match {
-            match_count += 1;
-            confidence += 0.2;
-        }
-        if word_match {
-            match_count += 1;
-            confidence += 0.2;
-        }
-        if tag_match {
-            match_count += 1;
-            confidence += 0.2;
-        }
+        report.line_count = contribution(line_match, 0.2);
+        report.word_count = contribution(word_match, 0.2);
+        report.html_tag_count = contribution(tag_match, 0.2);
+
+        // The hash is not counted as a match of its own, its weight alone is decisive
+        report.match_count = [
+            report.title,
+            report.error_message,
+            report.size,
+            report.line_count,
+            report.word_count,
+            report.html_tag_count,
+        ]
+        .iter()
+        .filter(|&&weight| weight > 0.0)
+        .count();
+        let mut confidence = report.sha256
+            + report.title
+            + report.error_message
+            + report.size
+            + report.line_count
+            + report.word_count
+            + report.html_tag_count;
 
         // 6. Don't filter based on status code alone for 200 OK responses
-        if resp.status_code == 200 {
+        report.is_wildcard = if resp.status_code == 200 {
             // For 200 OK, require high confidence or multiple matches
-            confidence >= 0.7 || (match_count >= 3 && confidence >= 0.5)
+            confidence >= 0.7 || (report.match_count >= 3 && confidence >= 0.5)
         } else {
             // For non-200 status codes, be more aggressive
-            if self.common_status_codes.contains(&resp.status_code) {
-                confidence += 0.6;
-            }
-            confidence >= 0.5 || match_count >= 2
+            report.status_code =
+                contribution(self.common_status_codes.contains(&resp.status_code), 0.6);
+            confidence += report.status_code;
+            confidence >= 0.5 || report.match_count >= 2
+        };
+
+        report.confidence = f32::min(confidence, 1.0);
+        report
+    }
+}
+
+/// What each criterion of a wildcard profile contributed to the confidence of one sample.
+///
+/// A criterion that did not match contributes 0.0. Its `Display` lists the matched
+/// criteria, then the total.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WildcardScoreReport {
+    pub sha256: f32,
+    pub title: f32,
+    pub error_message: f32,
+    pub size: f32,
+    pub line_count: f32,
+    pub word_count: f32,
+    pub html_tag_count: f32,
+    /// Only counted for statuses other than 200.
+    pub status_code: f32,
+    /// The number of matched criteria, besides the hash and the status code.
+    pub match_count: usize,
+    /// The total confidence, capped at 1.0.
+    pub confidence: f32,
+    pub is_wildcard: bool,
+}
+
+impl fmt::Display for WildcardScoreReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let criteria = [
+            ("sha256", self.sha256),
+            ("title", self.title),
+            ("error message", self.error_message),
+            ("size", self.size),
+            ("line count", self.line_count),
+            ("word count", self.word_count),
+            ("tag count", self.html_tag_count),
+            ("status", self.status_code),
+        ];
+        let matched: Vec<String> = criteria
+            .iter()
+            .filter(|(_, weight)| *weight > 0.0)
+            .map(|(name, weight)| format!("{name} +{weight:.1}"))
+            .collect();
+        if matched.is_empty() {
+            write!(f, "no criteria matched")?;
+        } else {
+            write!(f, "{}", matched.join(", "))?;
         }
+        write!(
+            f,
+            " = {:.2} ({} matches), {}",
+            self.confidence,
+            self.match_count,
+            if self.is_wildcard {
+                "wildcard"
+            } else {
+                "not a wildcard"
+            }
+        )
     }
 }
 
 /// Represents the characteristics of a single HTTP response used for wildcard detection.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WildcardSample {
     pub size: usize,
     pub sha256: String,
@@ -259,7 +375,7 @@ impl WildcardSample {
     }
 }
 /// Computes the SHA256 hash of a string and returns it as a hex string.
-fn sha256_hex(content: &str) -> String {
+pub fn sha256_hex(content: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(content.as_bytes());
     format!("{:x}", hasher.finalize())
@@ -290,6 +406,12 @@ fn extract_patterns(html: &str) -> (Option<String>, Option<String>) {
     (title, error_message)
 }
 
+/// Returns the range within `fraction` of `value`, with the tolerance rounded up.
+fn tolerance_range(value: usize, fraction: f64) -> (usize, usize) {
+    let tol = (value as f64 * fraction).ceil() as usize;
+    (value.saturating_sub(tol), value + tol)
+}
+
 /// Counts the number of HTML tags in a string.
 fn count_html_tags(html: &str) -> usize {
     // --- Optimization ---
@@ -297,6 +419,151 @@ fn count_html_tags(html: &str) -> usize {
     HTML_TAG_REGEX.find_iter(html).count()
 }
 
+/// The number of responses `--smart-filter` learns its profile from.
+pub const SMART_FILTER_SAMPLES: usize = 20;
+
+/// The typical response of the target, learned from the first words of the scan with
+/// `--smart-filter`.
+///
+/// Responses with its status, a content length within 5% and a word count within 10% of
+/// the typical ones are filtered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartFilterProfile {
+    pub status: u16,
+    /// The content lengths filtered, unless the typical responses had none.
+    pub size_range: Option<(u64, u64)>,
+    /// The word counts filtered, unless they were not counted.
+    pub word_range: Option<(usize, usize)>,
+}
+
+impl SmartFilterProfile {
+    /// Learns the profile from sample responses: the modal status and, among the responses
+    /// with that status, the modal content length and word count. Rate-limited responses
+    /// are ignored.
+    ///
+    /// Returns `None` if no status is shared by more than half of the samples.
+    pub fn learn(responses: &[DetailedResponse]) -> Option<Self> {
+        let responses: Vec<&DetailedResponse> =
+            responses.iter().filter(|resp| resp.status != 429).collect();
+        let (status, count) = mode(responses.iter().map(|resp| resp.status))?;
+        if count * 2 <= responses.len() {
+            return None;
+        }
+
+        let typical: Vec<&&DetailedResponse> = responses
+            .iter()
+            .filter(|resp| resp.status == status)
+            .collect();
+        let size_range =
+            mode(typical.iter().filter_map(|resp| resp.content_length)).map(|(size, _)| {
+                let (min, max) = tolerance_range(size as usize, 0.05);
+                (min as u64, max as u64)
+            });
+        let word_range = mode(typical.iter().filter_map(|resp| resp.word_count))
+            .map(|(words, _)| tolerance_range(words, 0.1));
+        debug!(
+            status,
+            count,
+            ?size_range,
+            ?word_range,
+            "Learned smart filter"
+        );
+
+        Some(Self {
+            status,
+            size_range,
+            word_range,
+        })
+    }
+
+    /// Filters a result whose response looks like the typical response. Rate-limited
+    /// responses are kept.
+    pub fn apply(&self, result: BustResult) -> BustResult {
+        match result.response() {
+            Some(resp) if resp.status != 429 && self.matches(resp) => {
+                BustResult::Filtered(resp.clone())
+            }
+            _ => result,
+        }
+    }
+
+    /// Returns true if a response looks like the typical response.
+    pub fn matches(&self, resp: &DetailedResponse) -> bool {
+        resp.status == self.status
+            && self.size_range.is_none_or(|(min, max)| {
+                resp.content_length
+                    .is_some_and(|len| (min..=max).contains(&len))
+            })
+            && self.word_range.is_none_or(|(min, max)| {
+                resp.word_count
+                    .is_some_and(|words| (min..=max).contains(&words))
+            })
+    }
+}
+
+impl fmt::Display for SmartFilterProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "status {}", self.status)?;
+        if let Some((min, max)) = self.size_range {
+            write!(f, ", size {min}-{max}")?;
+        }
+        if let Some((min, max)) = self.word_range {
+            write!(f, ", words {min}-{max}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The results of the first words of a `--smart-filter` scan, held back until the profile
+/// is learned from them.
+#[derive(Debug, Default)]
+pub struct SmartFilterSample {
+    /// The results held back, per word.
+    held: Vec<Vec<BustResult>>,
+    responses: usize,
+}
+
+impl SmartFilterSample {
+    /// Holds back the results of a word. Returns true once `SMART_FILTER_SAMPLES` responses
+    /// are held, enough to learn the profile.
+    pub fn hold(&mut self, results: Vec<BustResult>) -> bool {
+        self.responses += results.iter().filter(|r| r.response().is_some()).count();
+        self.held.push(results);
+        self.responses >= SMART_FILTER_SAMPLES
+    }
+
+    /// Learns the profile from the held responses, and returns it with the held results,
+    /// filtered by it.
+    pub fn learn(self) -> (Option<SmartFilterProfile>, Vec<Vec<BustResult>>) {
+        let responses: Vec<DetailedResponse> = self
+            .held
+            .iter()
+            .flatten()
+            .filter_map(|result| result.response().cloned())
+            .collect();
+        let Some(profile) = SmartFilterProfile::learn(&responses) else {
+            return (None, self.held);
+        };
+        let held = self
+            .held
+            .into_iter()
+            .map(|results| results.into_iter().map(|r| profile.apply(r)).collect())
+            .collect();
+        (Some(profile), held)
+    }
+}
+
+/// Returns the most common value and how often it occurs, preferring the smallest on a tie.
+fn mode<T: Ord>(values: impl Iterator<Item = T>) -> Option<(T, usize)> {
+    let mut counts = BTreeMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .reduce(|best, next| if next.1 > best.1 { next } else { best })
+}
+
 /// Builds a `WildcardProfile` by sending requests to known non-existent paths.
 ///
 /// This function is called at the beginning of a scan to establish a baseline
@@ -316,7 +583,8 @@ pub async fn build_wildcard_profile(
 
     for path in test_paths {
         let url = format!("{}/{}", config.base_url.trim_end_matches('/'), path);
-        if let Ok(resp) = client.get(&url).send().await {
+        debug!(url, "Probing for wildcard responses");
+        if let Ok(resp) = get_request(client, &url, config).send().await {
             let status = resp.status().as_u16();
             let headers = resp
                 .headers()
@@ -332,10 +600,66 @@ pub async fn build_wildcard_profile(
         tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
     }
 
-    println!("Built wildcard profile with:");
-    println!("  - {} size ranges", profile.size_ranges.len());
-    println!("  - {} known hashes", profile.sha256_hashes.len());
-    println!("  - {} header keys", profile.header_patterns.len());
+    info!(
+        size_ranges = profile.size_ranges.len(),
+        known_hashes = profile.sha256_hashes.len(),
+        header_keys = profile.header_patterns.len(),
+        "Built wildcard profile"
+    );
 
     profile
 }
+
+/// Computes a word-level difference score between a body and a baseline body.
+///
+/// Both bodies are split into words and compared as multisets: 0 means the same words in
+/// any order, 1 means no word in common. Two empty bodies are identical.
+pub fn diff_score(body: &str, baseline: &str) -> f32 {
+    let mut baseline_words: HashMap<&str, usize> = HashMap::new();
+    let mut baseline_len = 0;
+    for word in baseline.split_whitespace() {
+        *baseline_words.entry(word).or_default() += 1;
+        baseline_len += 1;
+    }
+
+    let mut body_len = 0;
+    let mut common = 0;
+    for word in body.split_whitespace() {
+        body_len += 1;
+        if let Some(count) = baseline_words.get_mut(word)
+            && *count > 0
+        {
+            *count -= 1;
+            common += 1;
+        }
+    }
+
+    if body_len + baseline_len == 0 {
+        return 0.0;
+    }
+    1.0 - (2 * common) as f32 / (body_len + baseline_len) as f32
+}
+
+/// Fetches the body of the `--diff-baseline` path under the base URL.
+pub async fn fetch_baseline(
+    client: &reqwest::Client,
+    config: &ScanConfig,
+    path: &str,
+) -> Result<String, reqwest::Error> {
+    let url = format!(
+        "{}/{}",
+        config.base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    debug!(url, "Fetching diff baseline");
+    let body = get_request(client, &url, config)
+        .send()
+        .await?
+        .text()
+        .await?;
+    info!(
+        words = body.split_whitespace().count(),
+        "Fetched diff baseline"
+    );
+    Ok(body)
+}